@@ -0,0 +1,281 @@
+use core::fmt;
+use std::any::{Any, TypeId};
+use std::ptr;
+use std::mem::MaybeUninit;
+
+use crate::alloc::{AllocatorBase, DefaultAllocator};
+use crate::typed::DebugPtr;
+use crate::{Array, RawArray, Reflect, TypeInfo};
+
+// Type-erased array, items are dropped and cloned through the TypeInfo
+pub struct AnyArray<A = DefaultAllocator> where
+    A: AllocatorBase
+{
+    raw: RawArray<A>,
+    type_info: &'static TypeInfo,
+}
+
+impl AnyArray {
+    #[inline]
+    pub fn new<T: Reflect>() -> Self {
+        Self::custom_allocator(T::type_info())
+    }
+
+    #[inline]
+    pub fn with_capacity<T: Reflect>(capacity: usize) -> Self {
+        Self::custom_allocator_with_capacity(T::type_info(), capacity)
+    }
+
+    #[inline]
+    pub fn for_type_info(type_info: &'static TypeInfo) -> Self {
+        Self::custom_allocator(type_info)
+    }
+}
+
+impl<A: AllocatorBase> AnyArray<A> {
+    #[inline]
+    pub fn custom_allocator(type_info: &'static TypeInfo) -> Self {
+        let raw = unsafe{ RawArray::for_type_unchecked(*type_info.get_layout()) };
+        AnyArray { raw, type_info }
+    }
+
+    #[inline]
+    pub fn custom_allocator_with_capacity(type_info: &'static TypeInfo, capacity: usize) -> Self {
+        let raw = unsafe{ RawArray::for_type_with_capacity_unchecked(*type_info.get_layout(), capacity) };
+        AnyArray { raw, type_info }
+    }
+
+    #[inline]
+    pub fn get_type_info(&self) -> &'static TypeInfo {
+        self.type_info
+    }
+
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        *self.type_info.get_id() == TypeId::of::<T>()
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.raw.capacity()
+    }
+
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.raw.reserve(additional);
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.raw.num()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    #[inline]
+    pub fn push<T: Any>(&mut self, value: T) {
+        assert!(self.is::<T>(), "AnyArray of {} can't store {}", self.type_info.get_name(), std::any::type_name::<T>());
+        unsafe {
+            self.raw.allocate_back(|ptr| ptr::write(ptr.cast::<T>(), value));
+        }
+    }
+
+    /// Moves the item pointed by src at the end of the array.
+    ///
+    /// # Safety
+    /// `src` must point to a valid, initialized value of the array type, readable for its size.
+    /// The array takes ownership of the value: the caller must not use or drop it afterwards.
+    #[inline]
+    pub unsafe fn push_from_ptr(&mut self, src: *const u8) {
+        let size = self.type_info.get_layout().size();
+        self.raw.allocate_back(|ptr| ptr::copy_nonoverlapping(src, ptr, size));
+    }
+
+    /// Clones the item pointed by src at the end, false if the type has no clone function.
+    ///
+    /// # Safety
+    /// `src` must point to a valid, initialized and aligned value of the array type. It stays owned by the caller.
+    pub unsafe fn push_clone_from_ptr(&mut self, src: *const u8) -> bool {
+        if !self.type_info.can_clone() {
+            return false;
+        }
+        let type_info = self.type_info;
+        self.raw.allocate_back(|ptr| { type_info.clone_into(src, ptr); });
+        true
+    }
+
+    // False if the type has no default function
+    pub fn push_default(&mut self) -> bool {
+        if !self.type_info.can_default() {
+            return false;
+        }
+        let type_info = self.type_info;
+        unsafe {
+            self.raw.allocate_back(|ptr| { type_info.default_into(ptr); });
+        }
+        true
+    }
+
+    #[inline]
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T> {
+        if !self.is::<T>() || index >= self.num() {
+            return None;
+        }
+        Some(unsafe{ &*self.raw.get_ptr(index).cast::<T>() })
+    }
+
+    #[inline]
+    pub fn get_mut<T: Any>(&mut self, index: usize) -> Option<&mut T> {
+        if !self.is::<T>() || index >= self.num() {
+            return None;
+        }
+        Some(unsafe{ &mut *self.raw.get_ptr_mut(index).cast::<T>() })
+    }
+
+    #[inline]
+    pub fn get_ptr(&self, index: usize) -> *const u8 {
+        assert!(index < self.num());
+        unsafe{ self.raw.get_ptr(index) }
+    }
+
+    #[inline]
+    pub fn get_ptr_mut(&mut self, index: usize) -> *mut u8 {
+        assert!(index < self.num());
+        unsafe{ self.raw.get_ptr_mut(index) }
+    }
+
+    // Start of the items storage, dangling when the array never allocated
+    #[inline]
+    pub fn get_base_ptr(&self) -> *mut u8 {
+        self.raw.as_ptr() as *mut u8
+    }
+
+    #[inline]
+    pub fn as_slice<T: Any>(&self) -> Option<&[T]> {
+        if !self.is::<T>() {
+            return None;
+        }
+        Some(unsafe{ std::slice::from_raw_parts(self.raw.as_ptr().cast::<T>(), self.num()) })
+    }
+
+    #[inline]
+    pub fn as_mut_slice<T: Any>(&mut self) -> Option<&mut [T]> {
+        if !self.is::<T>() {
+            return None;
+        }
+        let items_num = self.num();
+        Some(unsafe{ std::slice::from_raw_parts_mut(self.raw.as_mut_ptr().cast::<T>(), items_num) })
+    }
+
+    // Typed view of the same storage, Array is transparent over RawArray
+    #[inline]
+    pub fn downcast_ref<T: Any + Unpin>(&self) -> Option<&Array<T, A>> {
+        if !self.is::<T>() {
+            return None;
+        }
+        Some(unsafe{ &*(&self.raw as *const RawArray<A>).cast::<Array<T, A>>() })
+    }
+
+    #[inline]
+    pub fn downcast_mut<T: Any + Unpin>(&mut self) -> Option<&mut Array<T, A>> {
+        if !self.is::<T>() {
+            return None;
+        }
+        Some(unsafe{ &mut *(&mut self.raw as *mut RawArray<A>).cast::<Array<T, A>>() })
+    }
+
+    pub fn swap_remove(&mut self, index: usize) {
+        assert!(index < self.num());
+        let type_info = self.type_info;
+        unsafe {
+            self.raw.swap_remove(index, |ptr| type_info.drop_in_place(ptr));
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        assert!(index < self.num());
+        let type_info = self.type_info;
+        unsafe {
+            self.raw.remove(index, |ptr| type_info.drop_in_place(ptr));
+        }
+    }
+
+    /// Moves the item out in dst without dropping it, the last item takes its place.
+    ///
+    /// # Safety
+    /// `dst` must be valid for writes of the array type size, aligned for it and not overlap the array.
+    /// Whatever dst held is overwritten without being dropped, the caller owns the moved item and must drop it.
+    pub unsafe fn swap_remove_to_ptr(&mut self, index: usize, dst: *mut u8) {
+        assert!(index < self.num());
+        let size = self.type_info.get_layout().size();
+        self.raw.swap_remove(index, |ptr| ptr::copy_nonoverlapping(ptr, dst, size));
+    }
+
+    // Moves the item at the end of another array of the same type
+    pub fn swap_remove_into<B: AllocatorBase>(&mut self, index: usize, dst: &mut AnyArray<B>) {
+        assert!(index < self.num());
+        assert!(self.type_info.get_id() == dst.type_info.get_id());
+        let size = self.type_info.get_layout().size();
+        unsafe {
+            dst.raw.allocate_back(|dst_ptr| {
+                self.raw.swap_remove(index, |src_ptr| ptr::copy_nonoverlapping(src_ptr, dst_ptr, size));
+            });
+        }
+    }
+
+    pub fn take<T: Any>(&mut self, index: usize) -> Option<T> {
+        if !self.is::<T>() || index >= self.num() {
+            return None;
+        }
+        let mut tmp = MaybeUninit::<T>::uninit();
+        unsafe {
+            self.swap_remove_to_ptr(index, tmp.as_mut_ptr().cast());
+            Some(tmp.assume_init())
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let type_info = self.type_info;
+        let item_size = type_info.get_layout().size();
+        unsafe {
+            self.raw.clear(|ptr, num| {
+                for i in 0..num {
+                    type_info.drop_in_place(ptr.add(i * item_size));
+                }
+            });
+        }
+    }
+
+    // None if the type has no clone function
+    pub fn try_clone(&self) -> Option<Self> {
+        if !self.type_info.can_clone() {
+            return None;
+        }
+
+        let mut cloned = Self::custom_allocator_with_capacity(self.type_info, self.num());
+        for i in 0..self.num() {
+            let src = self.get_ptr(i);
+            unsafe {
+                cloned.raw.allocate_back(|dst| { self.type_info.clone_into(src, dst); });
+            }
+        }
+        Some(cloned)
+    }
+}
+
+impl<A: AllocatorBase> Drop for AnyArray<A> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<A: AllocatorBase> fmt::Debug for AnyArray<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.num()).map(|i| DebugPtr(self.type_info, self.get_ptr(i))))
+            .finish()
+    }
+}
@@ -0,0 +1,520 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::alloc::{AllocatorBase, ArrayAllocator};
+use crate::{Array, HashMixer, KeyValuePair, Map, NameIndex, NameTableReader, NameTableWriter, RawSetEntry, Set, SetItem, SetKey, StringAtom, xxh64_hash};
+
+// Layout (little endian):
+// header   u32 magic, u32 format version
+// payload  values and chunks (u8[4] tag, u64 size, body)
+// names    name table of the atoms used in the payload
+// footer   u64 names offset, u64 xxh64 checksum of everything before it
+const ARCHIVE_MAGIC: [u8; 4] = *b"RLAR";
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+const ARCHIVE_HEADER_SIZE: usize = 8;
+const ARCHIVE_FOOTER_SIZE: usize = 16;
+const CHUNK_HEADER_SIZE: usize = 12;
+const CHECKSUM_SEED: u64 = 0x524c_4152;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    UnexpectedEnd,
+    InvalidData(&'static str),
+    ChecksumMismatch,
+    NewerVersion { type_name: &'static str, version: u32, supported: u32 },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Io(error) => write!(f, "{}", error),
+            ArchiveError::UnexpectedEnd => write!(f, "Unexpected end of archive"),
+            ArchiveError::InvalidData(message) => write!(f, "Invalid archive data: {}", message),
+            ArchiveError::ChecksumMismatch => write!(f, "Archive checksum mismatch"),
+            ArchiveError::NewerVersion { type_name, version, supported } =>
+                write!(f, "{} saved with version {}, only versions up to {} can be loaded", type_name, version, supported),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError { }
+
+impl From<io::Error> for ArchiveError {
+    fn from(error: io::Error) -> Self {
+        ArchiveError::Io(error)
+    }
+}
+
+pub type ArchiveResult<T> = Result<T, ArchiveError>;
+
+/// Bidirectional serializer: the same `serialize` code reads when loading and writes when saving
+pub trait Archive : Sized {
+    fn is_loading(&self) -> bool;
+
+    #[inline]
+    fn is_saving(&self) -> bool {
+        !self.is_loading()
+    }
+
+    // Fills the bytes when loading, writes them when saving
+    fn serialize_bytes(&mut self, bytes: &mut [u8]) -> ArchiveResult<()>;
+
+    fn serialize_name(&mut self, name: &mut StringAtom) -> ArchiveResult<()>;
+
+    // Bytes left to load in the current chunk or payload, bounds the lengths read before allocating
+    fn remaining_bytes(&self) -> usize;
+
+    /// Size-prefixed block, unknown chunks are skipped by the readers and a chunk is left at its end
+    /// even if `f` didn't read all of it. When loading, chunks before the requested one are skipped
+    /// and false is returned if it isn't found before the end of the enclosing chunk.
+    fn chunk<F>(&mut self, tag: [u8; 4], f: F) -> ArchiveResult<bool>
+    where
+        F: FnOnce(&mut Self) -> ArchiveResult<()>;
+
+    // Version of the value being serialized by `serialize_versioned`, 0 outside of it
+    fn get_version(&self) -> u32;
+
+    #[doc(hidden)]
+    fn set_version(&mut self, version: u32);
+
+    #[inline]
+    fn serialize<T: Serializable + ?Sized>(&mut self, value: &mut T) -> ArchiveResult<()> {
+        value.serialize(self)
+    }
+
+    /// Prefixes the value with its type version, older data is loaded then upgraded
+    fn serialize_versioned<T: Serializable>(&mut self, value: &mut T) -> ArchiveResult<()> {
+        let mut version = T::VERSION;
+        self.serialize(&mut version)?;
+        if version > T::VERSION {
+            return Err(ArchiveError::NewerVersion { type_name: std::any::type_name::<T>(), version, supported: T::VERSION });
+        }
+
+        let outer_version = self.get_version();
+        self.set_version(version);
+        let result = value.serialize(self);
+        self.set_version(outer_version);
+        result?;
+
+        if self.is_loading() && version < T::VERSION {
+            value.upgrade(version);
+        }
+        Ok(())
+    }
+
+    // Collections lengths are written as u32
+    fn serialize_len(&mut self, len: &mut usize) -> ArchiveResult<()> {
+        let mut len32 = u32::try_from(*len).map_err(|_| ArchiveError::InvalidData("collection too big"))?;
+        self.serialize(&mut len32)?;
+        *len = len32 as usize;
+        Ok(())
+    }
+}
+
+/// Something an Archive can load in place and save
+pub trait Serializable {
+    // Bumped when the serialized layout changes, see Archive::serialize_versioned
+    const VERSION: u32 = 0;
+
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()>;
+
+    // Called after loading data saved with an older version
+    fn upgrade(&mut self, _from_version: u32) { }
+}
+
+pub struct ArchiveWriter {
+    data: Array<u8>,
+    names: NameTableWriter,
+    chunks: Array<usize>, // offsets of the sizes of the open chunks
+    version: u32,
+}
+
+impl ArchiveWriter {
+    pub fn new() -> Self {
+        let mut data = Array::new();
+        data.extend_from_slice(&ARCHIVE_MAGIC);
+        data.extend_from_slice(&ARCHIVE_FORMAT_VERSION.to_le_bytes());
+        ArchiveWriter { data, names: NameTableWriter::new(), chunks: Array::new(), version: 0 }
+    }
+
+    /// Appends the name table and the footer
+    pub fn finish(mut self) -> Array<u8> {
+        assert!(self.chunks.is_empty(), "Finishing an archive with open chunks");
+        let names_offset = self.data.num() as u64;
+        let mut names = Array::new();
+        self.names.write_to(&mut ArrayWriter(&mut names)).unwrap();
+        self.data.extend_from_slice(&names);
+        self.data.extend_from_slice(&names_offset.to_le_bytes());
+        let checksum = xxh64_hash(&self.data, CHECKSUM_SEED);
+        self.data.extend_from_slice(&checksum.to_le_bytes());
+        self.data
+    }
+
+    pub fn save_to_file(self, path: impl AsRef<Path>) -> ArchiveResult<()> {
+        fs::write(path, self.finish().as_slice())?;
+        Ok(())
+    }
+}
+
+impl Default for ArchiveWriter {
+    fn default() -> Self {
+        ArchiveWriter::new()
+    }
+}
+
+struct ArrayWriter<'a>(&'a mut Array<u8>);
+
+impl io::Write for ArrayWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Archive for ArchiveWriter {
+    #[inline]
+    fn is_loading(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn serialize_bytes(&mut self, bytes: &mut [u8]) -> ArchiveResult<()> {
+        self.data.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    // Nothing to load
+    #[inline]
+    fn remaining_bytes(&self) -> usize {
+        usize::MAX
+    }
+
+    fn serialize_name(&mut self, name: &mut StringAtom) -> ArchiveResult<()> {
+        let name_index = self.names.add(*name);
+        self.serialize(&mut { name_index.index })?;
+        self.serialize(&mut { name_index.number })
+    }
+
+    fn chunk<F>(&mut self, mut tag: [u8; 4], f: F) -> ArchiveResult<bool>
+    where
+        F: FnOnce(&mut Self) -> ArchiveResult<()>
+    {
+        self.serialize_bytes(&mut tag)?;
+        let size_offset = self.data.num();
+        self.serialize(&mut 0u64)?;
+
+        self.chunks.push_back(size_offset);
+        let result = f(self);
+        self.chunks.pop_back();
+        result?;
+
+        let size = (self.data.num() - size_offset - 8) as u64;
+        self.data[size_offset..size_offset + 8].copy_from_slice(&size.to_le_bytes());
+        Ok(true)
+    }
+
+    #[inline]
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+}
+
+pub struct ArchiveReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    ends: Array<usize>, // end of the payload then of every open chunk
+    names: NameTableReader,
+    version: u32,
+}
+
+impl<'a> ArchiveReader<'a> {
+    /// Checks the header and the checksum, then loads the name table
+    pub fn new(data: &'a [u8]) -> ArchiveResult<Self> {
+        if data.len() < ARCHIVE_HEADER_SIZE + ARCHIVE_FOOTER_SIZE {
+            return Err(ArchiveError::UnexpectedEnd);
+        }
+        if data[..4] != ARCHIVE_MAGIC {
+            return Err(ArchiveError::InvalidData("not an archive"));
+        }
+        if u32::from_le_bytes(data[4..8].try_into().unwrap()) != ARCHIVE_FORMAT_VERSION {
+            return Err(ArchiveError::InvalidData("unsupported archive format version"));
+        }
+
+        let footer = data.len() - ARCHIVE_FOOTER_SIZE;
+        let checksum = u64::from_le_bytes(data[footer + 8..].try_into().unwrap());
+        if xxh64_hash(&data[..footer + 8], CHECKSUM_SEED) != checksum {
+            return Err(ArchiveError::ChecksumMismatch);
+        }
+
+        let names_offset = u64::from_le_bytes(data[footer..footer + 8].try_into().unwrap()) as usize;
+        if names_offset < ARCHIVE_HEADER_SIZE || names_offset > footer {
+            return Err(ArchiveError::InvalidData("invalid name table offset"));
+        }
+        let names = NameTableReader::read_from(&mut &data[names_offset..footer])?;
+
+        let mut ends = Array::new();
+        ends.push_back(names_offset);
+        Ok(ArchiveReader { data, position: ARCHIVE_HEADER_SIZE, ends, names, version: 0 })
+    }
+
+    // True when the current chunk, or the payload, was entirely read
+    #[inline]
+    pub fn is_at_end(&self) -> bool {
+        self.position == self.get_end()
+    }
+
+    #[inline]
+    fn get_end(&self) -> usize {
+        *self.ends.last().unwrap()
+    }
+
+    fn read_array<const N: usize>(&mut self) -> ArchiveResult<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.serialize_bytes(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl Archive for ArchiveReader<'_> {
+    #[inline]
+    fn is_loading(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn serialize_bytes(&mut self, bytes: &mut [u8]) -> ArchiveResult<()> {
+        let end = self.position + bytes.len();
+        if end > self.get_end() {
+            return Err(ArchiveError::UnexpectedEnd);
+        }
+        bytes.copy_from_slice(&self.data[self.position..end]);
+        self.position = end;
+        Ok(())
+    }
+
+    #[inline]
+    fn remaining_bytes(&self) -> usize {
+        self.get_end() - self.position
+    }
+
+    fn serialize_name(&mut self, name: &mut StringAtom) -> ArchiveResult<()> {
+        let index = u32::from_le_bytes(self.read_array()?);
+        let number = u32::from_le_bytes(self.read_array()?);
+        *name = self.names.resolve(NameIndex { index, number }).ok_or(ArchiveError::InvalidData("invalid name index"))?;
+        Ok(())
+    }
+
+    fn chunk<F>(&mut self, tag: [u8; 4], f: F) -> ArchiveResult<bool>
+    where
+        F: FnOnce(&mut Self) -> ArchiveResult<()>
+    {
+        let start = self.position;
+        let end = self.get_end();
+        while self.position + CHUNK_HEADER_SIZE <= end {
+            let chunk_tag = self.read_array::<4>()?;
+            let size = u64::from_le_bytes(self.read_array()?);
+            let chunk_end = usize::try_from(size).ok()
+                .and_then(|size| self.position.checked_add(size))
+                .filter(|&chunk_end| chunk_end <= end)
+                .ok_or(ArchiveError::InvalidData("chunk bigger than its parent"))?;
+
+            if chunk_tag == tag {
+                self.ends.push_back(chunk_end);
+                let result = f(self);
+                self.ends.pop_back();
+                result?;
+                self.position = chunk_end;
+                return Ok(true);
+            }
+            self.position = chunk_end;
+        }
+
+        self.position = start;
+        Ok(false)
+    }
+
+    #[inline]
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+}
+
+macro_rules! impl_serializable_number {
+    ($($t:ty),*) => {
+        $(
+            impl Serializable for $t {
+                #[inline]
+                fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+                    let mut bytes = self.to_le_bytes();
+                    ar.serialize_bytes(&mut bytes)?;
+                    *self = <$t>::from_le_bytes(bytes);
+                    Ok(())
+                }
+            }
+        )*
+    }
+}
+
+impl_serializable_number!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+// Saved as 64 bits so archives are portable
+impl Serializable for usize {
+    #[inline]
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut value = *self as u64;
+        ar.serialize(&mut value)?;
+        *self = usize::try_from(value).map_err(|_| ArchiveError::InvalidData("usize overflow"))?;
+        Ok(())
+    }
+}
+
+impl Serializable for bool {
+    #[inline]
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut value = *self as u8;
+        ar.serialize(&mut value)?;
+        *self = match value {
+            0 => false,
+            1 => true,
+            _ => return Err(ArchiveError::InvalidData("invalid bool")),
+        };
+        Ok(())
+    }
+}
+
+impl Serializable for String {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut len = self.len();
+        ar.serialize_len(&mut len)?;
+        if ar.is_loading() {
+            if len > ar.remaining_bytes() {
+                return Err(ArchiveError::UnexpectedEnd);
+            }
+            let mut bytes = vec![0u8; len];
+            ar.serialize_bytes(&mut bytes)?;
+            *self = String::from_utf8(bytes).map_err(|_| ArchiveError::InvalidData("string is not valid utf8"))?;
+            Ok(())
+        } else {
+            let mut bytes = self.as_bytes().to_vec();
+            ar.serialize_bytes(&mut bytes)
+        }
+    }
+}
+
+impl Serializable for StringAtom {
+    #[inline]
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        ar.serialize_name(self)
+    }
+}
+
+impl<T: Serializable, const N: usize> Serializable for [T; N] {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        for item in self.iter_mut() {
+            item.serialize(ar)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, A> Serializable for Array<T, A> where
+    T: Serializable + Default + Unpin,
+    A: AllocatorBase
+{
+    fn serialize<Ar: Archive>(&mut self, ar: &mut Ar) -> ArchiveResult<()> {
+        let mut len = self.num();
+        ar.serialize_len(&mut len)?;
+        if ar.is_loading() {
+            self.clear();
+            for _ in 0..len {
+                let mut item = T::default();
+                item.serialize(ar)?;
+                self.push_back(item);
+            }
+            Ok(())
+        } else {
+            self.iter_mut().try_for_each(|item| item.serialize(ar))
+        }
+    }
+}
+
+// Items are saved from clones, the stored ones can't be mutably borrowed
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Serializable for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    T: SetItem + Serializable + Default + Clone,
+    DataAlloc: ArrayAllocator<T>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut len = self.num();
+        ar.serialize_len(&mut len)?;
+        if ar.is_loading() {
+            self.clear();
+            for _ in 0..len {
+                let mut item = T::default();
+                item.serialize(ar)?;
+                self.insert(item);
+            }
+            Ok(())
+        } else {
+            self.iter().try_for_each(|item| item.clone().serialize(ar))
+        }
+    }
+}
+
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Serializable for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    K: SetKey + Serializable + Default + Clone,
+    V: Serializable + Default + Unpin,
+    DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut len = self.num();
+        ar.serialize_len(&mut len)?;
+        if ar.is_loading() {
+            self.clear();
+            for _ in 0..len {
+                let mut key = K::default();
+                let mut value = V::default();
+                key.serialize(ar)?;
+                value.serialize(ar)?;
+                self.insert(key, value);
+            }
+            Ok(())
+        } else {
+            self.iter_mut().try_for_each(|(key, value)| {
+                key.clone().serialize(ar)?;
+                value.serialize(ar)
+            })
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra-glm")]
+impl<T, const R: usize, const C: usize> Serializable for nalgebra_glm::TMat<T, R, C> where
+    T: Serializable + nalgebra_glm::Scalar
+{
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        self.as_mut_slice().iter_mut().try_for_each(|value| value.serialize(ar))
+    }
+}
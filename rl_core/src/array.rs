@@ -1,3 +1,4 @@
+use core::fmt;
 use std::ptr::{self};
 use std::mem::MaybeUninit;
 use std::marker::PhantomData;
@@ -9,6 +10,8 @@ use crate::RawArray;
 
 pub type InlineArray<T, const N: usize> = Array<T, InlineAllocator<N, T>>;
 
+// Transparent so a type-erased RawArray can be viewed as a typed Array
+#[repr(transparent)]
 pub struct Array<T, A = DefaultAllocator>(RawArray<A>, PhantomData<T>) where
     T: Unpin,
     A: AllocatorBase;
@@ -25,6 +28,10 @@ impl<T: Unpin> Array<T> {
     }
 }
 
+// Owns its items like a Vec does
+unsafe impl<T: Unpin + Send, A: AllocatorBase + Send> Send for Array<T, A> { }
+unsafe impl<T: Unpin + Sync, A: AllocatorBase + Sync> Sync for Array<T, A> { }
+
 impl<T, A> Array<T, A> where
     T: Unpin,
     A: AllocatorBase
@@ -125,6 +132,13 @@ impl<T, A> Array<T, A> where
         }
     }
 
+    pub fn extend_from_slice(&mut self, values: &[T]) where T : Clone {
+        self.reserve(values.len());
+        for value in values {
+            self.push_back(value.clone());
+        }
+    }
+
     #[inline]
     pub fn swap_remove(&mut self, index: usize) -> T {
         let mut tmp = MaybeUninit::<T>::uninit();
@@ -157,6 +171,13 @@ impl<T, A> Array<T, A> where
         self.swap_remove(self.num() - 1)
     }
 
+    // Drops the items past `num`, keeps the capacity
+    pub fn truncate(&mut self, num: usize) {
+        while self.num() > num {
+            self.pop_back();
+        }
+    }
+
     #[inline]
     pub fn clear(&mut self) {
         unsafe {
@@ -315,3 +336,37 @@ impl<T: Unpin> Default for Array<T>
         Array::new()
     }
 }
+
+impl<T, A> Clone for Array<T, A> where
+    T: Unpin + Clone,
+    A: AllocatorBase
+{
+    fn clone(&self) -> Self {
+        self.iter().collect()
+    }
+}
+
+impl<T, A, B> PartialEq<Array<T, B>> for Array<T, A> where
+    T: Unpin + PartialEq,
+    A: AllocatorBase,
+    B: AllocatorBase
+{
+    fn eq(&self, other: &Array<T, B>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T, A> Eq for Array<T, A> where
+    T: Unpin + Eq,
+    A: AllocatorBase
+{
+}
+
+impl<T, A> fmt::Debug for Array<T, A> where
+    T: Unpin + fmt::Debug,
+    A: AllocatorBase
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,541 @@
+use std::any::{Any, TypeId, type_name};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, Weak};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{ArchiveError, Array, JobCounter, JobSystem, Map, StringAtom, Vfs, VfsError};
+
+#[derive(Clone, Debug)]
+pub enum AssetError {
+    InvalidPath(String),
+    NoLoader(&'static str),
+    Vfs(Arc<VfsError>),
+    Load(String),
+    Dependency { path: StringAtom, error: Box<AssetError> },
+    Panicked,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::InvalidPath(path) => write!(f, "Invalid asset path '{}'", path),
+            AssetError::NoLoader(type_name) => write!(f, "No loader registered for {}", type_name),
+            AssetError::Vfs(error) => write!(f, "{}", error),
+            AssetError::Load(message) => write!(f, "{}", message),
+            AssetError::Dependency { path, error } => write!(f, "Dependency {}: {}", path, error),
+            AssetError::Panicked => write!(f, "The loader panicked"),
+        }
+    }
+}
+
+impl std::error::Error for AssetError { }
+
+impl From<VfsError> for AssetError {
+    fn from(error: VfsError) -> Self {
+        AssetError::Vfs(Arc::new(error))
+    }
+}
+
+// Archives are the usual format of the loaded files
+impl From<ArchiveError> for AssetError {
+    fn from(error: ArchiveError) -> Self {
+        AssetError::Load(error.to_string())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum LoadState {
+    Pending,
+    Loaded,
+    Failed(AssetError),
+}
+
+/// Builds the assets of one type from the bytes of their file
+pub trait AssetLoader : Send + Sync + 'static {
+    type Asset : Send + Sync + 'static;
+
+    fn load(&self, data: &[u8], context: &mut LoadContext) -> Result<Self::Asset, AssetError>;
+}
+
+type LoadFn<T> = dyn Fn(&[u8], &mut LoadContext) -> Result<T, AssetError> + Send + Sync;
+
+/// Given to loaders, the assets loaded through it are dependencies kept alive by the loaded asset
+pub struct LoadContext {
+    manager: AssetManager,
+    path: StringAtom,
+    dependencies: Array<UntypedHandle>,
+}
+
+impl LoadContext {
+    #[inline]
+    pub fn get_path(&self) -> StringAtom {
+        self.path
+    }
+
+    #[inline]
+    pub fn get_manager(&self) -> &AssetManager {
+        &self.manager
+    }
+
+    pub fn load_dependency<T: Send + Sync + 'static>(&mut self, path: &str) -> AssetHandle<T> {
+        let handle = self.manager.load::<T>(path);
+        self.dependencies.push_back(handle.untyped());
+        handle
+    }
+}
+
+// Type-erased part of the slots, for dependencies and untyped handles
+trait ErasedSlot : Any + Send + Sync {
+    fn get_path(&self) -> StringAtom;
+
+    fn get_type_name(&self) -> &'static str;
+
+    fn get_state(&self) -> LoadState;
+
+    fn get_dependencies(&self) -> Array<UntypedHandle>;
+
+    fn add_dependent(&self, dependent: Weak<dyn ErasedSlot>);
+
+    fn get_dependents(&self) -> Array<UntypedHandle>;
+
+    // Once per reload generation, so dependency cycles stop
+    fn reload(self: Arc<Self>, manager: &AssetManager, generation: u64);
+}
+
+enum SlotState<T> {
+    Pending,
+    Loaded(Arc<T>),
+    Failed(AssetError),
+}
+
+struct AssetSlot<T> {
+    key: (TypeId, StringAtom),
+    state: RwLock<SlotState<T>>,
+    loading: Mutex<JobCounter>,
+    dependencies: Mutex<Array<UntypedHandle>>,
+    dependents: Mutex<Array<Weak<dyn ErasedSlot>>>,
+    version: AtomicU32,
+    reload_error: Mutex<Option<AssetError>>,
+    reload_generation: AtomicU64,
+    shared: Weak<AssetsShared>,
+}
+
+impl<T: Send + Sync + 'static> ErasedSlot for AssetSlot<T> {
+    fn get_path(&self) -> StringAtom {
+        self.key.1
+    }
+
+    fn get_type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn get_state(&self) -> LoadState {
+        match &*self.state.read().unwrap() {
+            SlotState::Pending => LoadState::Pending,
+            SlotState::Loaded(_) => LoadState::Loaded,
+            SlotState::Failed(error) => LoadState::Failed(error.clone()),
+        }
+    }
+
+    fn get_dependencies(&self) -> Array<UntypedHandle> {
+        self.dependencies.lock().unwrap().clone()
+    }
+
+    fn add_dependent(&self, dependent: Weak<dyn ErasedSlot>) {
+        let mut dependents = self.dependents.lock().unwrap();
+        let mut index = 0;
+        while index < dependents.num() {
+            match dependents[index].strong_count() > 0 {
+                true => index += 1,
+                false => { dependents.swap_remove(index); }
+            }
+        }
+        if !dependents.iter().any(|other| Weak::ptr_eq(other, &dependent)) {
+            dependents.push_back(dependent);
+        }
+    }
+
+    fn get_dependents(&self) -> Array<UntypedHandle> {
+        self.dependents.lock().unwrap().iter().filter_map(|dependent| dependent.upgrade().map(UntypedHandle)).collect()
+    }
+
+    fn reload(self: Arc<Self>, manager: &AssetManager, generation: u64) {
+        if self.reload_generation.swap(generation, Ordering::AcqRel) != generation {
+            manager.spawn_load(&self, Some(generation));
+        }
+    }
+}
+
+// Unloaded with the last handle, the registry entry goes with it unless the path was loaded again meanwhile
+impl<T> Drop for AssetSlot<T> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.upgrade() {
+            let mut assets = shared.assets.lock().unwrap();
+            if assets.get(&self.key).is_some_and(|slot| slot.strong_count() == 0) {
+                assets.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Reference counted handle of an asset, loading in the background or loaded
+pub struct AssetHandle<T: Send + Sync + 'static> {
+    slot: Arc<AssetSlot<T>>,
+}
+
+impl<T: Send + Sync + 'static> AssetHandle<T> {
+    #[inline]
+    pub fn get_path(&self) -> StringAtom {
+        self.slot.key.1
+    }
+
+    #[inline]
+    pub fn get_state(&self) -> LoadState {
+        self.slot.get_state()
+    }
+
+    /// The state of the asset combined with the states of its dependencies, the first failure found wins
+    pub fn get_state_with_dependencies(&self) -> LoadState {
+        self.untyped().get_state_with_dependencies()
+    }
+
+    #[inline]
+    pub fn is_loaded(&self) -> bool {
+        matches!(&*self.slot.state.read().unwrap(), SlotState::Loaded(_))
+    }
+
+    // None until loaded or when the load failed
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*self.slot.state.read().unwrap() {
+            SlotState::Loaded(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    /// Waits for the asset, not its dependencies, running other jobs meanwhile
+    pub fn wait(&self) -> LoadState {
+        let counter = self.slot.loading.lock().unwrap().clone();
+        if let Some(shared) = self.slot.shared.upgrade() {
+            counter.wait(shared.job_system);
+        }
+        self.get_state()
+    }
+
+    // Incremented every time the asset is loaded, reloads included
+    #[inline]
+    pub fn get_version(&self) -> u32 {
+        self.slot.version.load(Ordering::Acquire)
+    }
+
+    /// Error of the last reload, the asset loaded before it is kept
+    pub fn get_reload_error(&self) -> Option<AssetError> {
+        self.slot.reload_error.lock().unwrap().clone()
+    }
+
+    #[inline]
+    pub fn get_dependencies(&self) -> Array<UntypedHandle> {
+        self.slot.get_dependencies()
+    }
+
+    #[inline]
+    pub fn get_dependents(&self) -> Array<UntypedHandle> {
+        self.slot.get_dependents()
+    }
+
+    // Handles of the same asset included
+    #[inline]
+    pub fn refs_num(&self) -> usize {
+        Arc::strong_count(&self.slot)
+    }
+
+    #[inline]
+    pub fn untyped(&self) -> UntypedHandle {
+        UntypedHandle(self.slot.clone())
+    }
+}
+
+impl<T: Send + Sync + 'static> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self {
+        AssetHandle { slot: self.slot.clone() }
+    }
+}
+
+impl<T: Send + Sync + 'static> PartialEq for AssetHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+impl<T: Send + Sync + 'static> fmt::Debug for AssetHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AssetHandle<{}>({}, {:?})", type_name::<T>(), self.get_path(), self.get_state())
+    }
+}
+
+/// Handle of an asset of any type
+#[derive(Clone)]
+pub struct UntypedHandle(Arc<dyn ErasedSlot>);
+
+impl UntypedHandle {
+    #[inline]
+    pub fn get_path(&self) -> StringAtom {
+        self.0.get_path()
+    }
+
+    #[inline]
+    pub fn get_type_name(&self) -> &'static str {
+        self.0.get_type_name()
+    }
+
+    #[inline]
+    pub fn get_state(&self) -> LoadState {
+        self.0.get_state()
+    }
+
+    pub fn get_state_with_dependencies(&self) -> LoadState {
+        let mut visited = Array::new();
+        self.combine_states(&mut visited)
+    }
+
+    // Dependency cycles are visited once
+    fn combine_states(&self, visited: &mut Array<*const ()>) -> LoadState {
+        let address = Arc::as_ptr(&self.0) as *const ();
+        if visited.contains(&address) {
+            return LoadState::Loaded;
+        }
+        visited.push_back(address);
+
+        let state = self.get_state();
+        if !matches!(state, LoadState::Loaded) {
+            return state;
+        }
+        let mut pending = false;
+        for dependency in self.get_dependencies().iter() {
+            match dependency.combine_states(visited) {
+                LoadState::Failed(error) => return LoadState::Failed(AssetError::Dependency { path: dependency.get_path(), error: Box::new(error) }),
+                LoadState::Pending => pending = true,
+                LoadState::Loaded => (),
+            }
+        }
+        match pending {
+            true => LoadState::Pending,
+            false => LoadState::Loaded,
+        }
+    }
+
+    #[inline]
+    pub fn get_dependencies(&self) -> Array<UntypedHandle> {
+        self.0.get_dependencies()
+    }
+
+    #[inline]
+    pub fn get_dependents(&self) -> Array<UntypedHandle> {
+        self.0.get_dependents()
+    }
+
+    pub fn typed<T: Send + Sync + 'static>(&self) -> Option<AssetHandle<T>> {
+        let slot: Arc<dyn Any + Send + Sync> = self.0.clone();
+        slot.downcast::<AssetSlot<T>>().ok().map(|slot| AssetHandle { slot })
+    }
+}
+
+impl PartialEq for UntypedHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for UntypedHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UntypedHandle<{}>({}, {:?})", self.get_type_name(), self.get_path(), self.get_state())
+    }
+}
+
+struct AssetsShared {
+    vfs: &'static Vfs,
+    job_system: &'static JobSystem,
+    loaders: RwLock<Map<TypeId, Box<dyn Any + Send + Sync>>>, // Arc<LoadFn<T>> by asset type
+    assets: Mutex<Map<(TypeId, StringAtom), Weak<dyn ErasedSlot>>>,
+    reload_generation: AtomicU64,
+}
+
+/// Loads assets on the job system from their virtual path ("scenes:/level.scene"), the path atom being their id.
+/// Loading the same path and type again returns the same asset while one of its handles is alive.
+#[derive(Clone)]
+pub struct AssetManager {
+    shared: Arc<AssetsShared>,
+}
+
+impl AssetManager {
+    pub fn new(vfs: &'static Vfs, job_system: &'static JobSystem) -> Self {
+        let shared = AssetsShared {
+            vfs,
+            job_system,
+            loaders: RwLock::new(Map::new()),
+            assets: Mutex::new(Map::new()),
+            reload_generation: AtomicU64::new(0),
+        };
+        AssetManager { shared: Arc::new(shared) }
+    }
+
+    // Loads from the global VFS on the global job system
+    pub fn global() -> &'static AssetManager {
+        static GLOBAL: OnceLock<AssetManager> = OnceLock::new();
+        GLOBAL.get_or_init(|| AssetManager::new(Vfs::global(), JobSystem::global()))
+    }
+
+    #[inline]
+    pub fn get_vfs(&self) -> &'static Vfs {
+        self.shared.vfs
+    }
+
+    /// Replaces the loader of the same asset type
+    pub fn register_loader<L: AssetLoader>(&self, loader: L) {
+        let load: Arc<LoadFn<L::Asset>> = Arc::new(move |data, context| loader.load(data, context));
+        self.shared.loaders.write().unwrap().insert(TypeId::of::<L::Asset>(), Box::new(load));
+    }
+
+    pub fn has_loader<T: 'static>(&self) -> bool {
+        self.shared.loaders.read().unwrap().contains(&TypeId::of::<T>())
+    }
+
+    // Assets with at least one handle alive
+    pub fn assets_num(&self) -> usize {
+        self.shared.assets.lock().unwrap().iter().filter(|(_, slot)| slot.strong_count() > 0).count()
+    }
+
+    pub fn load<T: Send + Sync + 'static>(&self, path: &str) -> AssetHandle<T> {
+        if path.is_empty() || path.len() > STRINGS_TABLE_ENTRY_MAX_LEN || !path.contains(":/") {
+            let slot = self.new_slot::<T>(StringAtom::none());
+            *slot.state.write().unwrap() = SlotState::Failed(AssetError::InvalidPath(path.to_string()));
+            return AssetHandle { slot };
+        }
+
+        let key = (TypeId::of::<T>(), StringAtom::from(path));
+        let slot = {
+            let mut assets = self.shared.assets.lock().unwrap();
+            if let Some(slot) = assets.get(&key).and_then(|slot| slot.upgrade()) {
+                let slot: Arc<dyn Any + Send + Sync> = slot;
+                return AssetHandle { slot: slot.downcast().unwrap() };
+            }
+            let slot = self.new_slot::<T>(key.1);
+            let erased: Arc<dyn ErasedSlot> = slot.clone();
+            assets.insert(key, Arc::downgrade(&erased));
+            slot
+        };
+        self.spawn_load(&slot, None);
+        AssetHandle { slot }
+    }
+
+    /// The loaded asset if it is alive, without loading it
+    pub fn find<T: Send + Sync + 'static>(&self, path: &str) -> Option<AssetHandle<T>> {
+        if path.len() > STRINGS_TABLE_ENTRY_MAX_LEN {
+            return None;
+        }
+        let key = (TypeId::of::<T>(), StringAtom::from(path));
+        let slot: Arc<dyn Any + Send + Sync> = self.shared.assets.lock().unwrap().get(&key)?.upgrade()?;
+        Some(AssetHandle { slot: slot.downcast().unwrap() })
+    }
+
+    /// Loads again the assets of any type at the path, then their dependents once they are loaded.
+    /// Returns the number of assets at the path, the ones not loaded aren't reloaded.
+    pub fn reload(&self, path: &str) -> usize {
+        if path.len() > STRINGS_TABLE_ENTRY_MAX_LEN {
+            return 0;
+        }
+        let path = StringAtom::from(path);
+        let slots: Array<Arc<dyn ErasedSlot>> = self.shared.assets.lock().unwrap()
+            .iter()
+            .filter(|((_, slot_path), _)| *slot_path == path)
+            .filter_map(|(_, slot)| slot.upgrade())
+            .collect();
+        let slots: Array<&Arc<dyn ErasedSlot>> = slots.iter().filter(|slot| !matches!(slot.get_state(), LoadState::Pending)).collect();
+
+        // The assets depending on another one of the path are reloaded after it, when it reloads its dependents
+        let depends_on_path = |slot: &Arc<dyn ErasedSlot>| slot.get_dependencies().iter()
+            .any(|dependency| dependency.get_path() == path && !matches!(dependency.get_state(), LoadState::Pending));
+        let generation = self.shared.reload_generation.fetch_add(1, Ordering::AcqRel) + 1;
+        let mut roots = slots.iter().filter(|slot| !depends_on_path(slot)).peekable();
+        match roots.peek().is_some() {
+            true => roots.for_each(|slot| (*slot).clone().reload(self, generation)),
+            false => slots.iter().for_each(|slot| (*slot).clone().reload(self, generation)),
+        }
+        slots.num()
+    }
+
+    fn new_slot<T: Send + Sync + 'static>(&self, path: StringAtom) -> Arc<AssetSlot<T>> {
+        Arc::new(AssetSlot {
+            key: (TypeId::of::<T>(), path),
+            state: RwLock::new(SlotState::Pending),
+            loading: Mutex::new(JobCounter::new()),
+            dependencies: Mutex::new(Array::new()),
+            dependents: Mutex::new(Array::new()),
+            version: AtomicU32::new(0),
+            reload_error: Mutex::new(None),
+            reload_generation: AtomicU64::new(0),
+            shared: Arc::downgrade(&self.shared),
+        })
+    }
+
+    // The job doesn't keep the asset alive, dropping all the handles before it runs cancels the load
+    fn spawn_load<T: Send + Sync + 'static>(&self, slot: &Arc<AssetSlot<T>>, reload: Option<u64>) {
+        let counter = JobCounter::new();
+        *slot.loading.lock().unwrap() = counter.clone();
+        let weak = Arc::downgrade(slot);
+        let manager = self.clone();
+        self.shared.job_system.spawn_with_counter(&counter, move || {
+            if let Some(slot) = weak.upgrade() {
+                manager.load_now(&slot, reload);
+            }
+        });
+    }
+
+    fn load_now<T: Send + Sync + 'static>(&self, slot: &Arc<AssetSlot<T>>, reload: Option<u64>) {
+        let path = slot.key.1;
+        let mut context = LoadContext { manager: self.clone(), path, dependencies: Array::new() };
+        let result = self.get_load_fn::<T>()
+            .and_then(|load| {
+                let data = self.shared.vfs.read(&path.to_string())?;
+                panic::catch_unwind(AssertUnwindSafe(|| load(&data, &mut context))).unwrap_or(Err(AssetError::Panicked))
+            });
+
+        let dependent: Arc<dyn ErasedSlot> = slot.clone();
+        for dependency in context.dependencies.iter() {
+            dependency.0.add_dependent(Arc::downgrade(&dependent));
+        }
+        // A failed reload keeps the loaded asset and its dependencies
+        let state = match result {
+            Ok(asset) => SlotState::Loaded(Arc::new(asset)),
+            Err(error) if reload.is_some() && matches!(&*slot.state.read().unwrap(), SlotState::Loaded(_)) => {
+                *slot.reload_error.lock().unwrap() = Some(error);
+                return;
+            }
+            Err(error) => SlotState::Failed(error),
+        };
+        let loaded = matches!(state, SlotState::Loaded(_));
+
+        // The old asset and dependencies are dropped out of the locks
+        let old_dependencies = std::mem::replace(&mut *slot.dependencies.lock().unwrap(), context.dependencies);
+        let old_state = std::mem::replace(&mut *slot.state.write().unwrap(), state);
+        *slot.reload_error.lock().unwrap() = None;
+        if loaded {
+            slot.version.fetch_add(1, Ordering::AcqRel);
+        }
+        drop(old_state);
+        drop(old_dependencies);
+
+        if let (Some(generation), true) = (reload, loaded) {
+            for dependent in slot.get_dependents().iter() {
+                dependent.0.clone().reload(self, generation);
+            }
+        }
+    }
+
+    fn get_load_fn<T: 'static>(&self) -> Result<Arc<LoadFn<T>>, AssetError> {
+        let loaders = self.shared.loaders.read().unwrap();
+        let load = loaders.get(&TypeId::of::<T>()).ok_or(AssetError::NoLoader(type_name::<T>()))?;
+        Ok(load.downcast_ref::<Arc<LoadFn<T>>>().unwrap().clone())
+    }
+}
@@ -0,0 +1,84 @@
+use std::process::ExitCode;
+
+use rl_core::{Array, PakCompression, PakFile, PakWriter};
+
+const USAGE: &str = "\
+Usage:
+  pak build <output.pak> <directory> [--compression none|lz4|zstd] [--align <bytes>]
+  pak list <file.pak>";
+
+fn parse_compression(name: &str) -> Option<PakCompression> {
+    match name.to_ascii_lowercase().as_str() {
+        "none" => Some(PakCompression::None),
+        "lz4" => Some(PakCompression::Lz4),
+        "zstd" => Some(PakCompression::Zstd),
+        _ => None,
+    }
+}
+
+fn build(args: &[String]) -> Result<(), String> {
+    let [output, directory, options @ ..] = args else {
+        return Err(USAGE.to_string());
+    };
+    let mut compression = PakCompression::None;
+    let mut alignment = 16;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or_else(|| format!("Missing value for {}", option))?;
+        match option.as_str() {
+            "--compression" => compression = parse_compression(value).ok_or_else(|| format!("Unknown compression '{}'", value))?,
+            "--align" => alignment = value.parse().ok().filter(|alignment: &usize| alignment.is_power_of_two())
+                .ok_or_else(|| format!("Alignment '{}' isn't a power of two", value))?,
+            _ => return Err(format!("Unknown option '{}'\n{}", option, USAGE)),
+        }
+    }
+
+    let mut pak = PakWriter::create(output, alignment).map_err(|error| error.to_string())?;
+    let result = pak.add_directory(directory, compression).and_then(|files_num| pak.finish().map(|_| files_num));
+    match result {
+        Ok(files_num) => {
+            println!("{}: {} files", output, files_num);
+            Ok(())
+        }
+        Err(error) => {
+            // Don't leave a pak without table of contents behind
+            let _ = std::fs::remove_file(output);
+            Err(error.to_string())
+        }
+    }
+}
+
+fn list(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err(USAGE.to_string());
+    };
+    let pak = PakFile::open(path).map_err(|error| error.to_string())?;
+    let mut entries: Array<_> = pak.iter().map(|(path, entry)| (path.to_string(), entry.clone())).collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    println!("{:>12} {:>12} {:>12} {:<6} path", "offset", "size", "unpacked", "codec");
+    let (mut size, mut uncompressed_size) = (0, 0);
+    for (path, entry) in entries.iter() {
+        println!("{:>12} {:>12} {:>12} {:<6} {}", entry.get_offset(), entry.get_size(), entry.get_uncompressed_size(), format!("{:?}", entry.get_compression()), path);
+        size += entry.get_size();
+        uncompressed_size += entry.get_uncompressed_size();
+    }
+    println!("{} files, {} bytes stored, {} bytes unpacked, aligned to {}", entries.num(), size, uncompressed_size, pak.get_alignment());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Array<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(|command| command.as_str()) {
+        Some("build") => build(&args[1..]),
+        Some("list") => list(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
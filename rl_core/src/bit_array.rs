@@ -0,0 +1,505 @@
+use core::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, BitAndAssign, BitOrAssign, BitXorAssign, Not};
+
+use crate::alloc::{AllocatorBase, DefaultAllocator};
+use crate::Array;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[inline]
+const fn word_index(bit_index: usize) -> usize {
+    bit_index / WORD_BITS
+}
+
+#[inline]
+const fn word_mask(bit_index: usize) -> u64 {
+    1u64 << (bit_index % WORD_BITS)
+}
+
+#[inline]
+pub const fn words_for_bits(bits_num: usize) -> usize {
+    bits_num.div_ceil(WORD_BITS)
+}
+
+#[inline]
+fn count_ones(words: &[u64]) -> usize {
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+#[inline]
+fn find_first_set(words: &[u64], bits_num: usize) -> usize {
+    for (i, word) in words.iter().enumerate() {
+        if *word != 0 {
+            let bit_index = i * WORD_BITS + word.trailing_zeros() as usize;
+            return if bit_index < bits_num { bit_index } else { usize::MAX };
+        }
+    }
+    usize::MAX
+}
+
+#[inline]
+fn find_first_unset(words: &[u64], bits_num: usize) -> usize {
+    for (i, word) in words.iter().enumerate() {
+        if *word != u64::MAX {
+            let bit_index = i * WORD_BITS + word.trailing_ones() as usize;
+            return if bit_index < bits_num { bit_index } else { usize::MAX };
+        }
+    }
+    usize::MAX
+}
+
+pub struct BitsIter<'a> {
+    words: &'a [u64],
+    word_index: usize,
+    current_word: u64,
+}
+
+impl<'a> BitsIter<'a> {
+    #[inline]
+    fn new(words: &'a [u64]) -> Self {
+        BitsIter {
+            words,
+            word_index: 0,
+            current_word: words.first().copied().unwrap_or(0),
+        }
+    }
+}
+
+impl Iterator for BitsIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current_word == 0 {
+            self.word_index += 1;
+            if self.word_index >= self.words.len() {
+                return None;
+            }
+            self.current_word = self.words[self.word_index];
+        }
+
+        let bit_index = self.word_index * WORD_BITS + self.current_word.trailing_zeros() as usize;
+        // Clear lowest set bit
+        self.current_word &= self.current_word - 1;
+        Some(bit_index)
+    }
+}
+
+pub struct BitArray<A = DefaultAllocator> where A: AllocatorBase {
+    words: Array<u64, A>,
+    bits_num: usize,
+}
+
+impl BitArray {
+    #[inline]
+    pub fn new() -> Self {
+        BitArray { words: Array::new(), bits_num: 0 }
+    }
+
+    #[inline]
+    pub fn with_num(bits_num: usize, value: bool) -> Self {
+        let mut bit_array = Self::new();
+        bit_array.resize(bits_num, value);
+        bit_array
+    }
+}
+
+impl<A: AllocatorBase> BitArray<A> {
+    #[inline]
+    pub fn custom_allocator() -> Self {
+        BitArray { words: Array::custom_allocator(), bits_num: 0 }
+    }
+
+    #[inline]
+    pub fn custom_allocator_with_num(bits_num: usize, value: bool) -> Self {
+        let mut bit_array = Self::custom_allocator();
+        bit_array.resize(bits_num, value);
+        bit_array
+    }
+}
+
+impl<A: AllocatorBase> BitArray<A> {
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.bits_num
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bits_num == 0
+    }
+
+    #[inline]
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    #[inline]
+    fn clear_unused_bits(&mut self) {
+        let used_bits = self.bits_num % WORD_BITS;
+        if used_bits != 0 {
+            let last_word = self.words.num() - 1;
+            self.words[last_word] &= (1u64 << used_bits) - 1;
+        }
+    }
+
+    pub fn resize(&mut self, bits_num: usize, value: bool) {
+        let old_bits_num = self.bits_num;
+        let words_num = words_for_bits(bits_num);
+
+        while self.words.num() > words_num {
+            self.words.pop_back();
+        }
+
+        if bits_num > old_bits_num && value {
+            // Fill the tail of the last used word before adding new words
+            let used_bits = old_bits_num % WORD_BITS;
+            if used_bits != 0 {
+                let last_word = self.words.num() - 1;
+                self.words[last_word] |= !((1u64 << used_bits) - 1);
+            }
+        }
+
+        if words_num > self.words.num() {
+            let fill_word = if value { u64::MAX } else { 0 };
+            self.words.insert_range(self.words.num()..words_num, fill_word);
+        }
+
+        self.bits_num = bits_num;
+        self.clear_unused_bits();
+    }
+
+    #[inline]
+    pub fn push_back(&mut self, value: bool) {
+        let index = self.bits_num;
+        self.resize(index + 1, false);
+        self.set_value(index, value);
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.bits_num, "Bit {} is out of the {} bits", index, self.bits_num);
+        self.words[word_index(index)] & word_mask(index) != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: usize) {
+        assert!(index < self.bits_num, "Bit {} is out of the {} bits", index, self.bits_num);
+        self.words[word_index(index)] |= word_mask(index);
+    }
+
+    #[inline]
+    pub fn clear(&mut self, index: usize) {
+        assert!(index < self.bits_num, "Bit {} is out of the {} bits", index, self.bits_num);
+        self.words[word_index(index)] &= !word_mask(index);
+    }
+
+    #[inline]
+    pub fn toggle(&mut self, index: usize) {
+        assert!(index < self.bits_num, "Bit {} is out of the {} bits", index, self.bits_num);
+        self.words[word_index(index)] ^= word_mask(index);
+    }
+
+    #[inline]
+    pub fn set_value(&mut self, index: usize, value: bool) {
+        if value {
+            self.set(index);
+        } else {
+            self.clear(index);
+        }
+    }
+
+    #[inline]
+    pub fn set_all(&mut self) {
+        for word in &mut self.words {
+            *word = u64::MAX;
+        }
+        self.clear_unused_bits();
+    }
+
+    #[inline]
+    pub fn clear_all(&mut self) {
+        for word in &mut self.words {
+            *word = 0;
+        }
+    }
+
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        count_ones(&self.words)
+    }
+
+    #[inline]
+    pub fn count_zeros(&self) -> usize {
+        self.bits_num - self.count_ones()
+    }
+
+    #[inline]
+    pub fn any(&self) -> bool {
+        self.words.iter().any(|word| *word != 0)
+    }
+
+    #[inline]
+    pub fn find_first_set(&self) -> usize {
+        find_first_set(&self.words, self.bits_num)
+    }
+
+    #[inline]
+    pub fn find_first_unset(&self) -> usize {
+        find_first_unset(&self.words, self.bits_num)
+    }
+
+    #[inline]
+    pub fn iter_ones(&self) -> BitsIter<'_> {
+        BitsIter::new(&self.words)
+    }
+}
+
+impl<A: AllocatorBase, B: AllocatorBase> BitAndAssign<&BitArray<B>> for BitArray<A> {
+    fn bitand_assign(&mut self, other: &BitArray<B>) {
+        assert_eq!(self.bits_num, other.bits_num, "Combining bit arrays of different sizes");
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= *other_word;
+        }
+    }
+}
+
+impl<A: AllocatorBase, B: AllocatorBase> BitOrAssign<&BitArray<B>> for BitArray<A> {
+    fn bitor_assign(&mut self, other: &BitArray<B>) {
+        assert_eq!(self.bits_num, other.bits_num, "Combining bit arrays of different sizes");
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= *other_word;
+        }
+    }
+}
+
+impl<A: AllocatorBase, B: AllocatorBase> BitXorAssign<&BitArray<B>> for BitArray<A> {
+    fn bitxor_assign(&mut self, other: &BitArray<B>) {
+        assert_eq!(self.bits_num, other.bits_num, "Combining bit arrays of different sizes");
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word ^= *other_word;
+        }
+    }
+}
+
+impl<A: AllocatorBase> Not for BitArray<A> {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        for word in &mut self.words {
+            *word = !*word;
+        }
+        self.clear_unused_bits();
+        self
+    }
+}
+
+impl<A: AllocatorBase> Clone for BitArray<A> {
+    fn clone(&self) -> Self {
+        BitArray { words: self.words.iter().collect(), bits_num: self.bits_num }
+    }
+}
+
+impl<A: AllocatorBase, B: AllocatorBase> PartialEq<BitArray<B>> for BitArray<A> {
+    fn eq(&self, other: &BitArray<B>) -> bool {
+        self.bits_num == other.bits_num && self.as_words() == other.as_words()
+    }
+}
+
+impl<A: AllocatorBase> Eq for BitArray<A> { }
+
+impl<A: AllocatorBase> fmt::Debug for BitArray<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter_ones()).finish()
+    }
+}
+
+impl Default for BitArray {
+    fn default() -> BitArray {
+        BitArray::new()
+    }
+}
+
+impl<A: AllocatorBase> FromIterator<bool> for BitArray<A> {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bit_array = Self::custom_allocator();
+        for value in iter {
+            bit_array.push_back(value);
+        }
+        bit_array
+    }
+}
+
+/// Fixed-size set of `WORDS * 64` bits stored inline.
+///
+/// `WORDS` counts 64 bits words, not bits: use `BitSet<{ words_for_bits(100) }>` for 100 bits.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct BitSet<const WORDS: usize> {
+    words: [u64; WORDS],
+}
+
+impl<const WORDS: usize> BitSet<WORDS> {
+    pub const BITS: usize = WORDS * WORD_BITS;
+
+    #[inline]
+    pub const fn new() -> Self {
+        BitSet { words: [0; WORDS] }
+    }
+
+    #[inline]
+    pub const fn full() -> Self {
+        BitSet { words: [u64::MAX; WORDS] }
+    }
+
+    #[inline]
+    pub const fn num(&self) -> usize {
+        Self::BITS
+    }
+
+    #[inline]
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    #[inline]
+    pub const fn get(&self, index: usize) -> bool {
+        self.words[word_index(index)] & word_mask(index) != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: usize) {
+        self.words[word_index(index)] |= word_mask(index);
+    }
+
+    #[inline]
+    pub fn clear(&mut self, index: usize) {
+        self.words[word_index(index)] &= !word_mask(index);
+    }
+
+    #[inline]
+    pub fn toggle(&mut self, index: usize) {
+        self.words[word_index(index)] ^= word_mask(index);
+    }
+
+    #[inline]
+    pub fn set_value(&mut self, index: usize, value: bool) {
+        if value {
+            self.set(index);
+        } else {
+            self.clear(index);
+        }
+    }
+
+    #[inline]
+    pub fn set_all(&mut self) {
+        self.words = [u64::MAX; WORDS];
+    }
+
+    #[inline]
+    pub fn clear_all(&mut self) {
+        self.words = [0; WORDS];
+    }
+
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        count_ones(&self.words)
+    }
+
+    #[inline]
+    pub fn count_zeros(&self) -> usize {
+        Self::BITS - self.count_ones()
+    }
+
+    #[inline]
+    pub fn any(&self) -> bool {
+        self.words.iter().any(|word| *word != 0)
+    }
+
+    #[inline]
+    pub fn find_first_set(&self) -> usize {
+        find_first_set(&self.words, Self::BITS)
+    }
+
+    #[inline]
+    pub fn find_first_unset(&self) -> usize {
+        find_first_unset(&self.words, Self::BITS)
+    }
+
+    #[inline]
+    pub fn iter_ones(&self) -> BitsIter<'_> {
+        BitsIter::new(&self.words)
+    }
+}
+
+impl<const WORDS: usize> BitAndAssign<&BitSet<WORDS>> for BitSet<WORDS> {
+    fn bitand_assign(&mut self, other: &BitSet<WORDS>) {
+        for i in 0..WORDS {
+            self.words[i] &= other.words[i];
+        }
+    }
+}
+
+impl<const WORDS: usize> BitOrAssign<&BitSet<WORDS>> for BitSet<WORDS> {
+    fn bitor_assign(&mut self, other: &BitSet<WORDS>) {
+        for i in 0..WORDS {
+            self.words[i] |= other.words[i];
+        }
+    }
+}
+
+impl<const WORDS: usize> BitXorAssign<&BitSet<WORDS>> for BitSet<WORDS> {
+    fn bitxor_assign(&mut self, other: &BitSet<WORDS>) {
+        for i in 0..WORDS {
+            self.words[i] ^= other.words[i];
+        }
+    }
+}
+
+impl<const WORDS: usize> BitAnd for BitSet<WORDS> {
+    type Output = Self;
+
+    fn bitand(mut self, other: Self) -> Self {
+        self &= &other;
+        self
+    }
+}
+
+impl<const WORDS: usize> BitOr for BitSet<WORDS> {
+    type Output = Self;
+
+    fn bitor(mut self, other: Self) -> Self {
+        self |= &other;
+        self
+    }
+}
+
+impl<const WORDS: usize> BitXor for BitSet<WORDS> {
+    type Output = Self;
+
+    fn bitxor(mut self, other: Self) -> Self {
+        self ^= &other;
+        self
+    }
+}
+
+impl<const WORDS: usize> Not for BitSet<WORDS> {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        for word in &mut self.words {
+            *word = !*word;
+        }
+        self
+    }
+}
+
+impl<const WORDS: usize> fmt::Debug for BitSet<WORDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter_ones()).finish()
+    }
+}
+
+impl<const WORDS: usize> Default for BitSet<WORDS> {
+    fn default() -> Self {
+        BitSet::new()
+    }
+}
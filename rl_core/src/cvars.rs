@@ -0,0 +1,444 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{Array, Event, Map, StringAtom, Subscription};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CVarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Enum(usize), // index in the enum values
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CVarKind {
+    Bool,
+    Int(RangeInclusive<i64>),
+    Float(RangeInclusive<f64>),
+    String,
+    Enum(Array<StringAtom>),
+}
+
+#[derive(Debug)]
+pub enum CVarError {
+    UnknownVariable(String),
+    InvalidValue { name: StringAtom, value: String },
+    OutOfRange { name: StringAtom, value: String },
+    Syntax(String),
+    Config { line: usize, error: Box<CVarError> },
+    Io(io::Error),
+}
+
+impl fmt::Display for CVarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CVarError::UnknownVariable(name) => write!(f, "Unknown variable '{}'", name),
+            CVarError::InvalidValue { name, value } => write!(f, "Invalid value '{}' for '{}'", value, name),
+            CVarError::OutOfRange { name, value } => write!(f, "Value {} is out of the '{}' range", value, name),
+            CVarError::Syntax(message) => write!(f, "Syntax error: {}", message),
+            CVarError::Config { line, error } => write!(f, "Line {}: {}", line, error),
+            CVarError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CVarError { }
+
+struct CVar {
+    name: StringAtom,
+    help: String,
+    kind: CVarKind,
+    value: CVarValue,
+    default: CVarValue,
+    changed: Arc<Event<CVarValue>>,
+}
+
+impl CVar {
+    fn parse(&self, text: &str) -> Result<CVarValue, CVarError> {
+        let invalid = || CVarError::InvalidValue { name: self.name, value: text.to_string() };
+        let value = match &self.kind {
+            CVarKind::Bool => match text.to_ascii_lowercase().as_str() {
+                "1" | "true" | "on" | "yes" => CVarValue::Bool(true),
+                "0" | "false" | "off" | "no" => CVarValue::Bool(false),
+                _ => return Err(invalid()),
+            },
+            CVarKind::Int(_) => CVarValue::Int(text.parse().map_err(|_| invalid())?),
+            CVarKind::Float(_) => CVarValue::Float(text.parse().map_err(|_| invalid())?),
+            CVarKind::String => CVarValue::String(text.to_string()),
+            CVarKind::Enum(values) => {
+                // By name, case insensitive, or by index
+                let atom = StringAtom::from(text);
+                let index = values.iter()
+                    .position(|value| *value == atom)
+                    .or_else(|| text.parse::<usize>().ok())
+                    .ok_or_else(invalid)?;
+                CVarValue::Enum(index)
+            }
+        };
+        self.validate(value)
+    }
+
+    fn validate(&self, value: CVarValue) -> Result<CVarValue, CVarError> {
+        let in_range = match (&self.kind, &value) {
+            (CVarKind::Bool, CVarValue::Bool(_)) | (CVarKind::String, CVarValue::String(_)) => true,
+            (CVarKind::Int(range), CVarValue::Int(value)) => range.contains(value),
+            (CVarKind::Float(range), CVarValue::Float(value)) => range.contains(value),
+            (CVarKind::Enum(values), CVarValue::Enum(index)) => *index < values.num(),
+            // Value of another type
+            _ => return Err(CVarError::InvalidValue { name: self.name, value: self.format(&value) }),
+        };
+        if in_range {
+            Ok(value)
+        } else {
+            Err(CVarError::OutOfRange { name: self.name, value: self.format(&value) })
+        }
+    }
+
+    fn format(&self, value: &CVarValue) -> String {
+        match (value, &self.kind) {
+            (CVarValue::Enum(index), CVarKind::Enum(values)) if *index < values.num() => values[*index].to_string(),
+            (CVarValue::Bool(value), _) => value.to_string(),
+            (CVarValue::Int(value), _) => value.to_string(),
+            (CVarValue::Float(value), _) => value.to_string(),
+            (CVarValue::String(value), _) => format!("\"{}\"", value),
+            (CVarValue::Enum(index), _) => index.to_string(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let range = match &self.kind {
+            CVarKind::Int(range) => format!(" [{}, {}]", range.start(), range.end()),
+            CVarKind::Float(range) => format!(" [{}, {}]", range.start(), range.end()),
+            CVarKind::Enum(values) => {
+                let names: Array<String> = values.iter().map(|value| value.to_string()).collect();
+                format!(" [{}]", names.join(" | "))
+            }
+            _ => String::new(),
+        };
+        format!("{} = {} (default {}){} // {}", self.name, self.format(&self.value), self.format(&self.default), range, self.help)
+    }
+}
+
+#[derive(Default)]
+struct CVarsInner {
+    vars: Array<CVar>,
+    by_name: Map<StringAtom, usize>,
+    pending: Map<StringAtom, String>, // set before registration, applied when registered
+}
+
+/// Console variables registry, tunables are registered by name and can be changed at runtime,
+/// from a config file, from `+name=value` command-line arguments or from console commands
+#[derive(Default)]
+pub struct CVars {
+    inner: RwLock<CVarsInner>,
+}
+
+impl CVars {
+    pub fn new() -> Self {
+        CVars::default()
+    }
+
+    pub fn global() -> &'static CVars {
+        static GLOBAL: OnceLock<CVars> = OnceLock::new();
+        GLOBAL.get_or_init(CVars::new)
+    }
+
+    // Registering again keeps the current value, registering with another kind panics
+    fn register(&self, name: StringAtom, kind: CVarKind, default: CVarValue, help: &str) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(&index) = inner.by_name.get(&name) {
+            assert!(inner.vars[index].kind == kind, "CVar '{}' registered twice with different kinds", name);
+            return;
+        }
+
+        let mut var = CVar {
+            name,
+            help: help.to_string(),
+            kind,
+            value: default.clone(),
+            default,
+            changed: Arc::new(Event::new()),
+        };
+        var.value = var.validate(var.default.clone()).unwrap_or_else(|error| panic!("{}", error));
+
+        // A pending value that doesn't parse is dropped, the default is kept
+        if let Some(text) = inner.pending.remove(&name) {
+            if let Ok(value) = var.parse(&text) {
+                var.value = value;
+            }
+        }
+
+        let index = inner.vars.num();
+        inner.vars.push_back(var);
+        inner.by_name.insert(name, index);
+    }
+
+    pub fn register_bool(&self, name: StringAtom, default: bool, help: &str) {
+        self.register(name, CVarKind::Bool, CVarValue::Bool(default), help);
+    }
+
+    pub fn register_int(&self, name: StringAtom, default: i64, range: RangeInclusive<i64>, help: &str) {
+        self.register(name, CVarKind::Int(range), CVarValue::Int(default), help);
+    }
+
+    pub fn register_float(&self, name: StringAtom, default: f64, range: RangeInclusive<f64>, help: &str) {
+        self.register(name, CVarKind::Float(range), CVarValue::Float(default), help);
+    }
+
+    pub fn register_string(&self, name: StringAtom, default: &str, help: &str) {
+        self.register(name, CVarKind::String, CVarValue::String(default.to_string()), help);
+    }
+
+    pub fn register_enum(&self, name: StringAtom, default: StringAtom, values: &[StringAtom], help: &str) {
+        let index = values.iter().position(|value| *value == default).expect("The enum default isn't one of its values");
+        self.register(name, CVarKind::Enum(values.iter().copied().collect()), CVarValue::Enum(index), help);
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.inner.read().unwrap().vars.num()
+    }
+
+    #[inline]
+    pub fn contains(&self, name: StringAtom) -> bool {
+        self.inner.read().unwrap().by_name.contains(&name)
+    }
+
+    // Sorted by name
+    pub fn get_names(&self) -> Array<StringAtom> {
+        let inner = self.inner.read().unwrap();
+        let mut names: Array<StringAtom> = inner.vars.iter().map(|var| var.name).collect();
+        names.sort_by_key(|name| name.to_string().to_ascii_lowercase());
+        names
+    }
+
+    fn with_var<R>(&self, name: StringAtom, f: impl FnOnce(&CVar) -> R) -> Option<R> {
+        let inner = self.inner.read().unwrap();
+        inner.by_name.get(&name).map(|&index| f(&inner.vars[index]))
+    }
+
+    pub fn get(&self, name: StringAtom) -> Option<CVarValue> {
+        self.with_var(name, |var| var.value.clone())
+    }
+
+    pub fn get_kind(&self, name: StringAtom) -> Option<CVarKind> {
+        self.with_var(name, |var| var.kind.clone())
+    }
+
+    pub fn get_bool(&self, name: StringAtom) -> bool {
+        match self.get(name) {
+            Some(CVarValue::Bool(value)) => value,
+            value => panic!("CVar '{}' isn't a registered bool: {:?}", name, value),
+        }
+    }
+
+    pub fn get_int(&self, name: StringAtom) -> i64 {
+        match self.get(name) {
+            Some(CVarValue::Int(value)) => value,
+            value => panic!("CVar '{}' isn't a registered int: {:?}", name, value),
+        }
+    }
+
+    pub fn get_float(&self, name: StringAtom) -> f64 {
+        match self.get(name) {
+            Some(CVarValue::Float(value)) => value,
+            value => panic!("CVar '{}' isn't a registered float: {:?}", name, value),
+        }
+    }
+
+    pub fn get_string(&self, name: StringAtom) -> String {
+        match self.get(name) {
+            Some(CVarValue::String(value)) => value,
+            value => panic!("CVar '{}' isn't a registered string: {:?}", name, value),
+        }
+    }
+
+    pub fn get_enum(&self, name: StringAtom) -> StringAtom {
+        let value = self.with_var(name, |var| match (&var.value, &var.kind) {
+            (CVarValue::Enum(index), CVarKind::Enum(values)) => Some(values[*index]),
+            _ => None,
+        });
+        value.flatten().unwrap_or_else(|| panic!("CVar '{}' isn't a registered enum", name))
+    }
+
+    // Stores the validated value and notifies the listeners outside the lock
+    fn store(&self, name: StringAtom, parse: impl FnOnce(&CVar) -> Result<CVarValue, CVarError>) -> Result<(), CVarError> {
+        let (changed, value) = {
+            let mut inner = self.inner.write().unwrap();
+            let Some(&index) = inner.by_name.get(&name) else {
+                return Err(CVarError::UnknownVariable(name.to_string()));
+            };
+            let var = &mut inner.vars[index];
+            let value = parse(var)?;
+            if var.value == value {
+                return Ok(());
+            }
+            var.value = value.clone();
+            (var.changed.clone(), value)
+        };
+        changed.broadcast(&value);
+        Ok(())
+    }
+
+    pub fn set(&self, name: StringAtom, value: CVarValue) -> Result<(), CVarError> {
+        self.store(name, |var| var.validate(value))
+    }
+
+    pub fn set_from_str(&self, name: StringAtom, text: &str) -> Result<(), CVarError> {
+        self.store(name, |var| var.parse(text))
+    }
+
+    pub fn reset(&self, name: StringAtom) -> Result<(), CVarError> {
+        self.store(name, |var| Ok(var.default.clone()))
+    }
+
+    /// The listener is called with the new value every time it changes
+    pub fn subscribe<F>(&self, name: StringAtom, listener: F) -> Subscription
+    where
+        F: Fn(&CVarValue) + Send + Sync + 'static
+    {
+        self.with_var(name, |var| var.changed.subscribe(listener))
+            .unwrap_or_else(|| panic!("Subscribing to the unknown CVar '{}'", name))
+    }
+
+    // Unknown variables are kept until they get registered
+    fn set_or_defer(&self, name: StringAtom, text: &str) -> Result<(), CVarError> {
+        match self.set_from_str(name, text) {
+            Err(CVarError::UnknownVariable(_)) => {
+                let mut inner = self.inner.write().unwrap();
+                // Registered in between
+                if inner.by_name.contains(&name) {
+                    drop(inner);
+                    return self.set_from_str(name, text);
+                }
+                inner.pending.insert(name, text.to_string());
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Applies `name = value` lines, `#` and `//` start comments. Returns how many variables were set.
+    pub fn load_config_str(&self, text: &str) -> Result<usize, CVarError> {
+        let mut num = 0;
+        for (line_index, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let config_error = |error| CVarError::Config { line: line_index + 1, error: Box::new(error) };
+            let (name, value) = split_assignment(line).ok_or_else(|| config_error(CVarError::Syntax(format!("expected 'name = value', got '{}'", line))))?;
+            self.set_or_defer(to_name(name).map_err(config_error)?, value).map_err(config_error)?;
+            num += 1;
+        }
+        Ok(num)
+    }
+
+    pub fn load_config_file(&self, path: impl AsRef<Path>) -> Result<usize, CVarError> {
+        let text = fs::read_to_string(path).map_err(CVarError::Io)?;
+        self.load_config_str(&text)
+    }
+
+    /// Applies the `+name=value` arguments, the other ones are ignored. Returns how many variables were set.
+    pub fn apply_command_line<I, S>(&self, args: I) -> Result<usize, CVarError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>
+    {
+        let mut num = 0;
+        for arg in args {
+            let Some(assignment) = arg.as_ref().strip_prefix('+') else {
+                continue;
+            };
+            let (name, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| CVarError::Syntax(format!("expected '+name=value', got '{}'", arg.as_ref())))?;
+            self.set_or_defer(to_name(name.trim())?, unquote(value.trim()))?;
+            num += 1;
+        }
+        Ok(num)
+    }
+
+    /// Runs a console command and returns the text to print:
+    /// `name` prints the value, `name value` or `name = value` sets it,
+    /// `help name`, `reset name` and `list [filter]`
+    pub fn execute(&self, command: &str) -> Result<String, CVarError> {
+        let command = command.trim();
+        let (word, rest) = match command.split_once(char::is_whitespace) {
+            Some((word, rest)) => (word, rest.trim()),
+            None => (command, ""),
+        };
+
+        match word {
+            "" => Ok(String::new()),
+            "list" => {
+                let lines: Array<String> = self.get_names()
+                    .iter()
+                    .map(|name| name.to_string())
+                    .filter(|name| name.to_ascii_lowercase().contains(&rest.to_ascii_lowercase()))
+                    .map(|name| self.with_var(name.as_str().into(), |var| format!("{} = {}", var.name, var.format(&var.value))).unwrap())
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            "help" => self.with_var(to_name(rest)?, |var| var.describe()).ok_or_else(|| CVarError::UnknownVariable(rest.to_string())),
+            "reset" => {
+                self.reset(to_name(rest)?)?;
+                self.execute(rest)
+            }
+            _ => {
+                let (name, value) = split_assignment(command).unwrap_or((command, ""));
+                let name = to_name(name)?;
+                if !value.is_empty() {
+                    self.set_from_str(name, value)?;
+                }
+                self.with_var(name, |var| format!("{} = {}", var.name, var.format(&var.value)))
+                    .ok_or_else(|| CVarError::UnknownVariable(name.to_string()))
+            }
+        }
+    }
+}
+
+// Names too long for the strings table can't be registered, so they are unknown
+fn to_name(name: &str) -> Result<StringAtom, CVarError> {
+    if name.len() > STRINGS_TABLE_ENTRY_MAX_LEN {
+        return Err(CVarError::UnknownVariable(name.to_string()));
+    }
+    Ok(StringAtom::from(name))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '#' if !in_quotes => return &line[..i],
+            '/' if !in_quotes && line[i..].starts_with("//") => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[inline]
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value)
+}
+
+// "name = value", "name=value" or "name value", the value can be quoted
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let split = line.find(|c: char| c == '=' || c.is_whitespace())?;
+    let name = &line[..split];
+    let value = line[split..].trim_start();
+    let value = value.strip_prefix('=').unwrap_or(value).trim();
+    if name.is_empty() || value.is_empty() {
+        return None;
+    }
+    Some((name, unquote(value)))
+}
@@ -0,0 +1,214 @@
+use std::mem;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::Array;
+
+type Listener<Args> = Arc<dyn Fn(&Args) + Send + Sync>;
+
+struct ListenerSlot<Args> {
+    generation: u32,
+    listener: Option<Listener<Args>>,
+}
+
+// Slot map of listeners, a subscription keeps (index, generation) so a reused slot is never removed by a stale handle
+struct Listeners<Args> {
+    slots: Array<ListenerSlot<Args>>,
+    free_slots: Array<u32>,
+    num: usize,
+}
+
+struct EventShared<Args: Unpin + 'static> {
+    listeners: Mutex<Listeners<Args>>,
+    posted: Mutex<Array<Args>>,
+}
+
+trait Unsubscribe: Send + Sync {
+    fn unsubscribe(&self, index: u32, generation: u32);
+}
+
+impl<Args: Unpin + Send + 'static> Unsubscribe for EventShared<Args> {
+    fn unsubscribe(&self, index: u32, generation: u32) {
+        let mut listeners = self.listeners.lock().unwrap();
+        let slot = &mut listeners.slots[index as usize];
+        if slot.generation != generation || slot.listener.is_none() {
+            return;
+        }
+        // Dropped after the lock is released, the closure may own another subscription
+        let listener = slot.listener.take();
+        slot.generation = slot.generation.wrapping_add(1);
+        listeners.free_slots.push_back(index);
+        listeners.num -= 1;
+        drop(listeners);
+        drop(listener);
+    }
+}
+
+// Deferred part of an event, so queues can hold events of any argument type
+trait PostedEvents: Send + Sync {
+    fn dispatch_posted(&self) -> usize;
+}
+
+impl<Args: Unpin + Send + 'static> PostedEvents for EventShared<Args> {
+    fn dispatch_posted(&self) -> usize {
+        let posted = mem::take(&mut *self.posted.lock().unwrap());
+        for args in posted.iter() {
+            self.broadcast(args);
+        }
+        posted.num()
+    }
+}
+
+impl<Args: Unpin + Send + 'static> EventShared<Args> {
+    fn broadcast(&self, args: &Args) {
+        // Listeners run unlocked so they can subscribe, unsubscribe or post to this event
+        let listeners: Array<Listener<Args>> = {
+            let listeners = self.listeners.lock().unwrap();
+            listeners.slots.iter().filter_map(|slot| slot.listener.clone()).collect()
+        };
+        for listener in listeners.iter() {
+            listener(args);
+        }
+    }
+}
+
+/// Typed multicast delegate: every subscribed listener is called on broadcast.
+/// Listeners subscribed during a broadcast are called from the next one.
+pub struct Event<Args: Unpin + 'static> {
+    shared: Arc<EventShared<Args>>,
+}
+
+/// Keeps a listener subscribed, unsubscribes it on drop
+#[must_use = "the listener is unsubscribed when the subscription is dropped"]
+pub struct Subscription {
+    event: Option<Weak<dyn Unsubscribe>>,
+    index: u32,
+    generation: u32,
+}
+
+impl<Args: Unpin + Send + 'static> Event<Args> {
+    pub fn new() -> Self {
+        Event {
+            shared: Arc::new(EventShared {
+                listeners: Mutex::new(Listeners { slots: Array::new(), free_slots: Array::new(), num: 0 }),
+                posted: Mutex::new(Array::new()),
+            }),
+        }
+    }
+
+    pub fn subscribe<F>(&self, listener: F) -> Subscription
+    where
+        F: Fn(&Args) + Send + Sync + 'static
+    {
+        let mut listeners = self.shared.listeners.lock().unwrap();
+        let index = if listeners.free_slots.is_empty() {
+            listeners.slots.push_back(ListenerSlot { generation: 0, listener: None });
+            (listeners.slots.num() - 1) as u32
+        } else {
+            listeners.free_slots.pop_back()
+        };
+
+        let slot = &mut listeners.slots[index as usize];
+        slot.listener = Some(Arc::new(listener));
+        let generation = slot.generation;
+        listeners.num += 1;
+
+        let shared: Arc<dyn Unsubscribe> = self.shared.clone();
+        Subscription { event: Some(Arc::downgrade(&shared)), index, generation }
+    }
+
+    #[inline]
+    pub fn listeners_num(&self) -> usize {
+        self.shared.listeners.lock().unwrap().num
+    }
+
+    /// Calls every listener right away on the calling thread
+    #[inline]
+    pub fn broadcast(&self, args: &Args) {
+        self.shared.broadcast(args);
+    }
+
+    /// Queues the arguments for the next `dispatch_posted`, usually done once per frame by an EventQueue
+    pub fn post(&self, args: Args) {
+        self.shared.posted.lock().unwrap().push_back(args);
+    }
+
+    #[inline]
+    pub fn posted_num(&self) -> usize {
+        self.shared.posted.lock().unwrap().num()
+    }
+
+    /// Broadcasts the posted arguments in posting order, returns how many were dispatched.
+    /// Arguments posted by the listeners wait for the next call.
+    #[inline]
+    pub fn dispatch_posted(&self) -> usize {
+        self.shared.dispatch_posted()
+    }
+}
+
+impl<Args: Unpin + Send + 'static> Default for Event<Args> {
+    fn default() -> Self {
+        Event::new()
+    }
+}
+
+impl Subscription {
+    #[inline]
+    pub fn is_subscribed(&self) -> bool {
+        self.event.as_ref().is_some_and(|event| event.strong_count() > 0)
+    }
+
+    pub fn unsubscribe(mut self) {
+        self.release();
+    }
+
+    /// Keeps the listener subscribed for the whole event lifetime
+    pub fn detach(mut self) {
+        self.event = None;
+    }
+
+    fn release(&mut self) {
+        if let Some(event) = self.event.take().and_then(|event| event.upgrade()) {
+            event.unsubscribe(self.index, self.generation);
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Set of events whose posted arguments are dispatched together, once per frame.
+/// Events are held weakly, a dropped event leaves the queue on the next process.
+#[derive(Default)]
+pub struct EventQueue {
+    events: Mutex<Array<Weak<dyn PostedEvents>>>,
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        EventQueue { events: Mutex::new(Array::new()) }
+    }
+
+    pub fn add<Args: Unpin + Send + 'static>(&self, event: &Event<Args>) {
+        let shared: Arc<dyn PostedEvents> = event.shared.clone();
+        self.events.lock().unwrap().push_back(Arc::downgrade(&shared));
+    }
+
+    #[inline]
+    pub fn events_num(&self) -> usize {
+        self.events.lock().unwrap().num()
+    }
+
+    /// Dispatches the posted arguments of every event, returns the total number of dispatched arguments
+    pub fn process(&self) -> usize {
+        let events: Array<Arc<dyn PostedEvents>> = {
+            let mut events = self.events.lock().unwrap();
+            let alive: Array<Weak<dyn PostedEvents>> = events.iter().filter(|event| event.strong_count() > 0).cloned().collect();
+            *events = alive;
+            events.iter().filter_map(|event| event.upgrade()).collect()
+        };
+        events.iter().map(|event| event.dispatch_posted()).sum()
+    }
+}
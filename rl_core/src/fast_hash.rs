@@ -21,36 +21,454 @@ pub fn fnv_hash<const LOWERCASE: bool>(bytes: &[u8]) -> u32 {
     hash
 }
 
+pub const fn fnv_hash64_const<const N: usize>(bytes: &[u8; N], lowercase: bool) -> u64 {
+    let mut hash = 14695981039346656037u64;
+    let mut i = 0;
+    while i < N {
+        let byte = if lowercase { bytes[i].to_ascii_lowercase() } else { bytes[i] } as u64;
+        hash = u64::wrapping_mul(hash ^ byte, 1099511628211u64);
+        i += 1;
+    }
+    hash
+}
+
+pub fn fnv_hash64<const LOWERCASE: bool>(bytes: &[u8]) -> u64 {
+    let mut hash = 14695981039346656037u64;
+    for byte in bytes {
+        let byte = if LOWERCASE { byte.to_ascii_lowercase() } else { *byte } as u64;
+        hash = u64::wrapping_mul(hash ^ byte, 1099511628211u64);
+    }
+    hash
+}
+
+const XXH_PRIME64_1: u64 = 0x9E3779B185EBCA87;
+const XXH_PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+const XXH_PRIME64_3: u64 = 0x165667B19E3779F9;
+const XXH_PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+const XXH_PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+#[inline]
+const fn read_u64_le(bytes: &[u8], i: usize) -> u64 {
+    (bytes[i] as u64)
+        | (bytes[i + 1] as u64) << 8
+        | (bytes[i + 2] as u64) << 16
+        | (bytes[i + 3] as u64) << 24
+        | (bytes[i + 4] as u64) << 32
+        | (bytes[i + 5] as u64) << 40
+        | (bytes[i + 6] as u64) << 48
+        | (bytes[i + 7] as u64) << 56
+}
+
+#[inline]
+const fn read_u32_le(bytes: &[u8], i: usize) -> u64 {
+    ((bytes[i] as u32)
+        | (bytes[i + 1] as u32) << 8
+        | (bytes[i + 2] as u32) << 16
+        | (bytes[i + 3] as u32) << 24) as u64
+}
+
+#[inline]
+const fn xxh64_round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(XXH_PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(XXH_PRIME64_1)
+}
+
+#[inline]
+const fn xxh64_merge_round(acc: u64, val: u64) -> u64 {
+    (acc ^ xxh64_round(0, val))
+        .wrapping_mul(XXH_PRIME64_1)
+        .wrapping_add(XXH_PRIME64_4)
+}
+
+#[inline]
+const fn xxh64_avalanche(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(XXH_PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(XXH_PRIME64_3);
+    hash ^ (hash >> 32)
+}
+
+// xxHash64, usable at compile time: 32 bytes stripes, then 8/4/1 bytes tail and avalanche
+pub const fn xxh64_hash(bytes: &[u8], seed: u64) -> u64 {
+    let len = bytes.len();
+    let mut i = 0;
+    let mut hash;
+
+    if len >= 32 {
+        let mut v1 = seed.wrapping_add(XXH_PRIME64_1).wrapping_add(XXH_PRIME64_2);
+        let mut v2 = seed.wrapping_add(XXH_PRIME64_2);
+        let mut v3 = seed;
+        let mut v4 = seed.wrapping_sub(XXH_PRIME64_1);
+        while i + 32 <= len {
+            v1 = xxh64_round(v1, read_u64_le(bytes, i));
+            v2 = xxh64_round(v2, read_u64_le(bytes, i + 8));
+            v3 = xxh64_round(v3, read_u64_le(bytes, i + 16));
+            v4 = xxh64_round(v4, read_u64_le(bytes, i + 24));
+            i += 32;
+        }
+        hash = v1.rotate_left(1)
+            .wrapping_add(v2.rotate_left(7))
+            .wrapping_add(v3.rotate_left(12))
+            .wrapping_add(v4.rotate_left(18));
+        hash = xxh64_merge_round(hash, v1);
+        hash = xxh64_merge_round(hash, v2);
+        hash = xxh64_merge_round(hash, v3);
+        hash = xxh64_merge_round(hash, v4);
+    } else {
+        hash = seed.wrapping_add(XXH_PRIME64_5);
+    }
+
+    hash = hash.wrapping_add(len as u64);
+
+    while i + 8 <= len {
+        hash ^= xxh64_round(0, read_u64_le(bytes, i));
+        hash = hash.rotate_left(27).wrapping_mul(XXH_PRIME64_1).wrapping_add(XXH_PRIME64_4);
+        i += 8;
+    }
+
+    if i + 4 <= len {
+        hash ^= read_u32_le(bytes, i).wrapping_mul(XXH_PRIME64_1);
+        hash = hash.rotate_left(23).wrapping_mul(XXH_PRIME64_2).wrapping_add(XXH_PRIME64_3);
+        i += 4;
+    }
+
+    while i < len {
+        hash ^= (bytes[i] as u64).wrapping_mul(XXH_PRIME64_5);
+        hash = hash.rotate_left(11).wrapping_mul(XXH_PRIME64_1);
+        i += 1;
+    }
+
+    xxh64_avalanche(hash)
+}
+
+pub const fn xxh64_hash_const<const N: usize>(bytes: &[u8; N], seed: u64) -> u64 {
+    xxh64_hash(bytes, seed)
+}
+
+const XXH_PRIME32_1: u64 = 0x9E3779B1;
+const XXH_PRIME32_2: u64 = 0x85EBCA77;
+const XXH_PRIME32_3: u64 = 0xC2B2AE3D;
+const XXH_PRIME_MX1: u64 = 0x165667919E3779F9;
+const XXH_PRIME_MX2: u64 = 0x9FB21C651E98DF25;
+
+const XXH3_SECRET_LEN: usize = 192;
+const XXH3_STRIPE_LEN: usize = 64;
+const XXH3_STRIPES_PER_BLOCK: usize = (XXH3_SECRET_LEN - XXH3_STRIPE_LEN) / 8;
+
+const XXH3_DEFAULT_SECRET: [u8; XXH3_SECRET_LEN] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+#[inline]
+const fn mul128_fold64(a: u64, b: u64) -> u64 {
+    let product = a as u128 * b as u128;
+    (product as u64) ^ ((product >> 64) as u64)
+}
+
+#[inline]
+const fn xxh3_avalanche(mut hash: u64) -> u64 {
+    hash ^= hash >> 37;
+    hash = hash.wrapping_mul(XXH_PRIME_MX1);
+    hash ^ (hash >> 32)
+}
+
+#[inline]
+const fn xxh3_rrmxmx(mut hash: u64, len: usize) -> u64 {
+    hash ^= hash.rotate_left(49) ^ hash.rotate_left(24);
+    hash = hash.wrapping_mul(XXH_PRIME_MX2);
+    hash ^= (hash >> 35).wrapping_add(len as u64);
+    hash = hash.wrapping_mul(XXH_PRIME_MX2);
+    hash ^ (hash >> 28)
+}
+
+#[inline]
+const fn xxh3_mix16(bytes: &[u8], i: usize, secret: &[u8], s: usize, seed: u64) -> u64 {
+    mul128_fold64(
+        read_u64_le(bytes, i) ^ read_u64_le(secret, s).wrapping_add(seed),
+        read_u64_le(bytes, i + 8) ^ read_u64_le(secret, s + 8).wrapping_sub(seed))
+}
+
+const fn xxh3_hash_0_to_16(bytes: &[u8], seed: u64) -> u64 {
+    let secret = &XXH3_DEFAULT_SECRET;
+    let len = bytes.len();
+    if len > 8 {
+        let low = read_u64_le(bytes, 0) ^ (read_u64_le(secret, 24) ^ read_u64_le(secret, 32)).wrapping_add(seed);
+        let high = read_u64_le(bytes, len - 8) ^ (read_u64_le(secret, 40) ^ read_u64_le(secret, 48)).wrapping_sub(seed);
+        let acc = (len as u64)
+            .wrapping_add(low.swap_bytes())
+            .wrapping_add(high)
+            .wrapping_add(mul128_fold64(low, high));
+        xxh3_avalanche(acc)
+    } else if len >= 4 {
+        let seed = seed ^ (((seed as u32).swap_bytes() as u64) << 32);
+        let input = read_u32_le(bytes, len - 4).wrapping_add(read_u32_le(bytes, 0) << 32);
+        let bitflip = (read_u64_le(secret, 8) ^ read_u64_le(secret, 16)).wrapping_sub(seed);
+        xxh3_rrmxmx(input ^ bitflip, len)
+    } else if len > 0 {
+        let combined = (bytes[0] as u64) << 16 | (bytes[len >> 1] as u64) << 24 | bytes[len - 1] as u64 | (len as u64) << 8;
+        let bitflip = (read_u32_le(secret, 0) ^ read_u32_le(secret, 4)).wrapping_add(seed);
+        xxh64_avalanche(combined ^ bitflip)
+    } else {
+        xxh64_avalanche(seed ^ read_u64_le(secret, 56) ^ read_u64_le(secret, 64))
+    }
+}
+
+const fn xxh3_hash_17_to_128(bytes: &[u8], seed: u64) -> u64 {
+    let secret = &XXH3_DEFAULT_SECRET;
+    let len = bytes.len();
+    let mut acc = (len as u64).wrapping_mul(XXH_PRIME64_1);
+    if len > 32 {
+        if len > 64 {
+            if len > 96 {
+                acc = acc.wrapping_add(xxh3_mix16(bytes, 48, secret, 96, seed));
+                acc = acc.wrapping_add(xxh3_mix16(bytes, len - 64, secret, 112, seed));
+            }
+            acc = acc.wrapping_add(xxh3_mix16(bytes, 32, secret, 64, seed));
+            acc = acc.wrapping_add(xxh3_mix16(bytes, len - 48, secret, 80, seed));
+        }
+        acc = acc.wrapping_add(xxh3_mix16(bytes, 16, secret, 32, seed));
+        acc = acc.wrapping_add(xxh3_mix16(bytes, len - 32, secret, 48, seed));
+    }
+    acc = acc.wrapping_add(xxh3_mix16(bytes, 0, secret, 0, seed));
+    acc = acc.wrapping_add(xxh3_mix16(bytes, len - 16, secret, 16, seed));
+    xxh3_avalanche(acc)
+}
+
+const fn xxh3_hash_129_to_240(bytes: &[u8], seed: u64) -> u64 {
+    let secret = &XXH3_DEFAULT_SECRET;
+    let len = bytes.len();
+    let mut acc = (len as u64).wrapping_mul(XXH_PRIME64_1);
+    let mut i = 0;
+    while i < 8 {
+        acc = acc.wrapping_add(xxh3_mix16(bytes, i * 16, secret, i * 16, seed));
+        i += 1;
+    }
+    acc = xxh3_avalanche(acc);
+    while i < len / 16 {
+        acc = acc.wrapping_add(xxh3_mix16(bytes, i * 16, secret, (i - 8) * 16 + 3, seed));
+        i += 1;
+    }
+    acc = acc.wrapping_add(xxh3_mix16(bytes, len - 16, secret, 136 - 17, seed));
+    xxh3_avalanche(acc)
+}
+
+#[inline]
+const fn xxh3_accumulate_stripe(acc: &mut [u64; 8], bytes: &[u8], i: usize, secret: &[u8], s: usize) {
+    let mut lane = 0;
+    while lane < 8 {
+        let value = read_u64_le(bytes, i + lane * 8);
+        let key = value ^ read_u64_le(secret, s + lane * 8);
+        acc[lane ^ 1] = acc[lane ^ 1].wrapping_add(value);
+        acc[lane] = acc[lane].wrapping_add((key & 0xFFFFFFFF).wrapping_mul(key >> 32));
+        lane += 1;
+    }
+}
+
+#[inline]
+const fn xxh3_scramble(acc: &mut [u64; 8], secret: &[u8]) {
+    let mut lane = 0;
+    while lane < 8 {
+        let key = read_u64_le(secret, XXH3_SECRET_LEN - XXH3_STRIPE_LEN + lane * 8);
+        acc[lane] = (acc[lane] ^ (acc[lane] >> 47) ^ key).wrapping_mul(XXH_PRIME32_1);
+        lane += 1;
+    }
+}
+
+// 1024 bytes blocks of 64 bytes stripes, the seed is folded in the secret
+const fn xxh3_hash_long(bytes: &[u8], seed: u64) -> u64 {
+    let mut secret = XXH3_DEFAULT_SECRET;
+    if seed != 0 {
+        let mut i = 0;
+        while i < XXH3_SECRET_LEN {
+            let low = read_u64_le(&XXH3_DEFAULT_SECRET, i).wrapping_add(seed).to_le_bytes();
+            let high = read_u64_le(&XXH3_DEFAULT_SECRET, i + 8).wrapping_sub(seed).to_le_bytes();
+            let mut j = 0;
+            while j < 8 {
+                secret[i + j] = low[j];
+                secret[i + 8 + j] = high[j];
+                j += 1;
+            }
+            i += 16;
+        }
+    }
+
+    let len = bytes.len();
+    let mut acc = [XXH_PRIME32_3, XXH_PRIME64_1, XXH_PRIME64_2, XXH_PRIME64_3, XXH_PRIME64_4, XXH_PRIME32_2, XXH_PRIME64_5, XXH_PRIME32_1];
+    let block_len = XXH3_STRIPE_LEN * XXH3_STRIPES_PER_BLOCK;
+    let blocks_num = (len - 1) / block_len;
+    let mut block = 0;
+    while block < blocks_num {
+        let mut stripe = 0;
+        while stripe < XXH3_STRIPES_PER_BLOCK {
+            xxh3_accumulate_stripe(&mut acc, bytes, block * block_len + stripe * XXH3_STRIPE_LEN, &secret, stripe * 8);
+            stripe += 1;
+        }
+        xxh3_scramble(&mut acc, &secret);
+        block += 1;
+    }
+
+    let stripes_num = ((len - 1) - blocks_num * block_len) / XXH3_STRIPE_LEN;
+    let mut stripe = 0;
+    while stripe < stripes_num {
+        xxh3_accumulate_stripe(&mut acc, bytes, blocks_num * block_len + stripe * XXH3_STRIPE_LEN, &secret, stripe * 8);
+        stripe += 1;
+    }
+    xxh3_accumulate_stripe(&mut acc, bytes, len - XXH3_STRIPE_LEN, &secret, XXH3_SECRET_LEN - XXH3_STRIPE_LEN - 7);
+
+    let mut hash = (len as u64).wrapping_mul(XXH_PRIME64_1);
+    let mut lane = 0;
+    while lane < 8 {
+        hash = hash.wrapping_add(mul128_fold64(acc[lane] ^ read_u64_le(&secret, 11 + lane * 8), acc[lane + 1] ^ read_u64_le(&secret, 11 + lane * 8 + 8)));
+        lane += 2;
+    }
+    xxh3_avalanche(hash)
+}
+
+// XXH3 64 bits with the default secret, usable at compile time. Faster than xxh64_hash on short inputs
+pub const fn xxh3_hash(bytes: &[u8], seed: u64) -> u64 {
+    match bytes.len() {
+        0..=16 => xxh3_hash_0_to_16(bytes, seed),
+        17..=128 => xxh3_hash_17_to_128(bytes, seed),
+        129..=240 => xxh3_hash_129_to_240(bytes, seed),
+        _ => xxh3_hash_long(bytes, seed),
+    }
+}
+
+pub const fn xxh3_hash_const<const N: usize>(bytes: &[u8; N], seed: u64) -> u64 {
+    xxh3_hash(bytes, seed)
+}
+
+pub const fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+pub const fn murmur3_fmix64(x: u64) -> u64 {
+    let mut k = x;
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xFF51AFD7ED558CCD);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xC4CEB9FE1A85EC53);
+    k ^ (k >> 33)
+}
+
+#[inline]
+const fn wyhash_mum(a: u64, b: u64) -> (u64, u64) {
+    let product = a as u128 * b as u128;
+    (product as u64, (product >> 64) as u64)
+}
+
+// wyhash64(x, 0): a single multiply-fold keeps too much of the input structure
+pub const fn wyhash_mix(x: u64) -> u64 {
+    let (lo, hi) = wyhash_mum(x ^ 0x2D358DCCAA6C78A5, 0x8BB84B93962EACC9);
+    let (lo, hi) = wyhash_mum(lo ^ 0x2D358DCCAA6C78A5, hi ^ 0x8BB84B93962EACC9);
+    lo ^ hi
+}
+
+// Applied by RawSet to key hashes before bucketing, identity keeps the old behaviour
+pub trait HashMixer {
+    fn mix(hash: usize) -> usize;
+}
+
+pub struct IdentityMixer;
+
+impl HashMixer for IdentityMixer {
+    #[inline(always)]
+    fn mix(hash: usize) -> usize {
+        hash
+    }
+}
+
+pub struct SplitMix64Mixer;
+
+impl HashMixer for SplitMix64Mixer {
+    #[inline(always)]
+    fn mix(hash: usize) -> usize {
+        splitmix64(hash as u64) as usize
+    }
+}
+
+pub struct Murmur3Mixer;
+
+impl HashMixer for Murmur3Mixer {
+    #[inline(always)]
+    fn mix(hash: usize) -> usize {
+        murmur3_fmix64(hash as u64) as usize
+    }
+}
+
+pub struct WyMixer;
+
+impl HashMixer for WyMixer {
+    #[inline(always)]
+    fn mix(hash: usize) -> usize {
+        wyhash_mix(hash as u64) as usize
+    }
+}
+
 pub trait FastHash {
     fn fast_hash(&self) -> usize;
 }
 
 pub trait SetKey : Eq + Unpin + FastHash { }
 
-impl FastHash for i32 {
-    fn fast_hash(&self) -> usize {
-        *self as usize
-    }
+#[inline]
+pub const fn hash_combine(seed: usize, hash: usize) -> usize {
+    seed ^ (hash
+        .wrapping_add(0x9e3779b97f4a7c15u64 as usize)
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2))
 }
 
-impl FastHash for u32 {
-    fn fast_hash(&self) -> usize {
-        *self as usize
+macro_rules! impl_fast_hash_identity {
+    ($($t:ty),*) => {
+        $(
+            impl FastHash for $t {
+                #[inline]
+                fn fast_hash(&self) -> usize {
+                    *self as usize
+                }
+            }
+
+            impl SetKey for $t { }
+        )*
     }
 }
 
-impl FastHash for i64 {
+impl_fast_hash_identity!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, char, bool);
+
+impl FastHash for i128 {
+    #[inline]
     fn fast_hash(&self) -> usize {
-        *self as usize
+        (*self as u128).fast_hash()
     }
 }
 
-impl FastHash for u64 {
+impl FastHash for u128 {
+    #[inline]
     fn fast_hash(&self) -> usize {
-        *self as usize
+        hash_combine(*self as u64 as usize, (*self >> 64) as u64 as usize)
     }
 }
 
+impl SetKey for i128 { }
+impl SetKey for u128 { }
+
 impl FastHash for str {
     fn fast_hash(&self) -> usize {
         fnv_hash::<false>(self.as_bytes()) as usize
@@ -70,14 +488,103 @@ impl FastHash for TypeId {
     }
 }
 
-impl SetKey for i32 { }
-impl SetKey for u32 { }
-impl SetKey for i64 { }
-impl SetKey for u64 { }
 impl SetKey for str { }
 impl SetKey for String { }
 impl SetKey for TypeId { }
 
+impl<T: FastHash + ?Sized> FastHash for &T {
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        (**self).fast_hash()
+    }
+}
+
+impl<T: SetKey + ?Sized> SetKey for &T { }
+
+impl<T: FastHash> FastHash for [T] {
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        let mut hash = self.len();
+        for item in self {
+            hash = hash_combine(hash, item.fast_hash());
+        }
+        hash
+    }
+}
+
+impl<T: SetKey> SetKey for [T] { }
+
+impl<T: FastHash, const N: usize> FastHash for [T; N] {
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        self.as_slice().fast_hash()
+    }
+}
+
+impl<T: SetKey, const N: usize> SetKey for [T; N] { }
+
+impl<T: FastHash> FastHash for Option<T> {
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        match self {
+            Some(value) => hash_combine(1, value.fast_hash()),
+            None => 0,
+        }
+    }
+}
+
+impl<T: SetKey> SetKey for Option<T> { }
+
+impl FastHash for () {
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        0
+    }
+}
+
+impl SetKey for () { }
+
+macro_rules! impl_fast_hash_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: FastHash),+> FastHash for ($($name,)+) {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn fast_hash(&self) -> usize {
+                let ($($name,)+) = self;
+                let mut hash = 0usize;
+                $(hash = hash_combine(hash, $name.fast_hash());)+
+                hash
+            }
+        }
+
+        impl<$($name: SetKey),+> SetKey for ($($name,)+) { }
+    }
+}
+
+impl_fast_hash_tuple!(A);
+impl_fast_hash_tuple!(A B);
+impl_fast_hash_tuple!(A B C);
+impl_fast_hash_tuple!(A B C D);
+impl_fast_hash_tuple!(A B C D E);
+impl_fast_hash_tuple!(A B C D E F);
+impl_fast_hash_tuple!(A B C D E F G);
+impl_fast_hash_tuple!(A B C D E F G H);
+
+#[cfg(feature = "nalgebra-glm")]
+impl<T, const D: usize> FastHash for nalgebra_glm::TVec<T, D> where
+    T: FastHash + nalgebra_glm::Scalar
+{
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        self.as_slice().fast_hash()
+    }
+}
+
+#[cfg(feature = "nalgebra-glm")]
+impl<T, const D: usize> SetKey for nalgebra_glm::TVec<T, D> where
+    T: SetKey + nalgebra_glm::Scalar
+{ }
+
 pub trait SetItem : Unpin {
     type KeyType : SetKey;
 
@@ -111,6 +618,11 @@ impl<K: SetKey, V: Unpin> KeyValuePair<K, V> {
         &mut self.1
     }
 
+    #[inline]
+    pub fn get_key_value_mut(&mut self) -> (&K, &mut V) {
+        (&self.0, &mut self.1)
+    }
+
     #[inline]
     pub fn swap_value(&mut self, new_value: V) -> V {
         std::mem::replace(self.get_value_mut(), new_value)
@@ -0,0 +1,156 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{Array, AssetManager, Map, RString, StringAtom};
+
+const EVENTS_BUFFER_SIZE: usize = 4096;
+
+struct WatchedDirectory {
+    descriptor: WatchDescriptor,
+    mount: StringAtom,
+    relative_path: RString, // "" or "dir/" in the mount
+    directory: PathBuf,
+}
+
+/// Watches directories mounted in the VFS and reloads the assets of the files written in them.
+/// Editors often write a file several times in a row, it is reloaded once no event came for the debounce delay.
+pub struct HotReloader {
+    inotify: Inotify,
+    directories: Array<WatchedDirectory>,
+    changes: Map<StringAtom, Instant>, // last event by VFS path
+    debounce: Duration,
+    buffer: Array<u8>,
+}
+
+impl HotReloader {
+    pub fn new(debounce: Duration) -> io::Result<Self> {
+        let mut buffer = Array::with_capacity(EVENTS_BUFFER_SIZE);
+        buffer.insert_range(0..EVENTS_BUFFER_SIZE, 0);
+        Ok(HotReloader {
+            inotify: Inotify::init()?,
+            directories: Array::new(),
+            changes: Map::new(),
+            debounce,
+            buffer,
+        })
+    }
+
+    #[inline]
+    pub fn directories_num(&self) -> usize {
+        self.directories.num()
+    }
+
+    // Changed files waiting for the debounce delay
+    #[inline]
+    pub fn changes_num(&self) -> usize {
+        self.changes.num()
+    }
+
+    /// Watches the directory mounted as `mount` and its subdirectories, the ones created later included
+    pub fn watch(&mut self, mount: &str, directory: impl AsRef<Path>) -> io::Result<()> {
+        let mount = mount.trim_end_matches(":/");
+        if mount.is_empty() || mount.len() > STRINGS_TABLE_ENTRY_MAX_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid mount name '{}'", mount)));
+        }
+        let mount = StringAtom::from(mount);
+        self.watch_directory(mount, directory.as_ref(), RString::new())
+    }
+
+    fn watch_directory(&mut self, mount: StringAtom, directory: &Path, relative_path: RString) -> io::Result<()> {
+        let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE | WatchMask::CREATE | WatchMask::ONLYDIR;
+        let descriptor = self.inotify.watches().add(directory, mask)?;
+        // Watching a directory twice gives the same descriptor
+        if !self.directories.iter().any(|watched| watched.descriptor == descriptor) {
+            self.directories.push_back(WatchedDirectory { descriptor, mount, relative_path: relative_path.clone(), directory: directory.to_path_buf() });
+        }
+
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    let mut path = relative_path.clone();
+                    path.push_str(name);
+                    path.push('/');
+                    self.watch_directory(mount, &entry.path(), path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the file events then reloads the files that stopped changing, returns their VFS paths
+    pub fn update(&mut self, assets: &AssetManager) -> io::Result<Array<StringAtom>> {
+        let now = Instant::now();
+        self.read_events(now)?;
+
+        let mut reloaded = Array::new();
+        for (path, time) in self.changes.iter() {
+            if now.duration_since(*time) >= self.debounce {
+                reloaded.push_back(*path);
+            }
+        }
+        for path in reloaded.iter() {
+            self.changes.remove(path);
+            assets.reload(&path.to_string());
+        }
+        Ok(reloaded)
+    }
+
+    fn read_events(&mut self, now: Instant) -> io::Result<()> {
+        let mut new_directories = Array::new();
+        loop {
+            let events = match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => events,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            };
+            for event in events {
+                if event.mask.contains(EventMask::IGNORED) {
+                    // The directory was removed
+                    let index = self.directories.iter().position(|watched| watched.descriptor == event.wd);
+                    if let Some(index) = index {
+                        self.directories.swap_remove(index);
+                    }
+                    continue;
+                }
+                let Some(watched) = self.directories.iter().find(|watched| watched.descriptor == event.wd) else {
+                    continue;
+                };
+                let Some(name) = event.name.and_then(|name| name.to_str()) else {
+                    continue;
+                };
+
+                if event.mask.contains(EventMask::ISDIR) {
+                    if event.mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                        let mut relative_path = watched.relative_path.clone();
+                        relative_path.push_str(name);
+                        relative_path.push('/');
+                        new_directories.push_back((watched.mount, watched.directory.join(name), relative_path));
+                    }
+                    continue;
+                }
+
+                let mut path = RString::new();
+                path.push_str(&watched.mount.to_string());
+                path.push_str(":/");
+                path.push_str(&watched.relative_path);
+                path.push_str(name);
+                if path.len() <= STRINGS_TABLE_ENTRY_MAX_LEN {
+                    // Debounced from the last event
+                    self.changes.insert(StringAtom::from(&path), now);
+                }
+            }
+        }
+
+        for (mount, directory, relative_path) in new_directories.iter() {
+            // Removed again since the event, nothing to watch
+            let _ = self.watch_directory(*mount, directory, relative_path.clone());
+        }
+        Ok(())
+    }
+}
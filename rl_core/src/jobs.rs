@@ -0,0 +1,493 @@
+use std::any::Any;
+use std::alloc::Layout;
+use std::cell::{Cell, RefCell, UnsafeCell};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{Array, DequeStealer, DequeWorker, work_stealing_deque};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct Shared {
+    // Stealing ends of the worker deques, workers push and pop at the bottom of their own one
+    stealers: Array<DequeStealer<Job>>,
+    // Jobs pushed from threads that aren't workers
+    injector: Mutex<VecDeque<Job>>,
+    queued_jobs: AtomicUsize,
+    // Threads sleeping in JobCounter::wait
+    waiters: AtomicUsize,
+    sleep_lock: Mutex<()>,
+    wake_up: Condvar,
+    shutdown: AtomicBool,
+}
+
+thread_local! {
+    // (shared state address, worker index) of the job system owning this thread
+    static CURRENT_WORKER: Cell<(usize, usize)> = const { Cell::new((0, usize::MAX)) };
+    // Owning end of the deque of the worker running on this thread
+    static WORKER_DEQUE: RefCell<Option<DequeWorker<Job>>> = const { RefCell::new(None) };
+}
+
+impl Shared {
+    #[inline]
+    fn workers_num(&self) -> usize {
+        self.stealers.num()
+    }
+
+    #[inline]
+    fn current_worker(&self) -> usize {
+        let (shared, worker) = CURRENT_WORKER.with(|current| current.get());
+        if shared == self as *const Shared as usize { worker } else { usize::MAX }
+    }
+
+    fn push(&self, job: Job) {
+        if self.current_worker() == usize::MAX {
+            self.injector.lock().unwrap().push_back(job);
+        } else {
+            WORKER_DEQUE.with(|deque| deque.borrow_mut().as_mut().unwrap().push(job));
+        }
+        self.queued_jobs.fetch_add(1, Ordering::SeqCst);
+
+        // Taking the lock orders the notification after a sleeper checked queued_jobs
+        let _guard = self.sleep_lock.lock().unwrap();
+        self.wake_up.notify_one();
+    }
+
+    fn find_job(&self, worker: usize) -> Option<Job> {
+        if self.queued_jobs.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+
+        let job = if worker != usize::MAX {
+            WORKER_DEQUE.with(|deque| deque.borrow_mut().as_mut().unwrap().pop())
+        } else {
+            None
+        };
+
+        let job = job.or_else(|| self.injector.lock().unwrap().pop_front()).or_else(|| {
+            let workers_num = self.workers_num();
+            let first = if worker == usize::MAX { 0 } else { worker + 1 };
+            (0..workers_num)
+                .map(|i| (first + i) % workers_num)
+                .filter(|&other| other != worker)
+                .find_map(|other| self.stealers[other].steal())
+        });
+
+        if job.is_some() {
+            self.queued_jobs.fetch_sub(1, Ordering::SeqCst);
+        }
+        job
+    }
+
+    // Wakes the threads waiting for a counter, the job may have finished it
+    fn run_job(&self, job: Job) {
+        job();
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            let _guard = self.sleep_lock.lock().unwrap();
+            self.wake_up.notify_all();
+        }
+    }
+
+    fn worker_loop(&self, worker: usize, deque: DequeWorker<Job>) {
+        CURRENT_WORKER.with(|current| current.set((self as *const Shared as usize, worker)));
+        WORKER_DEQUE.with(|local| *local.borrow_mut() = Some(deque));
+
+        loop {
+            if let Some(job) = self.find_job(worker) {
+                self.run_job(job);
+                continue;
+            }
+
+            let guard = self.sleep_lock.lock().unwrap();
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            if self.queued_jobs.load(Ordering::SeqCst) == 0 {
+                // The timeout only guards against missed wake ups, pushes always notify
+                let _ = self.wake_up.wait_timeout(guard, Duration::from_millis(10)).unwrap();
+            }
+        }
+        WORKER_DEQUE.with(|local| local.borrow_mut().take());
+    }
+}
+
+struct CounterState {
+    remaining: AtomicUsize,
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+// Counts unfinished jobs, waiting on it runs queued jobs and only sleeps when there are none
+#[derive(Clone)]
+pub struct JobCounter {
+    state: Arc<CounterState>,
+}
+
+impl JobCounter {
+    pub fn new() -> Self {
+        JobCounter {
+            state: Arc::new(CounterState { remaining: AtomicUsize::new(0), panic: Mutex::new(None) }),
+        }
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.state.remaining.load(Ordering::Acquire) == 0
+    }
+
+    #[inline]
+    fn increment(&self) {
+        self.state.remaining.fetch_add(1, Ordering::AcqRel);
+    }
+
+    // SeqCst with the waiters count, see wait_without_panic
+    #[inline]
+    fn decrement(&self) {
+        self.state.remaining.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    // Runs queued jobs while the counted ones aren't done, sleeps when there's none
+    fn wait_without_panic(&self, job_system: &JobSystem) {
+        let shared = &*job_system.shared;
+        let worker = shared.current_worker();
+        while !self.is_done() {
+            if let Some(job) = shared.find_job(worker) {
+                shared.run_job(job);
+                continue;
+            }
+
+            // Either the last decrement sees the waiter and notifies after we sleep, or we see it done
+            let guard = shared.sleep_lock.lock().unwrap();
+            shared.waiters.fetch_add(1, Ordering::SeqCst);
+            if self.state.remaining.load(Ordering::SeqCst) != 0 && shared.queued_jobs.load(Ordering::SeqCst) == 0 {
+                let _ = shared.wake_up.wait_timeout(guard, Duration::from_millis(10)).unwrap();
+            } else {
+                drop(guard);
+            }
+            shared.waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // Resumes the first panic of the counted jobs
+    pub fn wait(&self, job_system: &JobSystem) {
+        self.wait_without_panic(job_system);
+        if let Some(payload) = self.state.panic.lock().unwrap().take() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Default for JobCounter {
+    fn default() -> Self {
+        JobCounter::new()
+    }
+}
+
+pub struct JobHandle<'a> {
+    job_system: &'a JobSystem,
+    counter: JobCounter,
+}
+
+impl JobHandle<'_> {
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.counter.is_done()
+    }
+
+    #[inline]
+    pub fn wait(self) {
+        self.counter.wait(self.job_system);
+    }
+}
+
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Array<JoinHandle<()>>,
+}
+
+static GLOBAL_JOB_SYSTEM: OnceLock<JobSystem> = OnceLock::new();
+
+impl JobSystem {
+    pub fn new(workers_num: usize) -> Self {
+        let workers_num = workers_num.max(1);
+        let mut deques = Array::new();
+        let mut stealers = Array::new();
+        for _ in 0..workers_num {
+            let (deque, stealer) = work_stealing_deque();
+            deques.push_back(deque);
+            stealers.push_back(stealer);
+        }
+        let shared = Arc::new(Shared {
+            stealers,
+            injector: Mutex::new(VecDeque::new()),
+            queued_jobs: AtomicUsize::new(0),
+            waiters: AtomicUsize::new(0),
+            sleep_lock: Mutex::new(()),
+            wake_up: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let workers = (0..workers_num)
+            .map(|worker| {
+                let deque = deques.pop_front();
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("Worker {}", worker))
+                    .spawn(move || shared.worker_loop(worker, deque))
+                    .expect("Failed to spawn a worker thread")
+            })
+            .collect();
+
+        JobSystem { shared, workers }
+    }
+
+    // One worker per core, minus the calling thread
+    pub fn global() -> &'static JobSystem {
+        GLOBAL_JOB_SYSTEM.get_or_init(|| {
+            let cores_num = thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1);
+            JobSystem::new(cores_num.saturating_sub(1))
+        })
+    }
+
+    #[inline]
+    pub fn workers_num(&self) -> usize {
+        self.shared.workers_num()
+    }
+
+    // usize::MAX when not called from one of the workers
+    #[inline]
+    pub fn current_worker(&self) -> usize {
+        self.shared.current_worker()
+    }
+
+    // Adds the job to the counter, the counter can be shared by many jobs
+    pub fn spawn_with_counter<F>(&self, counter: &JobCounter, job: F) where
+        F: FnOnce() + Send + 'static
+    {
+        counter.increment();
+        let counter = counter.clone();
+        self.shared.push(Box::new(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                counter.state.panic.lock().unwrap().get_or_insert(payload);
+            }
+            counter.decrement();
+        }));
+    }
+
+    pub fn spawn<F>(&self, job: F) -> JobHandle<'_> where
+        F: FnOnce() + Send + 'static
+    {
+        let counter = JobCounter::new();
+        self.spawn_with_counter(&counter, job);
+        JobHandle { job_system: self, counter }
+    }
+
+    // Jobs spawned in the scope can borrow from the caller, they are all finished when scope returns
+    pub fn scope<'scope, F, R>(&'scope self, scope_fn: F) -> R where
+        F: for<'s> FnOnce(&'s Scope<'scope>) -> R
+    {
+        let scope = Scope { job_system: self, counter: JobCounter::new(), marker: PhantomData };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| scope_fn(&scope)));
+        scope.counter.wait_without_panic(self);
+
+        match result {
+            Ok(result) => {
+                if let Some(payload) = scope.counter.state.panic.lock().unwrap().take() {
+                    panic::resume_unwind(payload);
+                }
+                result
+            },
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    // Calls f for every index, grain indices per job
+    pub fn parallel_for<F>(&self, range: Range<usize>, grain: usize, f: F) where
+        F: Fn(usize) + Sync
+    {
+        let grain = grain.max(1);
+        if range.len() <= grain {
+            range.for_each(&f);
+            return;
+        }
+
+        let f = &f;
+        self.scope(|scope| {
+            let mut start = range.start + grain;
+            while start < range.end {
+                let end = (start + grain).min(range.end);
+                scope.spawn(move || (start..end).for_each(f));
+                start = end;
+            }
+            (range.start..range.start + grain).for_each(f);
+        });
+    }
+
+    // Same as parallel_for with mutable access to the items
+    pub fn parallel_for_slice<T, F>(&self, items: &mut [T], grain: usize, f: F) where
+        T: Send,
+        F: Fn(usize, &mut T) + Sync
+    {
+        let grain = grain.max(1);
+        let f = &f;
+        self.scope(|scope| {
+            for (chunk_index, chunk) in items.chunks_mut(grain).enumerate() {
+                scope.spawn(move || {
+                    for (i, item) in chunk.iter_mut().enumerate() {
+                        f(chunk_index * grain + i, item);
+                    }
+                });
+            }
+        });
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        {
+            let _guard = self.shared.sleep_lock.lock().unwrap();
+            self.shared.shutdown.store(true, Ordering::SeqCst);
+            self.shared.wake_up.notify_all();
+        }
+        while !self.workers.is_empty() {
+            let _ = self.workers.pop_back().join();
+        }
+    }
+}
+
+pub struct Scope<'scope> {
+    job_system: &'scope JobSystem,
+    counter: JobCounter,
+    marker: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> Scope<'scope> {
+    pub fn spawn<F>(&self, job: F) where
+        F: FnOnce() + Send + 'scope
+    {
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(job);
+        // JobSystem::scope waits for the counter before 'scope ends
+        let job: Box<dyn FnOnce() + Send + 'static> = unsafe{ std::mem::transmute(job) };
+        self.job_system.spawn_with_counter(&self.counter, job);
+    }
+}
+
+const FRAME_ARENA_CHUNK_SIZE: usize = 64 * 1024;
+
+static FRAME_INDEX: AtomicU64 = AtomicU64::new(0);
+
+// Bump allocator for per frame scratch data, only for types without drop
+pub struct FrameArena {
+    chunks: UnsafeCell<Array<(NonNull<u8>, Layout)>>,
+    offset: Cell<usize>,
+    frame: u64,
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        FrameArena { chunks: UnsafeCell::new(Array::new()), offset: Cell::new(0), frame: 0 }
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        let chunks = unsafe{ &mut *self.chunks.get() };
+        if let Some((chunk, chunk_layout)) = chunks.last() {
+            let start = (chunk.as_ptr() as usize + self.offset.get()).next_multiple_of(layout.align());
+            let end = start + layout.size();
+            if end <= chunk.as_ptr() as usize + chunk_layout.size() {
+                self.offset.set(end - chunk.as_ptr() as usize);
+                return unsafe{ NonNull::new_unchecked(start as *mut u8) };
+            }
+        }
+
+        let chunk_size = FRAME_ARENA_CHUNK_SIZE.max(layout.size() + layout.align());
+        let chunk_layout = Layout::from_size_align(chunk_size, layout.align().max(16)).unwrap();
+        let chunk = NonNull::new(unsafe{ std::alloc::alloc(chunk_layout) })
+            .unwrap_or_else(|| std::alloc::handle_alloc_error(chunk_layout));
+        chunks.push_back((chunk, chunk_layout));
+        self.offset.set(layout.size());
+        chunk
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T: Copy>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+        unsafe {
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice<T: Copy>(&self, items: &[T]) -> &mut [T] {
+        let ptr = self.alloc_layout(Layout::array::<T>(items.len()).unwrap()).cast::<T>();
+        unsafe {
+            std::ptr::copy_nonoverlapping(items.as_ptr(), ptr.as_ptr(), items.len());
+            std::slice::from_raw_parts_mut(ptr.as_ptr(), items.len())
+        }
+    }
+
+    // Bytes used in the current chunks
+    pub fn allocated_size(&self) -> usize {
+        let chunks = unsafe{ &*self.chunks.get() };
+        match chunks.split_last() {
+            Some((_, previous)) => previous.iter().map(|(_, layout)| layout.size()).sum::<usize>() + self.offset.get(),
+            None => 0,
+        }
+    }
+
+    // Keeps the last chunk for the next frame
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        while chunks.num() > 1 {
+            let (chunk, layout) = chunks.remove(0);
+            unsafe{ std::alloc::dealloc(chunk.as_ptr(), layout) };
+        }
+        self.offset.set(0);
+    }
+}
+
+impl Drop for FrameArena {
+    fn drop(&mut self) {
+        for (chunk, layout) in self.chunks.get_mut().iter() {
+            unsafe{ std::alloc::dealloc(chunk.as_ptr(), *layout) };
+        }
+    }
+}
+
+impl Default for FrameArena {
+    fn default() -> Self {
+        FrameArena::new()
+    }
+}
+
+thread_local! {
+    static FRAME_ARENA: RefCell<FrameArena> = RefCell::new(FrameArena::new());
+}
+
+// Allocations can't outlive the closure, so every arena can be reset lazily on the next frame
+pub fn with_frame_arena<F, R>(f: F) -> R where
+    F: FnOnce(&FrameArena) -> R
+{
+    FRAME_ARENA.with(|arena| {
+        let frame = FRAME_INDEX.load(Ordering::Acquire);
+        if let Ok(mut arena) = arena.try_borrow_mut() {
+            if arena.frame != frame {
+                arena.reset();
+                arena.frame = frame;
+            }
+        }
+        f(&arena.borrow())
+    })
+}
+
+// Called once per frame, scratch memory of the previous frame gets reused
+pub fn advance_frame_arenas() {
+    FRAME_INDEX.fetch_add(1, Ordering::AcqRel);
+}
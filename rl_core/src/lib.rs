@@ -1,18 +1,42 @@
+// Lets the derive macros refer to ::rl_core from inside this crate too
+extern crate self as rl_core;
+
 mod fast_hash;
 
 pub use fast_hash::fnv_hash_const;
 pub use fast_hash::fnv_hash;
+pub use fast_hash::fnv_hash64_const;
+pub use fast_hash::fnv_hash64;
+pub use fast_hash::xxh64_hash_const;
+pub use fast_hash::xxh64_hash;
+pub use fast_hash::xxh3_hash_const;
+pub use fast_hash::xxh3_hash;
+pub use fast_hash::{splitmix64, murmur3_fmix64, wyhash_mix};
+pub use fast_hash::hash_combine;
+
+pub use fast_hash::HashMixer;
+pub use fast_hash::{IdentityMixer, SplitMix64Mixer, Murmur3Mixer, WyMixer};
 
 pub use fast_hash::FastHash;
 pub use fast_hash::SetKey;
 pub use fast_hash::SetItem;
 pub use fast_hash::KeyValuePair;
 
+pub use rl_core_derive::{FastHash, SetKey, SetItem};
+
 pub mod alloc;
 
 mod strings_table;
 
+pub use strings_table::Atom;
 pub use strings_table::StringAtom;
+pub use strings_table::CaseSensitiveAtom;
+pub use strings_table::dump_strings_table;
+
+mod string;
+
+pub use string::RString;
+pub use string::InlineString;
 
 mod raw_array;
 mod raw_set;
@@ -31,7 +55,116 @@ pub use array::InlineArray;
 pub use set::Set;
 pub use map::Map;
 
-//mod typed;
+mod bit_array;
+
+pub use bit_array::BitArray;
+pub use bit_array::BitSet;
+pub use bit_array::BitsIter;
+pub use bit_array::words_for_bits;
+
+mod priority_queue;
+
+pub use priority_queue::Compare;
+pub use priority_queue::OrdCompare;
+pub use priority_queue::PriorityQueue;
+pub use priority_queue::IndexedPriorityQueue;
+
+mod name_table;
+
+pub use name_table::NameIndex;
+pub use name_table::NameTableWriter;
+pub use name_table::NameTableReader;
+
+mod typed;
+
+pub use typed::TypeInfo;
+pub use typed::FieldInfo;
+pub use typed::Reflect;
+pub use typed::Reflected;
+pub use typed::{register_type_with, find_type, registered_types_num};
+pub use rl_core_derive::Reflect;
+
+mod any_array;
+
+pub use any_array::AnyArray;
+
+mod jobs;
+
+pub use jobs::JobSystem;
+pub use jobs::JobCounter;
+pub use jobs::JobHandle;
+pub use jobs::Scope;
+pub use jobs::FrameArena;
+pub use jobs::{with_frame_arena, advance_frame_arenas};
+
+mod queues;
+
+pub use queues::MpmcQueue;
+pub use queues::{spsc_ring, SpscProducer, SpscConsumer};
+pub use queues::{work_stealing_deque, DequeWorker, DequeStealer};
+
+mod events;
+
+pub use events::Event;
+pub use events::Subscription;
+pub use events::EventQueue;
+
+mod cvars;
+
+pub use cvars::CVars;
+pub use cvars::{CVarValue, CVarKind, CVarError};
+
+mod undo;
+
+pub use undo::UndoableCommand;
+pub use undo::UndoStack;
+pub use undo::{Snapshot, SnapshotCommand};
+
+mod archive;
+
+pub use archive::Archive;
+pub use archive::Serializable;
+pub use archive::{ArchiveWriter, ArchiveReader};
+pub use archive::{ArchiveError, ArchiveResult};
+
+mod relocatable;
+
+pub use relocatable::Relocatable;
+pub use relocatable::Validator;
+pub use relocatable::{ArchivedArray, ArchivedStr, ArchivedMap};
+pub use relocatable::{RelocatableBuilder, Resolver, Slot, ArraySlot, StrSlot, MapSlot};
+pub use relocatable::{AlignedBuffer, RelocatableFile};
+pub use relocatable::{access_root, access_root_unchecked};
+
+mod vfs;
+
+pub use vfs::Vfs;
+pub use vfs::{VfsSource, DirectorySource, MountId};
+pub use vfs::{PakWriter, PakFile, PakEntry, PakCompression};
+pub use vfs::{VfsError, VfsResult};
+pub use vfs::normalize_path;
+
+mod assets;
+
+pub use assets::AssetManager;
+pub use assets::{AssetLoader, LoadContext};
+pub use assets::{AssetHandle, UntypedHandle, LoadState};
+pub use assets::AssetError;
+
+#[cfg(all(feature = "hot-reload", target_os = "linux"))]
+mod hot_reload;
+
+#[cfg(all(feature = "hot-reload", target_os = "linux"))]
+pub use hot_reload::HotReloader;
+
+mod profiler;
+
+pub use profiler::{Profiler, ProfileScope};
+pub use profiler::{ProfileEvent, ProfileFrame, ScopeStats};
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
 //mod object;
 
 #[cfg(test)]
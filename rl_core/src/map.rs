@@ -3,19 +3,20 @@ use std::ops::{Index, IndexMut};
 
 use crate::RawSetEntry;
 use crate::Set;
-use crate::InlineArray;
-use crate::{SetKey, KeyValuePair, FastHash};
+use crate::{SetKey, SetItem, KeyValuePair, FastHash};
 use crate::alloc::{ArrayAllocator, DefaultAllocator};
+use crate::{HashMixer, IdentityMixer};
 
-pub struct Map<K, V, DataAlloc = DefaultAllocator, EntriesAlloc = DefaultAllocator, TableAlloc = DefaultAllocator>
+pub struct Map<K, V, DataAlloc = DefaultAllocator, EntriesAlloc = DefaultAllocator, TableAlloc = DefaultAllocator, Mix = IdentityMixer>
 (
-    Set<KeyValuePair<K, V>, DataAlloc, EntriesAlloc, TableAlloc>
+    Set<KeyValuePair<K, V>, DataAlloc, EntriesAlloc, TableAlloc, Mix>
 ) where
     K: SetKey,
     V: Unpin,
     DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>;
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer;
 
 impl<K: SetKey, V: Unpin> Map<K, V> {
     #[inline]
@@ -24,12 +25,13 @@ impl<K: SetKey, V: Unpin> Map<K, V> {
     }
 }
 
-impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     K: SetKey,
     V: Unpin,
     DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     #[inline]
     pub fn custom_allocators() -> Self {
@@ -37,12 +39,13 @@ impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAllo
     }
 }
 
-impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     K: SetKey,
     V: Unpin,
     DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     #[inline]
     pub fn capacity(&self) -> usize {
@@ -93,11 +96,11 @@ impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAllo
         K: Borrow<Q>,
         Q: FastHash + Eq
     {
-        let mut pair_array: InlineArray<KeyValuePair<K, V>, 1> = self.0.remove_all(key);
-        if pair_array.num() > 0 {
-            Option::Some(KeyValuePair::take_value(pair_array.swap_remove(0)))
-        } else {
+        let pair_index = self.0.find_first_index(key);
+        if pair_index == usize::MAX {
             Option::None
+        } else {
+            Option::Some(KeyValuePair::take_value(self.0.swap_remove(pair_index)))
         }
     }
 
@@ -127,6 +130,16 @@ impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAllo
         }
     }
 
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.0.iter().map(|pair| (pair.get_key(), pair.get_value()))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.0.iter_mut().map(|pair| pair.get_key_value_mut())
+    }
+
     #[inline]
     pub fn get_or_insert_mut(&mut self, key: K, value: V) -> &mut V {
         let index = self.0.find_index_or_insert_mut(KeyValuePair::new(key, value));
@@ -139,12 +152,13 @@ impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAllo
     }
 }
 
-impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     K: SetKey,
     V: Unpin + Default,
     DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     #[inline]
     pub fn get_or_insert_default_mut(&mut self, key: K) -> &mut V {
@@ -153,20 +167,21 @@ impl<K, V, DataAlloc, EntriesAlloc, TableAlloc> Map<K, V, DataAlloc, EntriesAllo
     }
 }
 
-impl<K: SetKey, V: Unpin> Default for Map<K, V> where
+impl<K: SetKey, V: Unpin, Mix: HashMixer> Default for Map<K, V, DefaultAllocator, DefaultAllocator, DefaultAllocator, Mix> where
 {
-    fn default() -> Map<K, V> {
-        Map::new()
+    fn default() -> Self {
+        Map::custom_allocators()
     }
 }
 
-impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Q> Index<&Q> for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix, Q> Index<&Q> for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     K: SetKey + Borrow<Q>,
     V: Unpin,
     DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
     TableAlloc: ArrayAllocator<usize>,
-    Q: FastHash + Eq + ?Sized
+    Q: FastHash + Eq + ?Sized,
+    Mix: HashMixer
 {
     type Output = V;
 
@@ -176,13 +191,14 @@ impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Q> Index<&Q> for Map<K, V, DataA
     }
 }
 
-impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Q> IndexMut<&Q> for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix, Q> IndexMut<&Q> for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     K: SetKey + Borrow<Q>,
     V: Unpin,
     DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
     TableAlloc: ArrayAllocator<usize>,
-    Q: FastHash + Eq + ?Sized
+    Q: FastHash + Eq + ?Sized,
+    Mix: HashMixer
 {
     #[inline]
     fn index_mut(&mut self, key: &Q) -> &mut V {
@@ -0,0 +1,123 @@
+use std::io::{self, Read, Write};
+
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{Array, Map, StringAtom};
+
+// How a StringAtom is written inside a payload: index in the name table plus numeric suffix
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NameIndex {
+    pub index: u32,
+    pub number: u32, // 0 is no number, same encoding as StringAtom
+}
+
+impl NameIndex {
+    pub const NONE: NameIndex = NameIndex { index: u32::MAX, number: 0 };
+
+    #[inline]
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.index.to_le_bytes())?;
+        writer.write_all(&self.number.to_le_bytes())
+    }
+
+    #[inline]
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(NameIndex { index: read_u32(reader)?, number: read_u32(reader)? })
+    }
+}
+
+#[inline]
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// Collects the atoms used by a payload, every base string is written once
+#[derive(Default)]
+pub struct NameTableWriter {
+    names: Array<StringAtom>,
+    indices: Map<StringAtom, u32>,
+}
+
+impl NameTableWriter {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.names.num()
+    }
+
+    pub fn add(&mut self, atom: StringAtom) -> NameIndex {
+        if atom.is_none() {
+            return NameIndex::NONE;
+        }
+
+        let base = atom.without_number();
+        let next_index = self.names.num() as u32;
+        let index = *self.indices.get_or_insert_mut(base, next_index);
+        if index == next_index {
+            self.names.push_back(base);
+        }
+
+        NameIndex { index, number: atom.raw_number() }
+    }
+
+    // Layout (little endian): u32 names count, then for every name u8 length + utf8 bytes
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.names.num() as u32).to_le_bytes())?;
+        for name in &self.names {
+            let bytes = name.base_str().as_bytes();
+            writer.write_all(&[bytes.len() as u8])?;
+            writer.write_all(bytes)?;
+        }
+        Ok(())
+    }
+}
+
+// Remaps the names of a loaded payload on this process' strings table
+pub struct NameTableReader {
+    atoms: Array<StringAtom>,
+}
+
+impl NameTableReader {
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let names_num = read_u32(reader)? as usize;
+        let mut atoms = Array::new();
+        let mut buffer = [0u8; STRINGS_TABLE_ENTRY_MAX_LEN];
+        for _ in 0..names_num {
+            let mut len = [0u8; 1];
+            reader.read_exact(&mut len)?;
+            let len = len[0] as usize;
+            if len == 0 || len > STRINGS_TABLE_ENTRY_MAX_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid name length"));
+            }
+
+            let bytes = &mut buffer[..len];
+            reader.read_exact(bytes)?;
+            if std::str::from_utf8(bytes).is_err() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "name is not valid utf8"));
+            }
+
+            atoms.push_back(StringAtom::from_base_bytes(bytes));
+        }
+        Ok(NameTableReader { atoms })
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.atoms.num()
+    }
+
+    pub fn resolve(&self, name_index: NameIndex) -> Option<StringAtom> {
+        if name_index == NameIndex::NONE {
+            Some(StringAtom::none())
+        } else {
+            self.atoms
+                .get(name_index.index as usize)
+                .map(|atom| atom.with_raw_number(name_index.number))
+        }
+    }
+}
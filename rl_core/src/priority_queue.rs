@@ -0,0 +1,386 @@
+use std::cmp::Ordering;
+
+use crate::alloc::{AllocatorBase, DefaultAllocator};
+use crate::Array;
+
+pub trait Compare<T> {
+    // Greater means higher priority (popped first)
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+#[derive(Default, Copy, Clone)]
+pub struct OrdCompare;
+
+impl<T: Ord> Compare<T> for OrdCompare {
+    #[inline]
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T, F> Compare<T> for F where
+    F: Fn(&T, &T) -> Ordering
+{
+    #[inline]
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+// Returns the final position of the item
+#[inline]
+fn sift_up<T, F>(items: &mut [T], mut index: usize, is_higher: F) -> usize where
+    F: Fn(&T, &T) -> bool
+{
+    while index > 0 {
+        let parent = (index - 1) / 2;
+        if !is_higher(&items[index], &items[parent]) {
+            break;
+        }
+        items.swap(index, parent);
+        index = parent;
+    }
+    index
+}
+
+#[inline]
+fn sift_down<T, F>(items: &mut [T], mut index: usize, is_higher: F) -> usize where
+    F: Fn(&T, &T) -> bool
+{
+    let items_num = items.len();
+    loop {
+        let left = index * 2 + 1;
+        if left >= items_num {
+            break;
+        }
+        let right = left + 1;
+        let child = if right < items_num && is_higher(&items[right], &items[left]) { right } else { left };
+        if !is_higher(&items[child], &items[index]) {
+            break;
+        }
+        items.swap(index, child);
+        index = child;
+    }
+    index
+}
+
+pub struct PriorityQueue<T, A = DefaultAllocator, C = OrdCompare> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    heap: Array<T, A>,
+    compare: C,
+}
+
+impl<T: Unpin + Ord> PriorityQueue<T> {
+    #[inline]
+    pub fn new() -> Self {
+        PriorityQueue { heap: Array::new(), compare: OrdCompare }
+    }
+
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        PriorityQueue { heap: Array::with_capacity(capacity), compare: OrdCompare }
+    }
+}
+
+impl<T: Unpin, C: Compare<T>> PriorityQueue<T, DefaultAllocator, C> {
+    #[inline]
+    pub fn with_compare(compare: C) -> Self {
+        PriorityQueue { heap: Array::new(), compare }
+    }
+}
+
+impl<T, A, C> PriorityQueue<T, A, C> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    #[inline]
+    pub fn custom_allocator(compare: C) -> Self {
+        PriorityQueue { heap: Array::custom_allocator(), compare }
+    }
+
+    #[inline]
+    pub fn custom_allocator_with_capacity(compare: C, capacity: usize) -> Self {
+        PriorityQueue { heap: Array::custom_allocator_with_capacity(capacity), compare }
+    }
+}
+
+impl<T, A, C> PriorityQueue<T, A, C> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.heap.capacity()
+    }
+
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.heap.reserve(additional);
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.heap.num()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    // Items in heap order, not sorted
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        &self.heap
+    }
+
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.heap.push_back(value);
+        let last = self.heap.num() - 1;
+        let compare = &self.compare;
+        sift_up(&mut self.heap, last, |a, b| compare.compare(a, b) == Ordering::Greater);
+    }
+
+    #[inline]
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.first()
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.heap.is_empty() {
+            return None;
+        }
+
+        // swap_remove moves the last item on the root, then restore the heap
+        let top = self.heap.swap_remove(0);
+        let compare = &self.compare;
+        sift_down(&mut self.heap, 0, |a, b| compare.compare(a, b) == Ordering::Greater);
+        Some(top)
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    // Lowest priority first, like a sort using the same comparator
+    pub fn into_sorted_array(mut self) -> Array<T, A> {
+        let compare = &self.compare;
+        let mut end = self.heap.num();
+        while end > 1 {
+            end -= 1;
+            self.heap.swap(0, end);
+            sift_down(&mut self.heap[..end], 0, |a, b| compare.compare(a, b) == Ordering::Greater);
+        }
+        self.heap
+    }
+}
+
+impl<T: Unpin + Ord> Default for PriorityQueue<T> {
+    fn default() -> PriorityQueue<T> {
+        PriorityQueue::new()
+    }
+}
+
+impl<T, A, C> Extend<T> for PriorityQueue<T, A, C> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T: Unpin + Ord> FromIterator<T> for PriorityQueue<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut queue = PriorityQueue::new();
+        queue.extend(iter);
+        queue
+    }
+}
+
+struct IndexedHeapItem<T> {
+    key: usize,
+    value: T,
+}
+
+// Keys are small user indices (e.g. chunk index), positions are tracked per key
+// so priorities can be changed in place (decrease-key/increase-key)
+pub struct IndexedPriorityQueue<T, A = DefaultAllocator, C = OrdCompare> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    heap: Array<IndexedHeapItem<T>, A>,
+    positions: Array<usize>,
+    compare: C,
+}
+
+impl<T: Unpin + Ord> IndexedPriorityQueue<T> {
+    #[inline]
+    pub fn new() -> Self {
+        IndexedPriorityQueue { heap: Array::new(), positions: Array::new(), compare: OrdCompare }
+    }
+}
+
+impl<T: Unpin, C: Compare<T>> IndexedPriorityQueue<T, DefaultAllocator, C> {
+    #[inline]
+    pub fn with_compare(compare: C) -> Self {
+        IndexedPriorityQueue { heap: Array::new(), positions: Array::new(), compare }
+    }
+}
+
+impl<T, A, C> IndexedPriorityQueue<T, A, C> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    #[inline]
+    pub fn custom_allocator(compare: C) -> Self {
+        IndexedPriorityQueue { heap: Array::custom_allocator(), positions: Array::new(), compare }
+    }
+}
+
+impl<T, A, C> IndexedPriorityQueue<T, A, C> where
+    T: Unpin,
+    A: AllocatorBase,
+    C: Compare<T>
+{
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.heap.num()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    #[inline]
+    fn position_of(&self, key: usize) -> usize {
+        if key < self.positions.num() { self.positions[key] } else { usize::MAX }
+    }
+
+    #[inline]
+    pub fn contains(&self, key: usize) -> bool {
+        self.position_of(key) != usize::MAX
+    }
+
+    #[inline]
+    pub fn get(&self, key: usize) -> Option<&T> {
+        let position = self.position_of(key);
+        if position == usize::MAX {
+            None
+        } else {
+            Some(&self.heap[position].value)
+        }
+    }
+
+    fn sift_up(&mut self, position: usize) {
+        let compare = &self.compare;
+        let new_position = sift_up(&mut self.heap, position, |a, b| compare.compare(&a.value, &b.value) == Ordering::Greater);
+        self.update_path(new_position, position);
+    }
+
+    fn sift_down(&mut self, position: usize) {
+        let compare = &self.compare;
+        let new_position = sift_down(&mut self.heap, position, |a, b| compare.compare(&a.value, &b.value) == Ordering::Greater);
+        self.update_path(position, new_position);
+    }
+
+    // Fix positions of every item on the parent chain between the two heap positions
+    fn update_path(&mut self, top: usize, mut bottom: usize) {
+        loop {
+            let key = self.heap[bottom].key;
+            self.positions[key] = bottom;
+            if bottom <= top {
+                break;
+            }
+            bottom = (bottom - 1) / 2;
+        }
+    }
+
+    // Inserts the key, or changes its priority if already queued
+    pub fn push(&mut self, key: usize, value: T) {
+        let position = self.position_of(key);
+        if position != usize::MAX {
+            self.change_priority(key, value);
+            return;
+        }
+
+        if key >= self.positions.num() {
+            self.positions.insert_range(self.positions.num()..key + 1, usize::MAX);
+        }
+
+        let new_position = self.heap.num();
+        self.heap.push_back(IndexedHeapItem { key, value });
+        self.positions[key] = new_position;
+        self.sift_up(new_position);
+    }
+
+    #[inline]
+    pub fn peek(&self) -> Option<(usize, &T)> {
+        self.heap.first().map(|item| (item.key, &item.value))
+    }
+
+    pub fn change_priority(&mut self, key: usize, value: T) -> T {
+        let position = self.position_of(key);
+        debug_assert!(position != usize::MAX);
+
+        let old_value = std::mem::replace(&mut self.heap[position].value, value);
+        if self.compare.compare(&self.heap[position].value, &old_value) == Ordering::Greater {
+            self.sift_up(position);
+        } else {
+            self.sift_down(position);
+        }
+        old_value
+    }
+
+    pub fn remove(&mut self, key: usize) -> Option<T> {
+        let position = self.position_of(key);
+        if position == usize::MAX {
+            return None;
+        }
+
+        let removed = self.heap.swap_remove(position);
+        self.positions[key] = usize::MAX;
+
+        if position < self.heap.num() {
+            // The last item has been moved on the removed position, it can go either way
+            let moved_key = self.heap[position].key;
+            self.positions[moved_key] = position;
+            self.sift_up(position);
+            if self.positions[moved_key] == position {
+                self.sift_down(position);
+            }
+        }
+
+        Some(removed.value)
+    }
+
+    pub fn pop(&mut self) -> Option<(usize, T)> {
+        let key = self.heap.first()?.key;
+        self.remove(key).map(|value| (key, value))
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.positions.clear();
+    }
+}
+
+impl<T: Unpin + Ord> Default for IndexedPriorityQueue<T> {
+    fn default() -> IndexedPriorityQueue<T> {
+        IndexedPriorityQueue::new()
+    }
+}
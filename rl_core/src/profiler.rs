@@ -0,0 +1,283 @@
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Array, Map, RString, StringAtom};
+
+const DEFAULT_MAX_FRAMES: usize = 300;
+
+/// Opens a profiled scope ending with the enclosing block, the name must be a literal.
+/// Compiled out without the `profiler` feature.
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:literal) => {
+        let _profile_scope = $crate::ProfileScope::new({
+            static NAME: std::sync::OnceLock<$crate::StringAtom> = std::sync::OnceLock::new();
+            *NAME.get_or_init(|| $crate::StringAtom::from($name))
+        });
+    };
+}
+
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:literal) => {};
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProfileEvent {
+    pub name: StringAtom,
+    pub start: u64, // ns since the profiler was created
+    pub duration: u64,
+    pub depth: u32,
+    pub thread: u32, // index in Profiler::get_thread_names
+}
+
+pub struct ProfileFrame {
+    pub index: u64,
+    pub start: u64,
+    pub duration: u64,
+    pub events: Array<ProfileEvent>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScopeStats {
+    pub name: StringAtom,
+    pub calls: usize,
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+// Only locked by its thread and by end_frame, so the lock is almost never contended
+struct ThreadEvents {
+    index: u32,
+    events: Mutex<Array<ProfileEvent>>,
+}
+
+thread_local! {
+    static THREAD_EVENTS: OnceLock<Arc<ThreadEvents>> = const { OnceLock::new() };
+    static SCOPE_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Collects the scopes of every thread into frames, the last ones are kept for the statistics and the trace export
+pub struct Profiler {
+    epoch: Instant,
+    recording: AtomicBool,
+    max_frames: AtomicUsize,
+    threads: Mutex<Array<(RString, Arc<ThreadEvents>)>>,
+    frames: Mutex<Array<ProfileFrame>>,
+    frame: Mutex<(u64, u64)>, // index and start of the current frame
+}
+
+impl Profiler {
+    // Thread buffers belong to the global profiler, there's no other one
+    fn new() -> Self {
+        Profiler {
+            epoch: Instant::now(),
+            recording: AtomicBool::new(true),
+            max_frames: AtomicUsize::new(DEFAULT_MAX_FRAMES),
+            threads: Mutex::new(Array::new()),
+            frames: Mutex::new(Array::new()),
+            frame: Mutex::new((0, 0)),
+        }
+    }
+
+    pub fn global() -> &'static Profiler {
+        static GLOBAL: OnceLock<Profiler> = OnceLock::new();
+        GLOBAL.get_or_init(Profiler::new)
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    // Scopes opened while not recording are ignored
+    #[inline]
+    pub fn set_recording(&self, recording: bool) {
+        self.recording.store(recording, Ordering::Relaxed);
+    }
+
+    pub fn set_max_frames(&self, max_frames: usize) {
+        self.max_frames.store(max_frames.max(1), Ordering::Relaxed);
+        self.trim_frames(&mut self.frames.lock().unwrap());
+    }
+
+    #[inline]
+    pub fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    // Scope that ran on the calling thread
+    fn record(&self, name: StringAtom, start: u64, duration: u64, depth: u32) {
+        let events = THREAD_EVENTS.with(|events| events.get_or_init(|| self.register_thread()).clone());
+        let event = ProfileEvent { name, start, duration, depth, thread: events.index };
+        events.events.lock().unwrap().push_back(event);
+    }
+
+    fn register_thread(&self) -> Arc<ThreadEvents> {
+        let mut threads = self.threads.lock().unwrap();
+        let events = Arc::new(ThreadEvents { index: threads.num() as u32, events: Mutex::new(Array::new()) });
+        let name = match thread::current().name() {
+            Some(name) => RString::from(name),
+            None => {
+                let mut name = RString::new();
+                let _ = write!(name, "Thread {}", threads.num());
+                name
+            }
+        };
+        threads.push_back((name, events.clone()));
+        events
+    }
+
+    /// Closes the current frame with the scopes that ended during it, then opens the next one
+    pub fn end_frame(&self) {
+        let end = self.now();
+        let mut events = Array::new();
+        for (_, thread) in self.threads.lock().unwrap().iter() {
+            let mut thread_events = thread.events.lock().unwrap();
+            events.extend_from_slice(&thread_events);
+            thread_events.clear();
+        }
+        events.sort_by_key(|event: &ProfileEvent| (event.thread, event.start, event.depth));
+
+        let (index, start) = {
+            let mut frame = self.frame.lock().unwrap();
+            let previous = *frame;
+            *frame = (previous.0 + 1, end);
+            previous
+        };
+        let mut frames = self.frames.lock().unwrap();
+        frames.push_back(ProfileFrame { index, start, duration: end - start, events });
+        self.trim_frames(&mut frames);
+    }
+
+    fn trim_frames(&self, frames: &mut Array<ProfileFrame>) {
+        let max_frames = self.max_frames.load(Ordering::Relaxed);
+        while frames.num() > max_frames {
+            frames.pop_front();
+        }
+    }
+
+    #[inline]
+    pub fn frames_num(&self) -> usize {
+        self.frames.lock().unwrap().num()
+    }
+
+    pub fn clear(&self) {
+        self.frames.lock().unwrap().clear();
+    }
+
+    pub fn get_thread_names(&self) -> Array<RString> {
+        self.threads.lock().unwrap().iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Durations of every call of the scopes in the kept frames, sorted by decreasing total
+    pub fn get_stats(&self) -> Array<ScopeStats> {
+        let mut stats: Map<StringAtom, ScopeStats> = Map::new();
+        for frame in self.frames.lock().unwrap().iter() {
+            for event in frame.events.iter() {
+                let duration = Duration::from_nanos(event.duration);
+                let scope = stats.get_or_insert_mut(event.name, ScopeStats {
+                    name: event.name, calls: 0, min: Duration::MAX, avg: Duration::ZERO, max: Duration::ZERO, total: Duration::ZERO,
+                });
+                scope.calls += 1;
+                scope.min = scope.min.min(duration);
+                scope.max = scope.max.max(duration);
+                scope.total += duration;
+            }
+        }
+
+        let mut stats: Array<ScopeStats> = stats.iter().map(|(_, scope)| ScopeStats { avg: scope.total / scope.calls as u32, ..*scope }).collect();
+        stats.sort_by_key(|scope| std::cmp::Reverse(scope.total));
+        stats
+    }
+
+    /// Chrome trace_event JSON of the kept frames, opened by Perfetto and chrome://tracing
+    pub fn write_chrome_trace(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut json = RString::new();
+        json.push_str("{\"traceEvents\":[\n");
+        for (index, name) in self.get_thread_names().iter().enumerate() {
+            let _ = write!(json, "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":", index);
+            push_json_string(&mut json, name);
+            json.push_str("}},\n");
+        }
+
+        let frames = self.frames.lock().unwrap();
+        for frame in frames.iter() {
+            let _ = writeln!(json, "{{\"name\":\"Frame {}\",\"ph\":\"i\",\"s\":\"g\",\"pid\":1,\"tid\":0,\"ts\":{:.3}}},", frame.index, frame.start as f64 / 1000.0);
+            for event in frame.events.iter() {
+                json.push_str("{\"name\":");
+                push_json_string(&mut json, &event.name.to_string());
+                let _ = writeln!(json, ",\"cat\":\"rl\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}},",
+                    event.thread, event.start as f64 / 1000.0, event.duration as f64 / 1000.0);
+            }
+            writer.write_all(json.as_bytes())?;
+            json.clear();
+        }
+        // Without a trailing comma
+        let _ = write!(json, "{{\"name\":\"End\",\"ph\":\"i\",\"s\":\"g\",\"pid\":1,\"tid\":0,\"ts\":{:.3}}}\n]}}\n", self.now() as f64 / 1000.0);
+        writer.write_all(json.as_bytes())
+    }
+
+    pub fn save_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_chrome_trace(&mut writer)?;
+        writer.flush()
+    }
+}
+
+fn push_json_string(json: &mut RString, string: &str) {
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => { let _ = write!(json, "\\u{:04x}", c as u32); }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Times the scope it lives in, see profile_scope!
+pub struct ProfileScope {
+    name: StringAtom,
+    start: u64,
+    depth: u32,
+    recording: bool,
+}
+
+impl ProfileScope {
+    #[inline]
+    pub fn new(name: StringAtom) -> Self {
+        let profiler = Profiler::global();
+        let recording = profiler.is_recording();
+        let depth = SCOPE_DEPTH.with(|depth| {
+            let current = depth.get();
+            depth.set(current + 1);
+            current
+        });
+        ProfileScope { name, start: if recording { profiler.now() } else { 0 }, depth, recording }
+    }
+}
+
+impl Drop for ProfileScope {
+    #[inline]
+    fn drop(&mut self) {
+        SCOPE_DEPTH.with(|depth| depth.set(self.depth));
+        if self.recording {
+            let profiler = Profiler::global();
+            profiler.record(self.name, self.start, profiler.now() - self.start, self.depth);
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::VecDeque;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::ptr;
+
+#[cfg(loom)]
+use loom::sync::{Arc, Condvar, Mutex};
+#[cfg(loom)]
+use loom::sync::atomic::{fence, AtomicIsize, AtomicPtr, AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+
+#[cfg(not(loom))]
+use std::sync::{Arc, Condvar, Mutex};
+#[cfg(not(loom))]
+use std::sync::atomic::{fence, AtomicIsize, AtomicPtr, AtomicUsize, Ordering};
+
+use crate::Array;
+
+// Same interface as loom's UnsafeCell so the queues can be model checked
+#[cfg(not(loom))]
+struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    #[inline]
+    fn new(value: T) -> Self {
+        UnsafeCell(std::cell::UnsafeCell::new(value))
+    }
+
+    #[inline]
+    fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+// Keeps producer and consumer indices on separate cache lines
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+// Sleep/wake up for the blocking variants, the lock is only taken when someone sleeps.
+// try_fn never runs under the lock, it can notify other parkings.
+struct Parking {
+    sleepers: AtomicUsize,
+    epoch: AtomicUsize, // incremented by every notify seen by a sleeper
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl Parking {
+    fn new() -> Self {
+        Parking { sleepers: AtomicUsize::new(0), epoch: AtomicUsize::new(0), lock: Mutex::new(()), condvar: Condvar::new() }
+    }
+
+    // try_fn is retried after registering as a sleeper, so a notify can't be missed
+    fn wait_for<T, F>(&self, mut try_fn: F) -> T where
+        F: FnMut() -> Option<T>
+    {
+        loop {
+            if let Some(result) = try_fn() {
+                return result;
+            }
+
+            self.sleepers.fetch_add(1, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            if let Some(result) = try_fn() {
+                self.sleepers.fetch_sub(1, Ordering::SeqCst);
+                return result;
+            }
+
+            let mut guard = self.lock.lock().unwrap();
+            while self.epoch.load(Ordering::SeqCst) == epoch {
+                guard = self.condvar.wait(guard).unwrap();
+            }
+            drop(guard);
+            self.sleepers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[inline]
+    fn notify(&self) {
+        // Pairs with the fence of wait_for: either the sleeper sees the new item or we see the sleeper
+        fence(Ordering::SeqCst);
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            let _guard = self.lock.lock().unwrap();
+            self.epoch.fetch_add(1, Ordering::SeqCst);
+            self.condvar.notify_all();
+        }
+    }
+}
+
+struct MpmcSlot<T> {
+    // Equal to the position when the slot is free for this lap, position + 1 once written
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Bounded lock-free multi producer multi consumer queue (Vyukov).
+// A successful push happens-before the pop returning the same item (release/acquire on the slot sequence).
+pub struct MpmcQueue<T> {
+    slots: Box<[MpmcSlot<T>]>,
+    mask: usize,
+    push_position: CachePadded<AtomicUsize>,
+    pop_position: CachePadded<AtomicUsize>,
+    not_empty: Parking,
+    not_full: Parking,
+}
+
+unsafe impl<T: Send> Send for MpmcQueue<T> { }
+unsafe impl<T: Send> Sync for MpmcQueue<T> { }
+
+impl<T> MpmcQueue<T> {
+    // Capacity is rounded up to a power of two
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(2).next_power_of_two();
+        MpmcQueue {
+            slots: (0..capacity)
+                .map(|i| MpmcSlot { sequence: AtomicUsize::new(i), value: UnsafeCell::new(MaybeUninit::uninit()) })
+                .collect(),
+            mask: capacity - 1,
+            push_position: CachePadded(AtomicUsize::new(0)),
+            pop_position: CachePadded(AtomicUsize::new(0)),
+            not_empty: Parking::new(),
+            not_full: Parking::new(),
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+
+    // Approximate when other threads are pushing or popping
+    #[inline]
+    pub fn num(&self) -> usize {
+        let pop_position = self.pop_position.load(Ordering::Relaxed);
+        let push_position = self.push_position.load(Ordering::Relaxed);
+        push_position.wrapping_sub(pop_position).min(self.capacity())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.num() == 0
+    }
+
+    // Gives the value back when the queue is full
+    pub fn try_push(&self, value: T) -> Result<(), T> {
+        let mut position = self.push_position.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let diff = sequence.wrapping_sub(position) as isize;
+
+            if diff == 0 {
+                match self.push_position.compare_exchange_weak(position, position.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        slot.value.with_mut(|ptr| unsafe{ (*ptr).write(value) });
+                        slot.sequence.store(position.wrapping_add(1), Ordering::Release);
+                        self.not_empty.notify();
+                        return Ok(());
+                    },
+                    Err(current) => position = current,
+                }
+            } else if diff < 0 {
+                // The slot still holds the item of the previous lap
+                return Err(value);
+            } else {
+                position = self.push_position.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        let mut position = self.pop_position.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let diff = sequence.wrapping_sub(position.wrapping_add(1)) as isize;
+
+            if diff == 0 {
+                match self.pop_position.compare_exchange_weak(position, position.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        let value = slot.value.with_mut(|ptr| unsafe{ (*ptr).assume_init_read() });
+                        slot.sequence.store(position.wrapping_add(self.mask + 1), Ordering::Release);
+                        self.not_full.notify();
+                        return Some(value);
+                    },
+                    Err(current) => position = current,
+                }
+            } else if diff < 0 {
+                return None;
+            } else {
+                position = self.pop_position.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Pushes from the front of items until the queue is full, returns the pushed count
+    pub fn try_push_batch(&self, items: &mut VecDeque<T>) -> usize {
+        let mut pushed = 0;
+        while let Some(value) = items.pop_front() {
+            if let Err(value) = self.try_push(value) {
+                items.push_front(value);
+                break;
+            }
+            pushed += 1;
+        }
+        pushed
+    }
+
+    // Appends up to max_num items, returns the popped count
+    pub fn try_pop_batch(&self, items: &mut Array<T>, max_num: usize) -> usize where
+        T: Unpin
+    {
+        let mut popped = 0;
+        while popped < max_num {
+            match self.try_pop() {
+                Some(value) => items.push_back(value),
+                None => break,
+            }
+            popped += 1;
+        }
+        popped
+    }
+
+    // Parks the thread while the queue is full
+    pub fn push(&self, value: T) {
+        let mut value = Some(value);
+        self.not_full.wait_for(|| {
+            match self.try_push(value.take().unwrap()) {
+                Ok(()) => Some(()),
+                Err(rejected) => { value = Some(rejected); None },
+            }
+        });
+    }
+
+    // Parks the thread while the queue is empty
+    pub fn pop(&self) -> T {
+        self.not_empty.wait_for(|| self.try_pop())
+    }
+}
+
+impl<T> Drop for MpmcQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() { }
+    }
+}
+
+struct SpscRing<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    // Only written by the consumer
+    head: CachePadded<AtomicUsize>,
+    // Only written by the producer
+    tail: CachePadded<AtomicUsize>,
+    not_empty: Parking,
+    not_full: Parking,
+}
+
+unsafe impl<T: Send> Send for SpscRing<T> { }
+unsafe impl<T: Send> Sync for SpscRing<T> { }
+
+impl<T> Drop for SpscRing<T> {
+    fn drop(&mut self) {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let mut head = self.head.load(Ordering::Relaxed);
+        while head != tail {
+            self.slots[head & self.mask].with_mut(|ptr| unsafe{ (*ptr).assume_init_drop() });
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+// Wait-free single producer single consumer ring, each side is owned by one thread.
+// Items written before the tail release are visible after the consumer acquires it, same for the head.
+pub fn spsc_ring<T>(capacity: usize) -> (SpscProducer<T>, SpscConsumer<T>) {
+    let capacity = capacity.max(2).next_power_of_two();
+    let ring = Arc::new(SpscRing {
+        slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+        mask: capacity - 1,
+        head: CachePadded(AtomicUsize::new(0)),
+        tail: CachePadded(AtomicUsize::new(0)),
+        not_empty: Parking::new(),
+        not_full: Parking::new(),
+    });
+    (SpscProducer { ring: ring.clone() }, SpscConsumer { ring })
+}
+
+pub struct SpscProducer<T> {
+    ring: Arc<SpscRing<T>>,
+}
+
+impl<T> SpscProducer<T> {
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.ring.mask + 1
+    }
+
+    #[inline]
+    fn free_num(&self, tail: usize) -> usize {
+        self.capacity() - tail.wrapping_sub(self.ring.head.load(Ordering::Acquire))
+    }
+
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        if self.free_num(tail) == 0 {
+            return Err(value);
+        }
+        self.ring.slots[tail & self.ring.mask].with_mut(|ptr| unsafe{ (*ptr).write(value) });
+        self.ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+        self.ring.not_empty.notify();
+        Ok(())
+    }
+
+    // Publishes every pushed item with a single release store
+    pub fn try_push_batch(&mut self, items: &mut VecDeque<T>) -> usize {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let pushed = self.free_num(tail).min(items.len());
+        for i in 0..pushed {
+            let value = items.pop_front().unwrap();
+            self.ring.slots[tail.wrapping_add(i) & self.ring.mask].with_mut(|ptr| unsafe{ (*ptr).write(value) });
+        }
+        if pushed > 0 {
+            self.ring.tail.store(tail.wrapping_add(pushed), Ordering::Release);
+            self.ring.not_empty.notify();
+        }
+        pushed
+    }
+
+    pub fn push(&mut self, value: T) {
+        let mut value = Some(value);
+        let ring = self.ring.clone();
+        ring.not_full.wait_for(|| {
+            match self.try_push(value.take().unwrap()) {
+                Ok(()) => Some(()),
+                Err(rejected) => { value = Some(rejected); None },
+            }
+        });
+    }
+}
+
+pub struct SpscConsumer<T> {
+    ring: Arc<SpscRing<T>>,
+}
+
+impl<T> SpscConsumer<T> {
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.ring.mask + 1
+    }
+
+    #[inline]
+    fn available_num(&self, head: usize) -> usize {
+        self.ring.tail.load(Ordering::Acquire).wrapping_sub(head)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.available_num(self.ring.head.load(Ordering::Relaxed)) == 0
+    }
+
+    pub fn try_pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        if self.available_num(head) == 0 {
+            return None;
+        }
+        let value = self.ring.slots[head & self.ring.mask].with_mut(|ptr| unsafe{ (*ptr).assume_init_read() });
+        self.ring.head.store(head.wrapping_add(1), Ordering::Release);
+        self.ring.not_full.notify();
+        Some(value)
+    }
+
+    // Frees every popped slot with a single release store
+    pub fn try_pop_batch(&mut self, items: &mut Array<T>, max_num: usize) -> usize where
+        T: Unpin
+    {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let popped = self.available_num(head).min(max_num);
+        for i in 0..popped {
+            let value = self.ring.slots[head.wrapping_add(i) & self.ring.mask].with_mut(|ptr| unsafe{ (*ptr).assume_init_read() });
+            items.push_back(value);
+        }
+        if popped > 0 {
+            self.ring.head.store(head.wrapping_add(popped), Ordering::Release);
+            self.ring.not_full.notify();
+        }
+        popped
+    }
+
+    pub fn pop(&mut self) -> T {
+        let ring = self.ring.clone();
+        ring.not_empty.wait_for(|| self.try_pop())
+    }
+}
+
+const DEQUE_INITIAL_CAPACITY: usize = 64;
+
+// Items are boxed so the slots can be atomic pointers, a stealer may read a slot the worker is overwriting
+struct DequeBuffer<T> {
+    slots: Box<[AtomicPtr<T>]>,
+    mask: usize,
+}
+
+impl<T> DequeBuffer<T> {
+    fn new(capacity: usize) -> Self {
+        DequeBuffer { slots: (0..capacity).map(|_| AtomicPtr::new(ptr::null_mut())).collect(), mask: capacity - 1 }
+    }
+
+    #[inline]
+    fn slot(&self, index: isize) -> &AtomicPtr<T> {
+        &self.slots[index as usize & self.mask]
+    }
+}
+
+struct WorkStealingDeque<T> {
+    // Stolen from the top
+    top: CachePadded<AtomicIsize>,
+    // Pushed and popped at the bottom, only written by the worker
+    bottom: CachePadded<AtomicIsize>,
+    buffer: AtomicPtr<DequeBuffer<T>>,
+    // Buffers replaced by a bigger one, stealers can still read them so they are freed with the deque. Only used by the worker.
+    retired: UnsafeCell<Array<*mut DequeBuffer<T>>>,
+}
+
+unsafe impl<T: Send> Send for WorkStealingDeque<T> { }
+unsafe impl<T: Send> Sync for WorkStealingDeque<T> { }
+
+impl<T> Drop for WorkStealingDeque<T> {
+    fn drop(&mut self) {
+        let top = self.top.load(Ordering::Relaxed);
+        let bottom = self.bottom.load(Ordering::Relaxed);
+        let buffer = self.buffer.load(Ordering::Relaxed);
+        unsafe {
+            for index in top..bottom {
+                drop(Box::from_raw((*buffer).slot(index).load(Ordering::Relaxed)));
+            }
+            drop(Box::from_raw(buffer));
+        }
+        self.retired.with_mut(|retired| unsafe {
+            for buffer in (*retired).iter() {
+                drop(Box::from_raw(*buffer));
+            }
+        });
+    }
+}
+
+// Unbounded Chase-Lev deque (Le, Pop, Cohen, Zappa Nardelli 2013): the worker pushes and pops at the bottom without
+// locking, stealers take from the top and only race with the worker for the last item.
+// A push happens-before the pop or steal returning the same item (release fence then acquire of the bottom).
+pub fn work_stealing_deque<T>() -> (DequeWorker<T>, DequeStealer<T>) {
+    let deque = Arc::new(WorkStealingDeque {
+        top: CachePadded(AtomicIsize::new(0)),
+        bottom: CachePadded(AtomicIsize::new(0)),
+        buffer: AtomicPtr::new(Box::into_raw(Box::new(DequeBuffer::new(DEQUE_INITIAL_CAPACITY)))),
+        retired: UnsafeCell::new(Array::new()),
+    });
+    (DequeWorker { deque: deque.clone() }, DequeStealer { deque })
+}
+
+pub struct DequeWorker<T> {
+    deque: Arc<WorkStealingDeque<T>>,
+}
+
+impl<T> DequeWorker<T> {
+    // Approximate when stealers are taking items
+    #[inline]
+    pub fn num(&self) -> usize {
+        let bottom = self.deque.bottom.load(Ordering::Relaxed);
+        (bottom - self.deque.top.load(Ordering::Relaxed)).max(0) as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.num() == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        let deque = &*self.deque;
+        let bottom = deque.bottom.load(Ordering::Relaxed);
+        let top = deque.top.load(Ordering::Acquire);
+        let mut buffer = unsafe{ &*deque.buffer.load(Ordering::Relaxed) };
+        if bottom - top > buffer.mask as isize {
+            buffer = self.grow(buffer, top, bottom);
+        }
+        buffer.slot(bottom).store(Box::into_raw(Box::new(value)), Ordering::Relaxed);
+        fence(Ordering::Release);
+        deque.bottom.store(bottom + 1, Ordering::Relaxed);
+    }
+
+    // Copies the items in a buffer twice as big, the old one is kept for the stealers reading it. Only called by push.
+    fn grow(&self, buffer: &DequeBuffer<T>, top: isize, bottom: isize) -> &DequeBuffer<T> {
+        let new_buffer = DequeBuffer::new((buffer.mask + 1) * 2);
+        for index in top..bottom {
+            new_buffer.slot(index).store(buffer.slot(index).load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        let new_buffer = Box::into_raw(Box::new(new_buffer));
+        let old_buffer = self.deque.buffer.swap(new_buffer, Ordering::Release);
+        self.deque.retired.with_mut(|retired| unsafe{ (*retired).push_back(old_buffer) });
+        unsafe{ &*new_buffer }
+    }
+
+    // Last pushed first
+    pub fn pop(&mut self) -> Option<T> {
+        let deque = &*self.deque;
+        let bottom = deque.bottom.load(Ordering::Relaxed) - 1;
+        let buffer = unsafe{ &*deque.buffer.load(Ordering::Relaxed) };
+        deque.bottom.store(bottom, Ordering::Relaxed);
+        // Orders the bottom store before the top load, pairs with the fence of steal
+        fence(Ordering::SeqCst);
+        let top = deque.top.load(Ordering::Relaxed);
+        if top > bottom {
+            deque.bottom.store(bottom + 1, Ordering::Relaxed);
+            return None;
+        }
+
+        let value = buffer.slot(bottom).load(Ordering::Relaxed);
+        if top == bottom {
+            // Last item, the stealers could be taking it
+            let won = deque.top.compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed).is_ok();
+            deque.bottom.store(bottom + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        Some(*unsafe{ Box::from_raw(value) })
+    }
+}
+
+#[derive(Clone)]
+pub struct DequeStealer<T> {
+    deque: Arc<WorkStealingDeque<T>>,
+}
+
+impl<T> DequeStealer<T> {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.deque.bottom.load(Ordering::Relaxed) <= self.deque.top.load(Ordering::Relaxed)
+    }
+
+    // First pushed first, retries while other threads take the top item first
+    pub fn steal(&self) -> Option<T> {
+        let deque = &*self.deque;
+        loop {
+            let top = deque.top.load(Ordering::Acquire);
+            fence(Ordering::SeqCst);
+            let bottom = deque.bottom.load(Ordering::Acquire);
+            if top >= bottom {
+                return None;
+            }
+
+            // The slot is only read, the item is owned once the top is moved past it
+            let buffer = unsafe{ &*deque.buffer.load(Ordering::Acquire) };
+            let value = buffer.slot(top).load(Ordering::Relaxed);
+            if deque.top.compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed).is_ok() {
+                return Some(*unsafe{ Box::from_raw(value) });
+            }
+        }
+    }
+}
@@ -25,7 +25,7 @@ impl<A: AllocatorBase> RawArray<A> {
     #[inline]
     pub unsafe fn for_type_unchecked(layout: Layout) -> Self {
         RawArray {
-            data: NonNull::dangling(),
+            data: NonNull::new_unchecked(layout.align() as *mut u8), // dangling but aligned for the item type
             items_layout: layout,
             items_num: 0,
             items_cap: 0,
@@ -1,6 +1,8 @@
 use std::alloc::Layout;
+use std::marker::PhantomData;
 
 use crate::alloc::{AllocatorBase, ArrayAllocator, DefaultAllocator};
+use crate::{HashMixer, IdentityMixer};
 use crate::RawArray;
 use crate::Array;
 
@@ -12,27 +14,31 @@ pub struct RawSetEntry {
     next: usize,
 }
 
-pub struct RawSet<DataAlloc = DefaultAllocator, EntriesAlloc = DefaultAllocator, TableAlloc = DefaultAllocator> where
+pub struct RawSet<DataAlloc = DefaultAllocator, EntriesAlloc = DefaultAllocator, TableAlloc = DefaultAllocator, Mix = IdentityMixer> where
     DataAlloc: AllocatorBase,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     data: RawArray<DataAlloc>,
     entries: Array<RawSetEntry, EntriesAlloc>,
     table: Array<usize, TableAlloc>,
+    mix: PhantomData<Mix>,
 }
 
-impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableAlloc> where
+impl<DataAlloc, EntriesAlloc, TableAlloc, Mix> RawSet<DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     DataAlloc: AllocatorBase,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     #[inline]
     pub unsafe fn for_type_unchecked(layout: Layout) -> Self {
         Self {
             data: RawArray::<DataAlloc>::for_type_unchecked(layout),
             entries: Array::custom_allocator(),
-            table: Array::custom_allocator()
+            table: Array::custom_allocator(),
+            mix: PhantomData,
         }
     }
 
@@ -41,7 +47,8 @@ impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableA
         let mut raw_set = Self {
             data: RawArray::<DataAlloc>::for_type_unchecked(layout),
             entries: Array::custom_allocator(),
-            table: Array::custom_allocator_with_capacity(table_size)
+            table: Array::custom_allocator_with_capacity(table_size),
+            mix: PhantomData,
         };
         raw_set.table.insert_range(0..table_size, usize::MAX);
         raw_set
@@ -58,17 +65,23 @@ impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableA
     }
 }
 
-impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableAlloc> where
+impl<DataAlloc, EntriesAlloc, TableAlloc, Mix> RawSet<DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     DataAlloc: AllocatorBase,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
+    #[inline]
+    fn table_index(&self, hash: usize) -> usize {
+        Mix::mix(hash) % self.table.num()
+    }
+
     #[inline]
     pub fn find_first_index(&self, hash: usize) -> usize {
         let mut entry_index = usize::MAX;
 
         if !self.table.is_empty() {
-            let table_index = hash % self.table.num();
+            let table_index = self.table_index(hash);
             entry_index = self.table[table_index];
             while entry_index != usize::MAX && self.entries[entry_index].hash != hash {
                 entry_index = self.entries[entry_index].next;
@@ -105,7 +118,7 @@ impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableA
 
     #[inline]
     fn setup_new_entry(&mut self, new_entry: &mut RawSetEntry) {
-        let table_index = new_entry.hash % self.table.num();
+        let table_index = self.table_index(new_entry.hash);
 
         new_entry.next = self.find_first_index(new_entry.hash);
         if new_entry.next == usize::MAX {
@@ -194,7 +207,7 @@ impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableA
 
         // Fix prev & next indices after removing removed_entry
         if removed_entry.prev == usize::MAX {
-            let table_index = removed_entry.hash % self.table.num();
+            let table_index = self.table_index(removed_entry.hash);
             self.table[table_index] = removed_entry.next;
         } else {
             self.entries[removed_entry.prev].next = removed_entry.next;
@@ -216,7 +229,7 @@ impl<DataAlloc, EntriesAlloc, TableAlloc> RawSet<DataAlloc, EntriesAlloc, TableA
         let moved_entry_copy = self.entries[index];
 
         if moved_entry_copy.prev == usize::MAX {
-            let table_index = moved_entry_copy.hash % self.table.num();
+            let table_index = self.table_index(moved_entry_copy.hash);
             self.table[table_index] = index;
         } else {
             self.entries[moved_entry_copy.prev].next = index;
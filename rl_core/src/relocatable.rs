@@ -0,0 +1,788 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+use std::str;
+
+use crate::{ArchiveError, ArchiveResult, Array, FastHash, HashMixer, SplitMix64Mixer};
+
+// Zero-copy data: values keep their in-memory layout and containers point to their items with offsets relative
+// to themselves, so a validated buffer is used in place wherever it is loaded or mapped.
+// Layout (native endianness):
+// header   u8[4] magic, u32 format version, u64 position of the root value
+// data     values aligned to their type alignment, at most 16
+const RELOCATABLE_MAGIC: [u8; 4] = *b"RLZC";
+const RELOCATABLE_FORMAT_VERSION: u32 = 1;
+const RELOCATABLE_HEADER_SIZE: usize = 16;
+const RELOCATABLE_MAX_ALIGN: usize = 16;
+
+/// Type stored as is in relocatable buffers.
+///
+/// # Safety
+/// The type must be a primitive or `#[repr(C)]` without padding, drop glue or references, pointers other than
+/// the archived containers. `validate` must reject every byte pattern that isn't a valid value.
+pub unsafe trait Relocatable : Sized + 'static {
+    // Every byte pattern is a valid value, the items of plain slices aren't visited by the validator
+    const PLAIN: bool = false;
+
+    /// Checks the value at `pos`, its bounds and alignment are already checked.
+    /// Structs check each of their fields with `validator.check::<Field>(pos + offset_of!(Struct, field))`.
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()>;
+}
+
+/// Bounds, alignment and value checks of untrusted relocatable data.
+/// Shared items are validated once per reference, a buffer can be made expensive to validate but not unsafe to use.
+pub struct Validator<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Validator { bytes }
+    }
+
+    pub fn check<T: Relocatable>(&self, pos: usize) -> ArchiveResult<()> {
+        self.check_range::<T>(pos, 1)?;
+        T::validate(self, pos)
+    }
+
+    pub fn check_slice<T: Relocatable>(&self, pos: usize, len: usize) -> ArchiveResult<()> {
+        self.check_range::<T>(pos, len)?;
+        if !T::PLAIN {
+            for index in 0..len {
+                T::validate(self, pos + index * mem::size_of::<T>())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the value at `pos` and returns it
+    pub fn get<T: Relocatable>(&self, pos: usize) -> ArchiveResult<&'a T> {
+        self.check::<T>(pos)?;
+        Ok(unsafe{ self.get_unchecked(pos) })
+    }
+
+    unsafe fn get_unchecked<T>(&self, pos: usize) -> &'a T {
+        &*self.bytes.as_ptr().add(pos).cast::<T>()
+    }
+
+    fn check_range<T>(&self, pos: usize, len: usize) -> ArchiveResult<()> {
+        let end = len.checked_mul(mem::size_of::<T>()).and_then(|size| pos.checked_add(size));
+        if end.is_none_or(|end| end > self.bytes.len()) {
+            return Err(ArchiveError::UnexpectedEnd);
+        }
+        if !(self.bytes.as_ptr() as usize).wrapping_add(pos).is_multiple_of(mem::align_of::<T>()) {
+            return Err(ArchiveError::InvalidData("misaligned value"));
+        }
+        Ok(())
+    }
+}
+
+macro_rules! impl_relocatable_plain {
+    ($($t:ty),*) => {
+        $(
+            unsafe impl Relocatable for $t {
+                const PLAIN: bool = true;
+
+                #[inline]
+                fn validate(_validator: &Validator, _pos: usize) -> ArchiveResult<()> {
+                    Ok(())
+                }
+            }
+        )*
+    }
+}
+
+impl_relocatable_plain!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+unsafe impl Relocatable for bool {
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        match validator.bytes[pos] {
+            0 | 1 => Ok(()),
+            _ => Err(ArchiveError::InvalidData("invalid bool")),
+        }
+    }
+}
+
+unsafe impl Relocatable for char {
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        let value = unsafe{ *validator.get_unchecked::<u32>(pos) };
+        char::from_u32(value).map(|_| ()).ok_or(ArchiveError::InvalidData("invalid char"))
+    }
+}
+
+unsafe impl<T: Relocatable, const N: usize> Relocatable for [T; N] {
+    const PLAIN: bool = T::PLAIN;
+
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        validator.check_slice::<T>(pos, N)
+    }
+}
+
+#[cfg(feature = "nalgebra-glm")]
+unsafe impl<T, const R: usize, const C: usize> Relocatable for nalgebra_glm::TMat<T, R, C> where
+    T: Relocatable + nalgebra_glm::Scalar
+{
+    const PLAIN: bool = T::PLAIN;
+
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        validator.check_slice::<T>(pos, R * C)
+    }
+}
+
+/// Array stored in a relocatable buffer. Only empty placeholders are built directly,
+/// RelocatableBuilder::resolve points them to pushed items.
+#[repr(C)]
+pub struct ArchivedArray<T> {
+    offset: i64, // from the address of the array to its first item
+    len: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Relocatable> ArchivedArray<T> {
+    #[inline]
+    pub const fn empty() -> Self {
+        ArchivedArray { offset: 0, len: 0, _marker: PhantomData }
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.len as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        if self.len == 0 {
+            return &[];
+        }
+        // Non empty arrays only exist in place, in a validated buffer
+        unsafe {
+            let items = (self as *const Self).cast::<u8>().offset(self.offset as isize).cast::<T>();
+            slice::from_raw_parts(items, self.len as usize)
+        }
+    }
+}
+
+unsafe impl<T: Relocatable> Relocatable for ArchivedArray<T> {
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        let array = unsafe{ validator.get_unchecked::<Self>(pos) };
+        if array.len == 0 {
+            return Ok(());
+        }
+        let items_pos = (pos as i64).checked_add(array.offset).and_then(|items_pos| usize::try_from(items_pos).ok());
+        let len = usize::try_from(array.len).ok();
+        match (items_pos, len) {
+            (Some(items_pos), Some(len)) => validator.check_slice::<T>(items_pos, len),
+            _ => Err(ArchiveError::UnexpectedEnd),
+        }
+    }
+}
+
+impl<T: Relocatable> Default for ArchivedArray<T> {
+    fn default() -> Self {
+        ArchivedArray::empty()
+    }
+}
+
+impl<T: Relocatable> Deref for ArchivedArray<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<'a, T: Relocatable> IntoIterator for &'a ArchivedArray<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl<T: Relocatable + fmt::Debug> fmt::Debug for ArchivedArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+/// UTF-8 string stored in a relocatable buffer
+#[repr(C)]
+#[derive(Default)]
+pub struct ArchivedStr {
+    bytes: ArchivedArray<u8>,
+}
+
+impl ArchivedStr {
+    #[inline]
+    pub const fn empty() -> Self {
+        ArchivedStr { bytes: ArchivedArray::empty() }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        unsafe{ str::from_utf8_unchecked(self.bytes.as_slice()) }
+    }
+}
+
+unsafe impl Relocatable for ArchivedStr {
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        let bytes = validator.get::<ArchivedArray<u8>>(pos)?;
+        str::from_utf8(bytes.as_slice()).map(|_| ()).map_err(|_| ArchiveError::InvalidData("invalid UTF-8 string"))
+    }
+}
+
+impl Deref for ArchivedStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for ArchivedStr {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for ArchivedStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ArchivedStr { }
+
+impl PartialEq<str> for ArchivedStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl FastHash for ArchivedStr {
+    #[inline]
+    fn fast_hash(&self) -> usize {
+        self.as_str().fast_hash()
+    }
+}
+
+impl fmt::Display for ArchivedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for ArchivedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Read-only hash map stored in a relocatable buffer, keys are hashed with their FastHash and expected to be unique
+#[repr(C)]
+pub struct ArchivedMap<K, V> {
+    keys: ArchivedArray<K>,
+    values: ArchivedArray<V>,
+    buckets: ArchivedArray<u32>, // first item of each bucket in `indices`, plus the end of the last one
+    indices: ArchivedArray<u32>, // of the keys, grouped by bucket
+}
+
+#[inline]
+fn get_bucket(hash: usize, buckets_num: usize) -> usize {
+    SplitMix64Mixer::mix(hash) & (buckets_num - 1)
+}
+
+impl<K: Relocatable + FastHash, V: Relocatable> ArchivedMap<K, V> {
+    #[inline]
+    pub const fn empty() -> Self {
+        ArchivedMap { keys: ArchivedArray::empty(), values: ArchivedArray::empty(), buckets: ArchivedArray::empty(), indices: ArchivedArray::empty() }
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.keys.num()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[inline]
+    pub fn get_keys(&self) -> &[K] {
+        self.keys.as_slice()
+    }
+
+    #[inline]
+    pub fn get_values(&self) -> &[V] {
+        self.values.as_slice()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.keys.iter().zip(self.values.iter())
+    }
+
+    /// Index of the key in get_keys/get_values, usize::MAX if not found
+    pub fn find<Q>(&self, key: &Q) -> usize where
+        K: Borrow<Q>,
+        Q: FastHash + Eq + ?Sized
+    {
+        if self.buckets.num() < 2 {
+            return usize::MAX;
+        }
+        let bucket = get_bucket(key.fast_hash(), self.buckets.num() - 1);
+        let (start, end) = (self.buckets[bucket] as usize, self.buckets[bucket + 1] as usize);
+        for &index in &self.indices[start..end] {
+            if self.keys[index as usize].borrow() == key {
+                return index as usize;
+            }
+        }
+        usize::MAX
+    }
+
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where
+        K: Borrow<Q>,
+        Q: FastHash + Eq + ?Sized
+    {
+        self.find(key) != usize::MAX
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V> where
+        K: Borrow<Q>,
+        Q: FastHash + Eq + ?Sized
+    {
+        match self.find(key) {
+            usize::MAX => None,
+            index => Some(&self.values[index]),
+        }
+    }
+}
+
+unsafe impl<K: Relocatable, V: Relocatable> Relocatable for ArchivedMap<K, V> {
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        let array_size = mem::size_of::<ArchivedArray<u8>>();
+        let keys = validator.get::<ArchivedArray<K>>(pos)?;
+        let values = validator.get::<ArchivedArray<V>>(pos + array_size)?;
+        let buckets = validator.get::<ArchivedArray<u32>>(pos + 2 * array_size)?;
+        let indices = validator.get::<ArchivedArray<u32>>(pos + 3 * array_size)?;
+
+        // Empty placeholder
+        if buckets.is_empty() {
+            return match keys.is_empty() && values.is_empty() && indices.is_empty() {
+                true => Ok(()),
+                false => Err(ArchiveError::InvalidData("map without buckets")),
+            };
+        }
+        let buckets_num = buckets.num() - 1;
+        let valid = keys.num() == values.num()
+            && indices.num() == keys.num()
+            && buckets_num.is_power_of_two()
+            && buckets[0] == 0
+            && buckets.windows(2).all(|range| range[0] <= range[1])
+            && buckets[buckets_num] as usize == indices.num()
+            && indices.iter().all(|&index| (index as usize) < keys.num());
+        match valid {
+            true => Ok(()),
+            false => Err(ArchiveError::InvalidData("invalid map table")),
+        }
+    }
+}
+
+impl<K: Relocatable + FastHash, V: Relocatable> Default for ArchivedMap<K, V> {
+    fn default() -> Self {
+        ArchivedMap::empty()
+    }
+}
+
+impl<K: Relocatable + FastHash + fmt::Debug, V: Relocatable + fmt::Debug> fmt::Debug for ArchivedMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct AlignedBlock([u8; RELOCATABLE_MAX_ALIGN]);
+
+/// Bytes aligned for any relocatable type, in-memory storage of relocatable data
+pub struct AlignedBuffer {
+    blocks: Array<AlignedBlock>,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    pub fn new() -> Self {
+        AlignedBuffer { blocks: Array::new(), len: 0 }
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut buffer = AlignedBuffer::new();
+        buffer.resize(bytes.len());
+        buffer.copy_from_slice(bytes);
+        buffer
+    }
+
+    pub fn read_file(path: impl AsRef<Path>) -> ArchiveResult<Self> {
+        let mut file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len()).map_err(|_| ArchiveError::InvalidData("file too big"))?;
+        let mut buffer = AlignedBuffer::new();
+        buffer.resize(len);
+        file.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> ArchiveResult<()> {
+        fs::write(path, &**self)?;
+        Ok(())
+    }
+
+    // Grows with zeros
+    fn resize(&mut self, len: usize) {
+        let blocks_num = len.div_ceil(RELOCATABLE_MAX_ALIGN);
+        if blocks_num > self.blocks.num() {
+            self.blocks.insert_range(self.blocks.num()..blocks_num, AlignedBlock([0; RELOCATABLE_MAX_ALIGN]));
+        }
+        self.len = len;
+    }
+}
+
+impl Default for AlignedBuffer {
+    fn default() -> Self {
+        AlignedBuffer::new()
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe{ slice::from_raw_parts(self.blocks.as_ptr().cast::<u8>(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe{ slice::from_raw_parts_mut(self.blocks.as_mut_ptr().cast::<u8>(), self.len) }
+    }
+}
+
+/// Position of a value pushed to a RelocatableBuilder
+pub struct Slot<T> {
+    pos: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Slot<T> {
+    #[inline]
+    pub fn get_pos(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<T> Clone for Slot<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Slot<T> { }
+
+/// Items pushed to a RelocatableBuilder, resolves an ArchivedArray
+pub struct ArraySlot<T> {
+    pos: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> ArraySlot<T> {
+    #[inline]
+    pub fn get_pos(&self) -> usize {
+        self.pos
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn get_slot(&self, index: usize) -> Slot<T> {
+        assert!(index < self.len, "Index {} out of {} items", index, self.len);
+        Slot { pos: self.pos + index * mem::size_of::<T>(), _marker: PhantomData }
+    }
+}
+
+impl<T> Clone for ArraySlot<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArraySlot<T> { }
+
+/// String pushed to a RelocatableBuilder, resolves an ArchivedStr
+#[derive(Clone, Copy)]
+pub struct StrSlot(ArraySlot<u8>);
+
+impl StrSlot {
+    #[inline]
+    pub fn get_pos(&self) -> usize {
+        self.0.pos
+    }
+}
+
+/// Map pushed to a RelocatableBuilder, resolves an ArchivedMap
+pub struct MapSlot<K, V> {
+    keys: ArraySlot<K>,
+    values: ArraySlot<V>,
+    buckets: ArraySlot<u32>,
+    indices: ArraySlot<u32>,
+}
+
+impl<K, V> Clone for MapSlot<K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for MapSlot<K, V> { }
+
+/// Pushed data that a field of a parent value can point to
+pub trait Resolver {
+    type Target: Relocatable;
+
+    // Writes the Target pointing to the data, as stored at `pos` in `bytes`
+    fn write_resolved(&self, bytes: &mut [u8], pos: usize);
+}
+
+fn write_array(bytes: &mut [u8], pos: usize, items_pos: usize, len: usize) {
+    let offset = items_pos as i64 - pos as i64;
+    bytes[pos..pos + 8].copy_from_slice(&offset.to_ne_bytes());
+    bytes[pos + 8..pos + 16].copy_from_slice(&(len as u64).to_ne_bytes());
+}
+
+impl<T: Relocatable> Resolver for ArraySlot<T> {
+    type Target = ArchivedArray<T>;
+
+    fn write_resolved(&self, bytes: &mut [u8], pos: usize) {
+        write_array(bytes, pos, self.pos, self.len);
+    }
+}
+
+impl Resolver for StrSlot {
+    type Target = ArchivedStr;
+
+    fn write_resolved(&self, bytes: &mut [u8], pos: usize) {
+        self.0.write_resolved(bytes, pos);
+    }
+}
+
+impl<K: Relocatable, V: Relocatable> Resolver for MapSlot<K, V> {
+    type Target = ArchivedMap<K, V>;
+
+    fn write_resolved(&self, bytes: &mut [u8], pos: usize) {
+        let array_size = mem::size_of::<ArchivedArray<u8>>();
+        write_array(bytes, pos, self.keys.pos, self.keys.len);
+        write_array(bytes, pos + array_size, self.values.pos, self.values.len);
+        write_array(bytes, pos + 2 * array_size, self.buckets.pos, self.buckets.len);
+        write_array(bytes, pos + 3 * array_size, self.indices.pos, self.indices.len);
+    }
+}
+
+/// Lays out relocatable data: children are pushed first, parents are pushed with empty containers
+/// then each container field is resolved to its pushed data.
+pub struct RelocatableBuilder {
+    data: AlignedBuffer,
+}
+
+impl RelocatableBuilder {
+    pub fn new() -> Self {
+        let mut data = AlignedBuffer::new();
+        data.resize(RELOCATABLE_HEADER_SIZE);
+        RelocatableBuilder { data }
+    }
+
+    #[inline]
+    pub fn num_bytes(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn push<T: Relocatable>(&mut self, value: T) -> Slot<T> {
+        let pos = self.allocate::<T>();
+        unsafe{ ptr::write(self.data.as_mut_ptr().add(pos).cast::<T>(), value) };
+        Slot { pos, _marker: PhantomData }
+    }
+
+    pub fn push_array<T, I>(&mut self, values: I) -> ArraySlot<T> where
+        T: Relocatable,
+        I: IntoIterator<Item = T>
+    {
+        // Sizes are multiples of the alignment, the items are contiguous
+        let pos = self.data.len().next_multiple_of(mem::align_of::<T>());
+        let len = values.into_iter().map(|value| self.push(value)).count();
+        ArraySlot { pos, len, _marker: PhantomData }
+    }
+
+    pub fn push_slice<T: Relocatable + Copy>(&mut self, values: &[T]) -> ArraySlot<T> {
+        self.push_array(values.iter().copied())
+    }
+
+    pub fn push_str(&mut self, value: &str) -> StrSlot {
+        StrSlot(self.push_slice(value.as_bytes()))
+    }
+
+    /// Builds the table of a map from pushed keys and values, string keys must be resolved before
+    pub fn push_map<K, V>(&mut self, keys: &ArraySlot<K>, values: &ArraySlot<V>) -> MapSlot<K, V> where
+        K: Relocatable + FastHash,
+        V: Relocatable
+    {
+        assert!(keys.len == values.len, "{} keys for {} values", keys.len, values.len);
+        assert!(keys.len <= u32::MAX as usize, "Too many map items");
+
+        let buckets_num = keys.len.next_power_of_two();
+        let validator = Validator::new(&self.data);
+        validator.check_slice::<K>(keys.pos, keys.len).expect("Invalid map keys");
+        let keys_buckets: Array<usize> = {
+            let keys = unsafe{ slice::from_raw_parts(validator.get_unchecked::<K>(keys.pos) as *const K, keys.len) };
+            keys.iter().map(|key| get_bucket(key.fast_hash(), buckets_num)).collect()
+        };
+
+        // Counting sort of the keys by bucket
+        let mut buckets = Array::new();
+        buckets.insert_range(0..buckets_num + 1, 0u32);
+        for &bucket in keys_buckets.iter() {
+            buckets[bucket + 1] += 1;
+        }
+        for bucket in 0..buckets_num {
+            buckets[bucket + 1] += buckets[bucket];
+        }
+        let mut next = buckets.clone();
+        let mut indices = Array::new();
+        indices.insert_range(0..keys.len, 0u32);
+        for (index, &bucket) in keys_buckets.iter().enumerate() {
+            indices[next[bucket] as usize] = index as u32;
+            next[bucket] += 1;
+        }
+
+        MapSlot { keys: *keys, values: *values, buckets: self.push_slice(&buckets), indices: self.push_slice(&indices) }
+    }
+
+    /// Points the container at `field_offset` in the parent, usually `offset_of!(Parent, field)`, to the resolver data
+    pub fn resolve<T, R>(&mut self, parent: Slot<T>, field_offset: usize, resolver: &R) where
+        T: Relocatable,
+        R: Resolver
+    {
+        assert!(field_offset + mem::size_of::<R::Target>() <= mem::size_of::<T>(), "Field out of its parent");
+        let pos = parent.pos + field_offset;
+        assert!(pos.is_multiple_of(mem::align_of::<R::Target>()), "Misaligned field");
+        resolver.write_resolved(&mut self.data, pos);
+    }
+
+    pub fn finish<T: Relocatable>(mut self, root: Slot<T>) -> AlignedBuffer {
+        self.data[0..4].copy_from_slice(&RELOCATABLE_MAGIC);
+        self.data[4..8].copy_from_slice(&RELOCATABLE_FORMAT_VERSION.to_ne_bytes());
+        self.data[8..16].copy_from_slice(&(root.pos as u64).to_ne_bytes());
+        self.data
+    }
+
+    fn allocate<T>(&mut self) -> usize {
+        assert!(mem::align_of::<T>() <= RELOCATABLE_MAX_ALIGN, "Relocatable types are aligned to at most {} bytes", RELOCATABLE_MAX_ALIGN);
+        let pos = self.data.len().next_multiple_of(mem::align_of::<T>());
+        self.data.resize(pos + mem::size_of::<T>());
+        pos
+    }
+}
+
+impl Default for RelocatableBuilder {
+    fn default() -> Self {
+        RelocatableBuilder::new()
+    }
+}
+
+fn read_root_pos(bytes: &[u8]) -> ArchiveResult<usize> {
+    if bytes.len() < RELOCATABLE_HEADER_SIZE {
+        return Err(ArchiveError::UnexpectedEnd);
+    }
+    if bytes[0..4] != RELOCATABLE_MAGIC {
+        return Err(ArchiveError::InvalidData("not relocatable data"));
+    }
+    if u32::from_ne_bytes(bytes[4..8].try_into().unwrap()) != RELOCATABLE_FORMAT_VERSION {
+        return Err(ArchiveError::InvalidData("unsupported relocatable format version"));
+    }
+    usize::try_from(u64::from_ne_bytes(bytes[8..16].try_into().unwrap())).map_err(|_| ArchiveError::UnexpectedEnd)
+}
+
+/// Validates the whole buffer and returns its root
+pub fn access_root<T: Relocatable>(bytes: &[u8]) -> ArchiveResult<&T> {
+    let root = read_root_pos(bytes)?;
+    Validator::new(bytes).get::<T>(root)
+}
+
+/// Root of trusted data, skips the validation
+///
+/// # Safety
+/// The bytes must have been built with a T root and be aligned like when they were built
+pub unsafe fn access_root_unchecked<T: Relocatable>(bytes: &[u8]) -> &T {
+    let root = u64::from_ne_bytes(bytes[8..16].try_into().unwrap()) as usize;
+    &*bytes.as_ptr().add(root).cast::<T>()
+}
+
+/// Memory mapped relocatable file, validated once when opened
+pub struct RelocatableFile<T: Relocatable> {
+    map: memmap2::Mmap,
+    root: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Relocatable> RelocatableFile<T> {
+    /// The file must not be modified while it is mapped
+    pub fn open(path: impl AsRef<Path>) -> ArchiveResult<Self> {
+        let file = File::open(path)?;
+        let map = unsafe{ memmap2::Mmap::map(&file)? };
+        let root = read_root_pos(&map)?;
+        Validator::new(&map).check::<T>(root)?;
+        Ok(RelocatableFile { map, root, _marker: PhantomData })
+    }
+
+    #[inline]
+    pub fn get(&self) -> &T {
+        unsafe{ &*self.map.as_ptr().add(self.root).cast::<T>() }
+    }
+
+    #[inline]
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl<T: Relocatable> Deref for RelocatableFile<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::alloc::{AllocatorBase, ArrayAllocator};
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{Array, Atom, HashMixer, KeyValuePair, Map, RawSetEntry, Set, SetItem, SetKey};
+
+// Arrays and sets are sequences, maps are maps and atoms are their string, numeric suffix included
+
+impl<T, A> Serialize for Array<T, A> where
+    T: Serialize + Unpin,
+    A: AllocatorBase
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+struct ArrayVisitor<T, A>(PhantomData<(T, A)>);
+
+impl<'de, T, A> Visitor<'de> for ArrayVisitor<T, A> where
+    T: Deserialize<'de> + Unpin,
+    A: AllocatorBase
+{
+    type Value = Array<T, A>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        // The hint comes from the data, don't trust it for big allocations
+        let mut array = Array::custom_allocator_with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? {
+            array.push_back(item);
+        }
+        Ok(array)
+    }
+}
+
+impl<'de, T, A> Deserialize<'de> for Array<T, A> where
+    T: Deserialize<'de> + Unpin,
+    A: AllocatorBase
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ArrayVisitor(PhantomData))
+    }
+}
+
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Serialize for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    T: SetItem + Serialize,
+    DataAlloc: ArrayAllocator<T>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.num()))?;
+        for item in self.iter() {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+}
+
+struct SetVisitor<T, DataAlloc, EntriesAlloc, TableAlloc, Mix>(PhantomData<(T, DataAlloc, EntriesAlloc, TableAlloc, Mix)>);
+
+impl<'de, T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Visitor<'de> for SetVisitor<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    T: SetItem + Deserialize<'de>,
+    DataAlloc: ArrayAllocator<T>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    type Value = Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        let mut set = Set::custom_allocators();
+        while let Some(item) = seq.next_element()? {
+            set.insert(item);
+        }
+        Ok(set)
+    }
+}
+
+impl<'de, T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Deserialize<'de> for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    T: SetItem + Deserialize<'de>,
+    DataAlloc: ArrayAllocator<T>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SetVisitor(PhantomData))
+    }
+}
+
+impl<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Serialize for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    K: SetKey + Serialize,
+    V: Unpin + Serialize,
+    DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.num()))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+struct MapVisitor<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix>(PhantomData<(K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix)>);
+
+impl<'de, K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Visitor<'de> for MapVisitor<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    K: SetKey + Deserialize<'de>,
+    V: Unpin + Deserialize<'de>,
+    DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    type Value = Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Self::Value, M::Error> {
+        let mut map = Map::custom_allocators();
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<'de, K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> Deserialize<'de> for Map<K, V, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    K: SetKey + Deserialize<'de>,
+    V: Unpin + Deserialize<'de>,
+    DataAlloc: ArrayAllocator<KeyValuePair<K, V>>,
+    EntriesAlloc: ArrayAllocator<RawSetEntry>,
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+// A (key, value) tuple
+impl<K: SetKey + Serialize, V: Unpin + Serialize> Serialize for KeyValuePair<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.get_key(), self.get_value()).serialize(serializer)
+    }
+}
+
+impl<'de, K: SetKey + Deserialize<'de>, V: Unpin + Deserialize<'de>> Deserialize<'de> for KeyValuePair<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (key, value) = <(K, V)>::deserialize(deserializer)?;
+        Ok(KeyValuePair::new(key, value))
+    }
+}
+
+impl<const CASE_SENSITIVE: bool> Serialize for Atom<CASE_SENSITIVE> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct AtomVisitor<const CASE_SENSITIVE: bool>;
+
+impl<const CASE_SENSITIVE: bool> Visitor<'_> for AtomVisitor<CASE_SENSITIVE> {
+    type Value = Atom<CASE_SENSITIVE>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        if value.is_empty() {
+            Ok(Atom::none())
+        } else if value.len() > STRINGS_TABLE_ENTRY_MAX_LEN {
+            Err(E::invalid_length(value.len(), &"a string of at most 128 bytes"))
+        } else {
+            Ok(Atom::from(value))
+        }
+    }
+}
+
+impl<'de, const CASE_SENSITIVE: bool> Deserialize<'de> for Atom<CASE_SENSITIVE> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(AtomVisitor)
+    }
+}
@@ -7,20 +7,38 @@ use std::option::Option;
 use std::borrow::Borrow;
 
 use crate::alloc::{AllocatorBase, DefaultAllocator, ArrayAllocator};
-use crate::{SetItem, FastHash};
+use crate::{SetItem, FastHash, HashMixer, IdentityMixer};
 use crate::RawSet;
 use crate::RawSetEntry;
 use crate::Array;
 
-pub struct Set<T, DataAlloc = DefaultAllocator, EntriesAlloc = DefaultAllocator, TableAlloc = DefaultAllocator>
+pub struct Set<T, DataAlloc = DefaultAllocator, EntriesAlloc = DefaultAllocator, TableAlloc = DefaultAllocator, Mix = IdentityMixer>
 (
-    RawSet<DataAlloc, EntriesAlloc, TableAlloc>,
+    RawSet<DataAlloc, EntriesAlloc, TableAlloc, Mix>,
     PhantomData<T>,
 ) where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>;
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer;
+
+// Owns its items like a Vec does
+unsafe impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Send for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    T: SetItem + Send,
+    DataAlloc: ArrayAllocator<T> + Send,
+    EntriesAlloc: ArrayAllocator<RawSetEntry> + Send,
+    TableAlloc: ArrayAllocator<usize> + Send,
+    Mix: HashMixer
+{ }
+
+unsafe impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Sync for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
+    T: SetItem + Sync,
+    DataAlloc: ArrayAllocator<T> + Sync,
+    EntriesAlloc: ArrayAllocator<RawSetEntry> + Sync,
+    TableAlloc: ArrayAllocator<usize> + Sync,
+    Mix: HashMixer
+{ }
 
 impl<T: SetItem> Set<T> {
     #[inline]
@@ -34,11 +52,12 @@ impl<T: SetItem> Set<T> {
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc> Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     #[inline]
     pub fn custom_allocators() -> Self {
@@ -51,11 +70,12 @@ impl<T, DataAlloc, EntriesAlloc, TableAlloc> Set<T, DataAlloc, EntriesAlloc, Tab
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc> Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     #[inline]
     pub fn capacity(&self) -> usize {
@@ -211,11 +231,12 @@ impl<T, DataAlloc, EntriesAlloc, TableAlloc> Set<T, DataAlloc, EntriesAlloc, Tab
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc> Deref for Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Deref for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     type Target = [T];
 
@@ -224,23 +245,25 @@ impl<T, DataAlloc, EntriesAlloc, TableAlloc> Deref for Set<T, DataAlloc, Entries
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc> DerefMut for Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> DerefMut for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     fn deref_mut(&mut self) -> &mut [T] {
         unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.num()) }
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc, I> Index<I> for Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix, I> Index<I> for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
     TableAlloc: ArrayAllocator<usize>,
-    I: SliceIndex<[T]>
+    I: SliceIndex<[T]>,
+    Mix: HashMixer
 {
     type Output = I::Output;
 
@@ -250,12 +273,13 @@ impl<T, DataAlloc, EntriesAlloc, TableAlloc, I> Index<I> for Set<T, DataAlloc, E
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc, I> IndexMut<I> for Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix, I> IndexMut<I> for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
     TableAlloc: ArrayAllocator<usize>,
-    I: SliceIndex<[T]>
+    I: SliceIndex<[T]>,
+    Mix: HashMixer
 {
     #[inline]
     fn index_mut(&mut self, index: I) -> &mut Self::Output {
@@ -263,11 +287,12 @@ impl<T, DataAlloc, EntriesAlloc, TableAlloc, I> IndexMut<I> for Set<T, DataAlloc
     }
 }
 
-impl<'a, T, DataAlloc, EntriesAlloc, TableAlloc> IntoIterator for &'a Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<'a, T, DataAlloc, EntriesAlloc, TableAlloc, Mix> IntoIterator for &'a Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     type Item = &'a T;
     type IntoIter = slice::Iter<'a, T>;
@@ -277,11 +302,12 @@ impl<'a, T, DataAlloc, EntriesAlloc, TableAlloc> IntoIterator for &'a Set<T, Dat
     }
 }
 
-impl<'a, T, DataAlloc, EntriesAlloc, TableAlloc> IntoIterator for &'a mut Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<'a, T, DataAlloc, EntriesAlloc, TableAlloc, Mix> IntoIterator for &'a mut Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     type Item = &'a mut T;
     type IntoIter = slice::IterMut<'a, T>;
@@ -291,11 +317,12 @@ impl<'a, T, DataAlloc, EntriesAlloc, TableAlloc> IntoIterator for &'a mut Set<T,
     }
 }
 
-impl<T, DataAlloc, EntriesAlloc, TableAlloc> Drop for Set<T, DataAlloc, EntriesAlloc, TableAlloc> where
+impl<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> Drop for Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix> where
     T: SetItem,
     DataAlloc: ArrayAllocator<T>,
     EntriesAlloc: ArrayAllocator<RawSetEntry>,
-    TableAlloc: ArrayAllocator<usize>
+    TableAlloc: ArrayAllocator<usize>,
+    Mix: HashMixer
 {
     fn drop(&mut self) {
         unsafe {
@@ -304,9 +331,9 @@ impl<T, DataAlloc, EntriesAlloc, TableAlloc> Drop for Set<T, DataAlloc, EntriesA
     }
 }
 
-impl<T: SetItem> Default for Set<T>
+impl<T: SetItem, Mix: HashMixer> Default for Set<T, DefaultAllocator, DefaultAllocator, DefaultAllocator, Mix>
 {
-    fn default() -> Set<T> {
-        Set::new()
+    fn default() -> Self {
+        Set::custom_allocators()
     }
 }
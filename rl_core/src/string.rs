@@ -0,0 +1,418 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::str;
+
+use crate::alloc::{AllocatorBase, DefaultAllocator};
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{Array, Atom, FastHash, SetKey, StringAtom};
+
+/// Growable UTF-8 string stored in an `Array<u8, A>`
+pub struct RString<A = DefaultAllocator>(Array<u8, A>) where
+    A: AllocatorBase;
+
+impl RString {
+    #[inline]
+    pub fn new() -> Self {
+        RString(Array::new())
+    }
+
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        RString(Array::with_capacity(capacity))
+    }
+}
+
+impl<A: AllocatorBase> RString<A> {
+    #[inline]
+    pub fn custom_allocator() -> Self {
+        RString(Array::custom_allocator())
+    }
+
+    #[inline]
+    pub fn custom_allocator_with_capacity(capacity: usize) -> Self {
+        RString(Array::custom_allocator_with_capacity(capacity))
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional);
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        unsafe{ str::from_utf8_unchecked(&self.0) }
+    }
+
+    #[inline]
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe{ str::from_utf8_unchecked_mut(&mut self.0) }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    #[inline]
+    pub fn push_str(&mut self, string: &str) {
+        self.0.extend_from_slice(string.as_bytes());
+    }
+
+    /// Appends the bytes if they are valid UTF-8
+    pub fn push_utf8(&mut self, bytes: &[u8]) -> Result<(), str::Utf8Error> {
+        self.push_str(str::from_utf8(bytes)?);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn insert(&mut self, index: usize, c: char) {
+        self.insert_str(index, c.encode_utf8(&mut [0; 4]));
+    }
+
+    // Panics if index isn't on a char boundary
+    pub fn insert_str(&mut self, index: usize, string: &str) {
+        assert!(self.is_char_boundary(index), "Inserting at {} which isn't a char boundary", index);
+        self.0.extend_from_slice(string.as_bytes());
+        self.0[index..].rotate_right(string.len());
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.chars().next_back()?;
+        self.0.truncate(self.len() - c.len_utf8());
+        Some(c)
+    }
+
+    // Panics if len isn't on a char boundary
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            assert!(self.is_char_boundary(len), "Truncating at {} which isn't a char boundary", len);
+            self.0.truncate(len);
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl<A: AllocatorBase> Clone for RString<A> {
+    fn clone(&self) -> Self {
+        RString(self.0.clone())
+    }
+}
+
+impl<A: AllocatorBase> From<&str> for RString<A> {
+    fn from(string: &str) -> Self {
+        let mut result = RString::custom_allocator_with_capacity(string.len());
+        result.push_str(string);
+        result
+    }
+}
+
+impl<A: AllocatorBase> Deref for RString<A> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<A: AllocatorBase> DerefMut for RString<A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+// Inline storage lives in the string itself, so unlike InlineAllocator it doesn't point into the value and can be moved
+enum InlineStringData<const N: usize> {
+    Inline { len: usize, bytes: [u8; N] },
+    Heap(RString),
+}
+
+/// UTF-8 string stored in place up to N bytes, on the heap past them
+pub struct InlineString<const N: usize>(InlineStringData<N>);
+
+impl<const N: usize> InlineString<N> {
+    #[inline]
+    pub const fn new() -> Self {
+        InlineString(InlineStringData::Inline { len: 0, bytes: [0; N] })
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        match capacity <= N {
+            true => InlineString::new(),
+            false => InlineString(InlineStringData::Heap(RString::with_capacity(capacity))),
+        }
+    }
+
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, InlineStringData::Inline { .. })
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        match &self.0 {
+            InlineStringData::Inline { .. } => N,
+            InlineStringData::Heap(heap) => heap.capacity(),
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        match &mut self.0 {
+            InlineStringData::Inline { len, .. } if *len + additional <= N => (),
+            InlineStringData::Inline { len, bytes } => {
+                let mut heap = RString::with_capacity(*len + additional);
+                heap.push_str(unsafe{ str::from_utf8_unchecked(&bytes[..*len]) });
+                self.0 = InlineStringData::Heap(heap);
+            }
+            InlineStringData::Heap(heap) => heap.reserve(additional),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        unsafe{ str::from_utf8_unchecked(self.as_bytes()) }
+    }
+
+    #[inline]
+    pub fn as_mut_str(&mut self) -> &mut str {
+        match &mut self.0 {
+            InlineStringData::Inline { len, bytes } => unsafe{ str::from_utf8_unchecked_mut(&mut bytes[..*len]) },
+            InlineStringData::Heap(heap) => heap.as_mut_str(),
+        }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.0 {
+            InlineStringData::Inline { len, bytes } => &bytes[..*len],
+            InlineStringData::Heap(heap) => heap.as_bytes(),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    #[inline]
+    pub fn push_str(&mut self, string: &str) {
+        self.insert_str(self.len(), string);
+    }
+
+    /// Appends the bytes if they are valid UTF-8
+    pub fn push_utf8(&mut self, bytes: &[u8]) -> Result<(), str::Utf8Error> {
+        self.push_str(str::from_utf8(bytes)?);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn insert(&mut self, index: usize, c: char) {
+        self.insert_str(index, c.encode_utf8(&mut [0; 4]));
+    }
+
+    // Panics if index isn't on a char boundary
+    pub fn insert_str(&mut self, index: usize, string: &str) {
+        assert!(self.is_char_boundary(index), "Inserting at {} which isn't a char boundary", index);
+        self.reserve(string.len());
+        match &mut self.0 {
+            InlineStringData::Inline { len, bytes } => {
+                bytes.copy_within(index..*len, index + string.len());
+                bytes[index..index + string.len()].copy_from_slice(string.as_bytes());
+                *len += string.len();
+            }
+            InlineStringData::Heap(heap) => heap.insert_str(index, string),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.chars().next_back()?;
+        self.truncate(self.len() - c.len_utf8());
+        Some(c)
+    }
+
+    // Panics if new_len isn't on a char boundary, a spilled string stays on the heap
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len() {
+            assert!(self.is_char_boundary(new_len), "Truncating at {} which isn't a char boundary", new_len);
+            match &mut self.0 {
+                InlineStringData::Inline { len, .. } => *len = new_len,
+                InlineStringData::Heap(heap) => heap.truncate(new_len),
+            }
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+}
+
+impl<const N: usize> Clone for InlineString<N> {
+    fn clone(&self) -> Self {
+        InlineString::from(self.as_str())
+    }
+}
+
+impl<const N: usize> From<&str> for InlineString<N> {
+    fn from(string: &str) -> Self {
+        let mut result = InlineString::with_capacity(string.len());
+        result.push_str(string);
+        result
+    }
+}
+
+impl<const N: usize> Deref for InlineString<N> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> DerefMut for InlineString<N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+// Compared and hashed like the str they hold, so they can be looked up by &str in sets and maps
+macro_rules! impl_string_traits {
+    ($t:ty, $($params:tt)*) => {
+        impl<$($params)*> $t {
+            // Panics if the string is longer than an atom, see try_to_atom
+            #[inline]
+            pub fn to_atom(&self) -> StringAtom {
+                StringAtom::from(self.as_str())
+            }
+
+            // None if the string is longer than an atom
+            #[inline]
+            pub fn try_to_atom(&self) -> Option<StringAtom> {
+                (self.len() <= STRINGS_TABLE_ENTRY_MAX_LEN).then(|| self.to_atom())
+            }
+        }
+
+        impl<$($params)*> Default for $t {
+            fn default() -> Self {
+                Self::from("")
+            }
+        }
+
+        impl<$($params)*> PartialEq for $t {
+            fn eq(&self, other: &Self) -> bool {
+                self.as_str() == other.as_str()
+            }
+        }
+
+        impl<$($params)*> Eq for $t { }
+
+        impl<$($params)*> PartialEq<str> for $t {
+            fn eq(&self, other: &str) -> bool {
+                self.as_str() == other
+            }
+        }
+
+        impl<$($params)*> PartialEq<&str> for $t {
+            fn eq(&self, other: &&str) -> bool {
+                self.as_str() == *other
+            }
+        }
+
+        impl<$($params)*> PartialOrd for $t {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl<$($params)*> Ord for $t {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.as_str().cmp(other.as_str())
+            }
+        }
+
+        impl<$($params)*> Hash for $t {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.as_str().hash(state);
+            }
+        }
+
+        impl<$($params)*> FastHash for $t {
+            #[inline]
+            fn fast_hash(&self) -> usize {
+                self.as_str().fast_hash()
+            }
+        }
+
+        impl<$($params)*> Borrow<str> for $t {
+            #[inline]
+            fn borrow(&self) -> &str {
+                self.as_str()
+            }
+        }
+
+        impl<$($params)*> AsRef<str> for $t {
+            #[inline]
+            fn as_ref(&self) -> &str {
+                self.as_str()
+            }
+        }
+
+        impl<$($params)*> fmt::Write for $t {
+            #[inline]
+            fn write_str(&mut self, string: &str) -> fmt::Result {
+                self.push_str(string);
+                Ok(())
+            }
+
+            #[inline]
+            fn write_char(&mut self, c: char) -> fmt::Result {
+                self.push(c);
+                Ok(())
+            }
+        }
+
+        impl<$($params)*> fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(self.as_str(), f)
+            }
+        }
+
+        impl<$($params)*> fmt::Debug for $t {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(self.as_str(), f)
+            }
+        }
+
+        impl<$($params)*, const CASE_SENSITIVE: bool> From<&$t> for Atom<CASE_SENSITIVE> {
+            #[inline]
+            fn from(string: &$t) -> Self {
+                Atom::from(string.as_str())
+            }
+        }
+    }
+}
+
+impl_string_traits!(RString<A>, A: AllocatorBase);
+impl_string_traits!(InlineString<N>, const N: usize);
+
+impl<A: AllocatorBase + Unpin> SetKey for RString<A> { }
+impl<const N: usize> SetKey for InlineString<N> { }
@@ -2,30 +2,33 @@ use core::fmt;
 use std::slice;
 use std::str;
 use std::ptr::{self, NonNull};
-use std::mem::MaybeUninit;
+use std::sync::{OnceLock, RwLock};
+use std::borrow::Borrow;
 
-use crate::{fnv_hash_const, fnv_hash, FastHash, SetKey};
-use crate::RawSet;
+use crate::{fnv_hash_const, fnv_hash, hash_combine, FastHash, SetKey, SetItem};
+use crate::{RawSet, RawSetEntry, Set, HashMixer};
+use crate::alloc::ArrayAllocator;
 
-static mut STRINGS_TABLE: StringsTable = StringsTable::new(); // TODO multithreaded access (just use a mutex...)
+// Like unreal, the table is split in shards (last 4 bits of hash) each one behind its own RwLock
 // would also be nice to have a const new on StringAtom using a 'static &str and keeping that in the entry (no allocation...but gets a lot more complicated)
-// const new for StringAtom is a must because of the mutex to be added (i can keep const StringAtoms around to not create them @ runtime)
-// The mutex in unreal is on shards, not on the whole table (every shard is a table, e.g. shards can be last 4 bits of hash, 16 shards)
-// Don't use mutex, just an array of RwLock<RawSet>
+static STRINGS_TABLE: OnceLock<StringsTable> = OnceLock::new();
 
-const STRINGS_TABLE_ENTRY_MAX_LEN: usize = 128;
+const STRINGS_TABLE_SHARDS_NUM: usize = 16;
+pub(crate) const STRINGS_TABLE_ENTRY_MAX_LEN: usize = 128;
 
 struct StringsTableEntry {
     data: [u8; STRINGS_TABLE_ENTRY_MAX_LEN],
     len: usize,
+    case_sensitive: bool,
 }
 
 impl StringsTableEntry {
     #[inline]
-    fn new(bytes: &[u8]) -> Self {
+    fn new(bytes: &[u8], case_sensitive: bool) -> Self {
         let mut new_entry = StringsTableEntry {
             data: [0; STRINGS_TABLE_ENTRY_MAX_LEN],
-            len: bytes.len()
+            len: bytes.len(),
+            case_sensitive,
         };
         unsafe{ ptr::copy_nonoverlapping(bytes.as_ptr(), new_entry.data.as_mut_ptr(), bytes.len()) };
         new_entry
@@ -36,119 +39,212 @@ impl StringsTableEntry {
         slice::from_raw_parts(self.data.as_ptr(), self.len)
     }
 
+    // Both flavors live in the same shards, an entry only matches atoms of its own flavor
     #[inline]
-    unsafe fn as_str(&self) -> &str {
-        str::from_utf8_unchecked(self.as_bytes())
+    fn matches(&self, bytes: &[u8], case_sensitive: bool) -> bool {
+        if self.case_sensitive != case_sensitive {
+            return false;
+        }
+
+        let entry_bytes = unsafe{ self.as_bytes() };
+        if case_sensitive {
+            bytes == entry_bytes
+        } else {
+            bytes.eq_ignore_ascii_case(entry_bytes)
+        }
     }
 }
 
-struct StringsTable {
-    initialized: bool,
-    table: MaybeUninit<RawSet>,
-}
-
-unsafe impl Sync for StringsTable { }
-
-impl Drop for StringsTable {
-    fn drop(&mut self) {
-        unsafe {
-            if self.initialized {
-                let table = self.table.assume_init_mut();
-                ptr::drop_in_place(
-                    ptr::slice_from_raw_parts_mut(
-                        table.as_mut_ptr().cast::<StringsTableEntry>(),
-                        table.num()
-                    )
-                );
-                self.table.assume_init_drop();
+// Entries are boxed and never freed so StringAtoms can keep pointers to them while shards grow
+struct StringsTableShard(RawSet);
+
+unsafe impl Send for StringsTableShard { }
+unsafe impl Sync for StringsTableShard { }
+
+impl StringsTableShard {
+    #[inline]
+    fn entry(&self, index: usize) -> &'static StringsTableEntry {
+        unsafe{ &**self.0.as_ptr().cast::<*const StringsTableEntry>().add(index) }
+    }
+
+    fn find(&self, hash: usize, bytes: &[u8], case_sensitive: bool) -> Option<&'static StringsTableEntry> {
+        let mut entry_index = self.0.find_first_index(hash);
+        while entry_index != usize::MAX {
+            let entry = self.entry(entry_index);
+            if entry.matches(bytes, case_sensitive) {
+                return Some(entry);
             }
+            entry_index = self.0.find_next_index(entry_index);
         }
+        None
+    }
+
+    fn add(&mut self, hash: usize, bytes: &[u8], case_sensitive: bool) -> &'static StringsTableEntry {
+        let entry: &'static StringsTableEntry = Box::leak(Box::new(StringsTableEntry::new(bytes, case_sensitive)));
+        self.0.insert_data(hash, |ptr| unsafe {
+            ptr::write(ptr.cast::<*const StringsTableEntry>(), entry)
+        });
+        entry
     }
 }
 
+struct StringsTable {
+    shards: [RwLock<StringsTableShard>; STRINGS_TABLE_SHARDS_NUM],
+}
+
 impl StringsTable {
-    const fn new() -> Self {
+    fn new() -> Self {
         Self {
-            initialized: false,
-            table: MaybeUninit::uninit(),
+            shards: std::array::from_fn(|_| RwLock::new(StringsTableShard(RawSet::for_type::<*const StringsTableEntry>()))),
         }
     }
 
     #[inline]
-    fn lazy_init(&mut self) {
-        if !self.initialized {
-            self.table = MaybeUninit::new(RawSet::for_type::<StringsTableEntry>());
-            self.initialized = true;
-        }
+    fn get() -> &'static StringsTable {
+        STRINGS_TABLE.get_or_init(StringsTable::new)
     }
 
-    fn get_or_add_string(&mut self, hash: usize, bytes: &[u8]) -> StringAtom {
-        self.lazy_init();
+    fn get_or_add_string<const CASE_SENSITIVE: bool>(&self, hash: usize, bytes: &[u8]) -> Atom<CASE_SENSITIVE> {
+        assert!(bytes.len() <= STRINGS_TABLE_ENTRY_MAX_LEN, "Atoms are limited to {} bytes", STRINGS_TABLE_ENTRY_MAX_LEN);
 
-        debug_assert!(bytes.len() <= STRINGS_TABLE_ENTRY_MAX_LEN);
+        let shard_lock = &self.shards[hash % STRINGS_TABLE_SHARDS_NUM];
 
-        let mut entry_index = unsafe{ self.table.assume_init_ref().find_first_index(hash) };
-        while entry_index != usize::MAX {
-            let entry_bytes = unsafe {
-                (&*self.table
-                    .assume_init_ref()
-                    .as_ptr()
-                    .cast::<StringsTableEntry>()
-                    .add(entry_index))
-                    .as_bytes()
-            };
-            if bytes.eq_ignore_ascii_case(entry_bytes) {
-                break;
+        let existing_entry = shard_lock.read().unwrap().find(hash, bytes, CASE_SENSITIVE);
+        let entry = match existing_entry {
+            Some(entry) => entry,
+            None => {
+                let mut shard = shard_lock.write().unwrap();
+                // Another thread could have added it between the two locks
+                match shard.find(hash, bytes, CASE_SENSITIVE) {
+                    Some(entry) => entry,
+                    None => shard.add(hash, bytes, CASE_SENSITIVE),
+                }
             }
-            entry_index = unsafe{ self.table.assume_init_ref().find_next_index(entry_index) };
-        }
-
-        if entry_index == usize::MAX {
-            entry_index = unsafe {
-                self.table
-                    .assume_init_mut()
-                    .insert_data(hash, |ptr| {
-                        ptr::write(ptr.cast::<StringsTableEntry>(), StringsTableEntry::new(bytes))
-                    })
-            };
-        }
-
-        let entry = unsafe {
-            &*self.table
-                .assume_init_ref()
-                .as_ptr()
-                .cast::<StringsTableEntry>()
-                .add(entry_index)
         };
 
-        StringAtom {
+        Atom {
             hash,
             ptr: (&entry.data[0]).into(),
-            len: entry.len
+            len: entry.len,
+            number: 0,
         }
     }
+
+    fn dump(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let mut entries = Vec::new();
+        for (shard_index, shard_lock) in self.shards.iter().enumerate() {
+            let shard = shard_lock.read().unwrap();
+            for entry_index in 0..shard.0.num() {
+                let entry = shard.entry(entry_index);
+                entries.push((shard.0.get_hash(entry_index), shard_index, entry.case_sensitive, unsafe{ entry.as_bytes() }));
+            }
+        }
+        entries.sort_by_key(|(hash, _, _, _)| *hash);
+
+        writeln!(out, "{} interned strings", entries.len())?;
+        for (i, (hash, shard_index, case_sensitive, bytes)) in entries.iter().enumerate() {
+            let collides = (i > 0 && entries[i - 1].0 == *hash) || (i + 1 < entries.len() && entries[i + 1].0 == *hash);
+            writeln!(out, "{:#018x} shard {:2} {} {:?}{}",
+                hash,
+                shard_index,
+                if *case_sensitive { "cs" } else { "ci" },
+                unsafe{ str::from_utf8_unchecked(bytes) },
+                if collides { " (collision)" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+// Lists every interned string with its hash, sorted by hash so collisions end up next to each other
+pub fn dump_strings_table() -> String {
+    let mut out = String::new();
+    StringsTable::get().dump(&mut out).unwrap();
+    out
 }
 
-pub struct StringAtom {
+// Splits "Sphere_12" in ("Sphere", 12), numbers with leading zeros ("Box_03") are kept in the base string
+fn split_number_suffix(bytes: &[u8]) -> (&[u8], Option<u32>) {
+    const MAX_DIGITS: usize = 9;
+
+    let mut digits = 0;
+    while digits < bytes.len() && bytes[bytes.len() - 1 - digits].is_ascii_digit() {
+        digits += 1;
+    }
+
+    let digits_start = bytes.len() - digits;
+    if digits == 0 || digits > MAX_DIGITS || digits_start < 2 || bytes[digits_start - 1] != b'_' {
+        return (bytes, None);
+    }
+
+    if digits > 1 && bytes[digits_start] == b'0' {
+        return (bytes, None);
+    }
+
+    let mut number = 0u32;
+    for digit in &bytes[digits_start..] {
+        number = number * 10 + (digit - b'0') as u32;
+    }
+
+    (&bytes[..digits_start - 1], Some(number))
+}
+
+// The base string lives in the table, the numeric suffix lives in the atom ("Sphere_12" is ("Sphere", 12))
+// Case insensitive atoms display the first casing interned, case sensitive ones their exact casing
+#[derive(Copy, Clone)]
+pub struct Atom<const CASE_SENSITIVE: bool> {
     hash: usize,
     ptr: NonNull<u8>,
     len: usize,
+    number: u32, // 0 is no number, otherwise suffix + 1
 }
 
-impl StringAtom {
+// Points to leaked, immutable table entries
+unsafe impl<const CASE_SENSITIVE: bool> Send for Atom<CASE_SENSITIVE> { }
+unsafe impl<const CASE_SENSITIVE: bool> Sync for Atom<CASE_SENSITIVE> { }
+
+pub type StringAtom = Atom<false>;
+pub type CaseSensitiveAtom = Atom<true>;
+
+#[inline]
+fn hash_bytes<const CASE_SENSITIVE: bool>(bytes: &[u8]) -> usize {
+    if CASE_SENSITIVE {
+        fnv_hash::<false>(bytes) as usize
+    } else {
+        fnv_hash::<true>(bytes) as usize
+    }
+}
+
+impl<const CASE_SENSITIVE: bool> Atom<CASE_SENSITIVE> {
     #[inline]
     pub const fn none() -> Self {
         Self {
             hash: 0,
             ptr: NonNull::dangling(),
-            len: 0
+            len: 0,
+            number: 0,
         }
     }
 
     #[inline]
     pub fn new<const N: usize>(string: &[u8; N]) -> Self {
-        let hash = fnv_hash_const(string, true) as usize;
-        unsafe{ STRINGS_TABLE.get_or_add_string(hash, string) }
+        match split_number_suffix(string) {
+            (_, None) => {
+                let hash = fnv_hash_const(string, !CASE_SENSITIVE) as usize;
+                StringsTable::get().get_or_add_string(hash, string)
+            }
+            (base, Some(number)) => Self::from_base_and_number(base, number),
+        }
+    }
+
+    #[inline]
+    fn from_base_and_number(base: &[u8], number: u32) -> Self {
+        Self::from_base_bytes(base).with_number(number)
+    }
+
+    // Interns the bytes as they are, without splitting a numeric suffix
+    #[inline]
+    pub(crate) fn from_base_bytes(base: &[u8]) -> Self {
+        StringsTable::get().get_or_add_string(hash_bytes::<CASE_SENSITIVE>(base), base)
     }
 
     #[inline]
@@ -156,59 +252,143 @@ impl StringAtom {
         self.len == 0
     }
 
+    // Only the base string ("Sphere" for "Sphere_12"), use Display/to_string to get the numeric suffix too
     #[inline]
-    pub fn as_str(&self) -> &str {
+    pub fn base_str(&self) -> &str {
         if self.len > 0 {
             unsafe{ str::from_utf8_unchecked(slice::from_raw_parts(self.ptr.as_ptr(), self.len)) }
         } else {
             ""
         }
     }
-}
 
-impl PartialEq<Self> for StringAtom {
-    fn eq(&self, other: &Self) -> bool {
+    #[inline]
+    pub(crate) fn raw_number(&self) -> u32 {
+        self.number
+    }
+
+    #[inline]
+    pub(crate) fn with_raw_number(&self, raw_number: u32) -> Self {
+        Self { number: raw_number, ..*self }
+    }
+
+    #[inline]
+    pub fn number(&self) -> Option<u32> {
+        if self.number == 0 { None } else { Some(self.number - 1) }
+    }
+
+    #[inline]
+    pub fn with_number(&self, number: u32) -> Self {
+        debug_assert!(number < u32::MAX);
+        Self { number: number + 1, ..*self }
+    }
+
+    #[inline]
+    pub fn without_number(&self) -> Self {
+        Self { number: 0, ..*self }
+    }
+
+    // Interns the displayed base casing in the other flavor, the number is kept
+    #[inline]
+    fn convert<const OTHER_CASE_SENSITIVE: bool>(&self) -> Atom<OTHER_CASE_SENSITIVE> {
         if self.is_none() {
-            other.is_none()
-        } else {
-            !other.is_none() && self.hash == other.hash && self.ptr == other.ptr
+            return Atom::none();
         }
+        Atom::<OTHER_CASE_SENSITIVE>::from_base_bytes(self.base_str().as_bytes()).with_raw_number(self.number)
     }
 
-    fn ne(&self, other: &Self) -> bool {
+    // Lowest numbered variant of this atom's base not used as key in the set
+    pub fn next_unique_in<T, DataAlloc, EntriesAlloc, TableAlloc, Mix>(&self, set: &Set<T, DataAlloc, EntriesAlloc, TableAlloc, Mix>) -> Self where
+        T: SetItem,
+        T::KeyType: Borrow<Self>,
+        DataAlloc: ArrayAllocator<T>,
+        EntriesAlloc: ArrayAllocator<RawSetEntry>,
+        TableAlloc: ArrayAllocator<usize>,
+        Mix: HashMixer
+    {
+        let mut number = 0;
+        loop {
+            let candidate = self.with_number(number);
+            if set.find_first_index(&candidate) == usize::MAX {
+                return candidate;
+            }
+            number += 1;
+        }
+    }
+}
+
+impl StringAtom {
+    #[inline]
+    pub fn to_case_sensitive(&self) -> CaseSensitiveAtom {
+        self.convert()
+    }
+}
+
+impl CaseSensitiveAtom {
+    // Resolves to the casing first interned as case insensitive, that could differ from this one
+    #[inline]
+    pub fn to_case_insensitive(&self) -> StringAtom {
+        self.convert()
+    }
+}
+
+impl<const CASE_SENSITIVE: bool> PartialEq<Self> for Atom<CASE_SENSITIVE> {
+    fn eq(&self, other: &Self) -> bool {
         if self.is_none() {
-            !other.is_none()
+            other.is_none()
         } else {
-            other.is_none() || self.hash != other.hash || self.ptr != other.ptr
+            !other.is_none() && self.hash == other.hash && self.ptr == other.ptr && self.number == other.number
         }
     }
 }
 
-impl Eq for StringAtom { }
+impl<const CASE_SENSITIVE: bool> Eq for Atom<CASE_SENSITIVE> { }
+
+impl<const CASE_SENSITIVE: bool> Default for Atom<CASE_SENSITIVE> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
 
-impl FastHash for StringAtom {
+impl<const CASE_SENSITIVE: bool> FastHash for Atom<CASE_SENSITIVE> {
     fn fast_hash(&self) -> usize {
-        self.hash
+        if self.number == 0 {
+            self.hash
+        } else {
+            hash_combine(self.hash, self.number as usize)
+        }
     }
 }
 
-impl SetKey for StringAtom { }
+impl<const CASE_SENSITIVE: bool> SetKey for Atom<CASE_SENSITIVE> { }
 
-impl From<&str> for StringAtom {
+impl<const CASE_SENSITIVE: bool> From<&str> for Atom<CASE_SENSITIVE> {
     fn from(string: &str) -> Self {
         let string_bytes = string.as_bytes();
-        unsafe{ STRINGS_TABLE.get_or_add_string(fnv_hash::<true>(string_bytes) as usize, string_bytes) }
+        match split_number_suffix(string_bytes) {
+            (_, None) => StringsTable::get().get_or_add_string(hash_bytes::<CASE_SENSITIVE>(string_bytes), string_bytes),
+            (base, Some(number)) => Self::from_base_and_number(base, number),
+        }
     }
 }
 
-impl Into<String> for StringAtom {
+impl<const CASE_SENSITIVE: bool> Into<String> for Atom<CASE_SENSITIVE> {
     fn into(self) -> String {
-        self.as_str().to_string()
+        self.to_string()
+    }
+}
+
+impl<const CASE_SENSITIVE: bool> fmt::Display for Atom<CASE_SENSITIVE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.number() {
+            None => f.pad(self.base_str()),
+            Some(number) => write!(f, "{}_{}", self.base_str(), number),
+        }
     }
 }
 
-impl fmt::Debug for StringAtom {
+impl<const CASE_SENSITIVE: bool> fmt::Debug for Atom<CASE_SENSITIVE> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.pad(self.as_str())
+        fmt::Display::fmt(self, f)
     }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
 use crate::alloc::DefaultAllocator;
-use crate::{SetItem, fnv_hash, fnv_hash_const};
+use crate::{SetItem, fnv_hash, fnv_hash_const, fnv_hash64, fnv_hash64_const, xxh64_hash, xxh64_hash_const, xxh3_hash, xxh3_hash_const};
 use crate::{Array, InlineArray, Set, Map, strings_table::StringAtom};
+use crate::{BitArray, BitSet, words_for_bits};
+use crate::{PriorityQueue, IndexedPriorityQueue};
 
 #[test]
 fn stringatom_test() {
@@ -10,13 +12,105 @@ fn stringatom_test() {
     assert_eq!(name0, name1);
     assert_eq!(name0, name2);
     assert_eq!(name1, name2);
-    assert_eq!(name2.as_str(), "nAMe tESt");
+    assert_eq!(name2.base_str(), "nAMe tESt");
+
+    // The strings table entries are fixed size, longer strings are refused in release too
+    let longest = "a".repeat(128);
+    assert_eq!(StringAtom::from(longest.as_str()).base_str(), longest);
+    assert!(std::panic::catch_unwind(|| StringAtom::from("a".repeat(129).as_str())).is_err());
 }
 
 #[test]
 fn fnv_test() {
     assert_eq!(fnv_hash::<false>("Hello world!".as_bytes()), fnv_hash_const(b"Hello world!", false));
     assert_eq!(fnv_hash::<true>("hello WORLD!".as_bytes()), fnv_hash_const(b"HeLLo woRLd!", true));
+    assert_eq!(fnv_hash64::<false>(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(fnv_hash64::<true>("hello WORLD!".as_bytes()), fnv_hash64_const(b"HeLLo woRLd!", true));
+}
+
+#[test]
+fn xxh64_test() {
+    const EMPTY_HASH: u64 = xxh64_hash_const(b"", 0);
+    assert_eq!(EMPTY_HASH, 0xef46db3751d8e999);
+    assert_eq!(xxh64_hash(b"abc", 0), 0x44bc2cf5ad770999);
+    assert_eq!(xxh64_hash(b"Nobody inspects the spammish repetition", 0), 0xfbcea83c8a378bf1);
+    assert_ne!(xxh64_hash(b"Nobody inspects the spammish repetition", 1), 0xfbcea83c8a378bf1);
+}
+
+#[test]
+fn xxh3_test() {
+    const HASH: u64 = xxh3_hash_const(b"Nobody inspects the spammish repetition", 0);
+    assert_eq!(HASH, 0x6cb00603b5cc47e9);
+    assert_eq!(xxh3_hash(b"Nobody inspects the spammish repetition", 1), 0x21c83ff4205c56d0);
+
+    // Every length path, with and without a seed
+    let bytes: Array<u8> = (0..2048u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+    let expected: [(usize, u64, u64); 26] = [
+        (0, 0x2d06800538d394c2, 0x07f70f819703314d),
+        (1, 0xc44bdff4074eecdb, 0x719ae0fc4eb5db08),
+        (2, 0x1ca5cfa6a6d57dc2, 0x3b649c3d935a18d5),
+        (3, 0xa1c4a8259b827291, 0x7d0052f498092e08),
+        (4, 0xbb4e3d89ee0b271d, 0x323d0a92304b3d55),
+        (5, 0xdafb9806147ded6f, 0x6ccc2ca4b0b22b69),
+        (8, 0x79d02238b80e37b1, 0xf26e264286d9dd96),
+        (9, 0xf64cecc4271ff461, 0xa40e2a4adf16abb3),
+        (16, 0x222e9aead6bddd51, 0x0b20f724758c1efb),
+        (17, 0x47aad6b375eb4bba, 0x1bddd4734d957a7b),
+        (32, 0x774140158f21ff0a, 0x018457a1fd6a5f25),
+        (33, 0x537e7ed26d825e92, 0xab6ad5ac24daf90f),
+        (64, 0x70a70e66815e67e5, 0x4cb45f5fb38f2177),
+        (65, 0x4b4ce7050eeb9559, 0x9b609965d99085cb),
+        (96, 0xa97f9ae93c0ff67a, 0x298e6a09f45cec10),
+        (97, 0x0dd88db1bbaf7326, 0xbd2c34cabe819b16),
+        (128, 0x421a9c905c6e66ba, 0x2780ca88ba9d60c0),
+        (129, 0x9e2414800f83768a, 0x6066274702dc874a),
+        (200, 0x20a87db907ce74e4, 0x7a9450641c88df35),
+        (240, 0xb714c5fd22744964, 0x68d516165ecf407a),
+        (241, 0xbc424a2c480dd281, 0x326418fbc2e4f0b5),
+        (255, 0x155baa5891f7606f, 0x8607866d1773c7ba),
+        (256, 0x2d040b1ab40f0d78, 0x34c3c27ee7f3106d),
+        (1024, 0x1fd15e7d36f5e1bc, 0x42db26d8029f24e3),
+        (1025, 0xfe08e5a874d23fd2, 0x521bd4a252ef2b5d),
+        (2048, 0x81ec4a6a9ee23d55, 0x4b20bf88fadd6ec9),
+    ];
+    for (len, unseeded, seeded) in expected {
+        assert_eq!(xxh3_hash(&bytes[..len], 0), unseeded, "length {}", len);
+        assert_eq!(xxh3_hash(&bytes[..len], 0x9E3779B185EBCA87), seeded, "seeded length {}", len);
+    }
+}
+
+#[test]
+fn hash_mixer_test() {
+    use crate::{IdentityMixer, SplitMix64Mixer, Murmur3Mixer, WyMixer, HashMixer};
+
+    assert_ne!(SplitMix64Mixer::mix(64), SplitMix64Mixer::mix(128));
+
+    // Buckets used and the fullest bucket for 1024 page aligned keys in 1024 buckets
+    fn buckets_load<Mix: HashMixer>() -> (usize, usize) {
+        let mut loads = [0usize; 1024];
+        for i in 0..1024usize {
+            loads[Mix::mix(i * 4096) % loads.len()] += 1;
+        }
+        (loads.iter().filter(|load| **load > 0).count(), *loads.iter().max().unwrap())
+    }
+    assert_eq!(buckets_load::<IdentityMixer>(), (1, 1024));
+    // A uniform hash uses about 1 - 1/e of the buckets, with no more than a handful of keys in any of them
+    for (used, fullest) in [buckets_load::<SplitMix64Mixer>(), buckets_load::<Murmur3Mixer>(), buckets_load::<WyMixer>()] {
+        assert!(used > 610 && used < 690, "{} buckets used", used);
+        assert!(fullest <= 8, "{} keys in a bucket", fullest);
+    }
+
+    // Aligned keys would all land in the same bucket without mixing
+    let mut map: Map<u64, u64, DefaultAllocator, DefaultAllocator, DefaultAllocator, Murmur3Mixer> = Map::default();
+    for i in 0..256 {
+        map.insert(i * 4096, i);
+    }
+    for i in 0..256 {
+        assert_eq!(map[&(i * 4096)], i);
+    }
+    assert_eq!(map.remove(&4096), Some(1));
+    assert!(!map.contains(&4096));
+    assert_eq!(map.num(), 255);
 }
 
 #[test]
@@ -36,9 +130,18 @@ fn map_test() {
     map["other key"] = 40;
     assert_eq!(map["other key"], 40);
     assert_eq!(map.remove("key").unwrap(), 10);
+    assert_eq!(map.remove("key"), None);
+    assert_eq!(map["other key"], 40);
+    let mut names: Map<i32, String> = Map::new();
+    for i in 0..10 {
+        names.insert(i, i.to_string());
+    }
+    assert_eq!(names.remove(&3).as_deref(), Some("3"));
+    assert_eq!(names.num(), 9);
+    assert!((0..10).filter(|i| *i != 3).all(|i| names[&i] == i.to_string()));
     map.clear();
     assert!(map.is_empty());
-    assert_eq!(*map.get_or_insert_default_mut("default key".to_string()), Default::default());
+    assert_eq!(*map.get_or_insert_default_mut("default key".to_string()), 0);
     assert_eq!(map.contains("default key"), true);
 }
 
@@ -96,6 +199,9 @@ fn set_test() {
 
 #[test]
 fn array_test() {
+    // Empty arrays still have a pointer aligned for their items
+    assert!(Array::<u64>::new().as_ptr().is_aligned());
+    assert!(Array::<u128>::new().as_slice().is_empty());
     let mut array = Array::new();
     array.push_back(2);
     array.push_front(1);
@@ -143,3 +249,1513 @@ fn array_test() {
     let inline_array: InlineArray<i32, INLINE_TEST_SIZE> = Array::custom_allocator();
     assert_eq!(std::mem::size_of_val(&inline_array), std::mem::size_of_val(&array) + std::mem::size_of::<i32>() * INLINE_TEST_SIZE);
 }
+
+#[test]
+fn bitarray_test() {
+    let mut bits = BitArray::with_num(70, false);
+    assert_eq!(bits.num(), 70);
+    assert_eq!(bits.count_ones(), 0);
+    bits.set(3);
+    bits.set(64);
+    bits.set(69);
+    assert!(bits.get(64));
+    assert_eq!(bits.count_ones(), 3);
+    bits.toggle(3);
+    bits.toggle(4);
+    bits.clear(69);
+    assert_eq!(bits.iter_ones().collect::<Vec<usize>>(), vec![4, 64]);
+    assert_eq!(bits.find_first_set(), 4);
+    assert_eq!(bits.find_first_unset(), 0);
+
+    let inverted = !bits.clone();
+    assert_eq!(inverted.count_ones(), 68);
+    assert_eq!(inverted.find_first_unset(), 4);
+
+    let mut other = BitArray::with_num(70, true);
+    other.clear(64);
+    other &= &bits;
+    assert_eq!(other.iter_ones().collect::<Vec<usize>>(), vec![4]);
+    other |= &bits;
+    assert_eq!(other, bits);
+    other ^= &bits;
+    assert!(!other.any());
+
+    let mut full = BitArray::with_num(3, true);
+    assert_eq!(full.find_first_unset(), usize::MAX);
+    full.resize(130, true);
+    assert_eq!(full.count_ones(), 130);
+    full.push_back(false);
+    assert_eq!(full.find_first_unset(), 130);
+
+    // Bits past the end share the last word but are out of bounds, in release too
+    assert!(std::panic::catch_unwind(|| BitArray::with_num(70, false).get(70)).is_err());
+    assert!(std::panic::catch_unwind(|| BitArray::with_num(70, false).set(100)).is_err());
+    assert!(std::panic::catch_unwind(|| BitArray::with_num(70, false).toggle(127)).is_err());
+
+    let mut set = BitSet::<{ words_for_bits(128) }>::new();
+    assert_eq!(set.num(), 128);
+    set.set(1);
+    set.set(127);
+    assert_eq!(set.count_ones(), 2);
+    assert_eq!(set.iter_ones().collect::<Vec<usize>>(), vec![1, 127]);
+    let mut mask = BitSet::full();
+    mask.clear(127);
+    assert_eq!((set & mask).iter_ones().collect::<Vec<usize>>(), vec![1]);
+    assert_eq!((set | mask).count_ones(), 128);
+    assert_eq!((set ^ mask).count_ones(), 127);
+    assert_eq!((!mask).find_first_set(), 127);
+    assert_eq!(BitSet::<2>::full().find_first_unset(), usize::MAX);
+    assert_eq!(BitSet::<2>::BITS, 128);
+}
+
+#[test]
+fn priority_queue_test() {
+    let mut queue: PriorityQueue<i32> = [5, 1, 8, 3, 9, 2].into_iter().collect();
+    assert_eq!(queue.num(), 6);
+    assert_eq!(*queue.peek().unwrap(), 9);
+    assert_eq!(queue.pop(), Some(9));
+    assert_eq!(queue.pop(), Some(8));
+    queue.push(7);
+    assert_eq!(queue.pop(), Some(7));
+    assert_eq!(queue.into_sorted_array().as_slice(), &[1, 2, 3, 5]);
+
+    let mut min_queue = PriorityQueue::with_compare(|a: &i32, b: &i32| b.cmp(a));
+    min_queue.extend([4, 6, 1, 3]);
+    assert_eq!(min_queue.pop(), Some(1));
+    assert_eq!(min_queue.pop(), Some(3));
+    assert_eq!(min_queue.into_sorted_array().as_slice(), &[6, 4]);
+
+    let mut indexed = IndexedPriorityQueue::new();
+    for key in 0..8 {
+        indexed.push(key, key as i32 * 10);
+    }
+    assert_eq!(indexed.peek(), Some((7, &70)));
+    assert_eq!(indexed.change_priority(7, 5), 70);
+    assert_eq!(indexed.change_priority(2, 100), 20);
+    assert_eq!(indexed.remove(4), Some(40));
+    assert!(!indexed.contains(4));
+    assert_eq!(*indexed.get(2).unwrap(), 100);
+    let mut popped = Array::new();
+    while let Some((key, _)) = indexed.pop() {
+        popped.push_back(key);
+    }
+    assert_eq!(popped.as_slice(), &[2, 6, 5, 3, 1, 7, 0]);
+}
+
+#[test]
+fn fast_hash_test() {
+    use crate::{FastHash, SetKey};
+
+    let mut map: Map<(u8, char), bool> = Map::new();
+    map.insert((1, 'a'), true);
+    map.insert((2, 'b'), false);
+    assert!(map[&(1, 'a')]);
+    assert!(!map.contains(&(1, 'b')));
+
+    let mut grid: Map<[i32; 3], usize> = Map::new();
+    grid.insert([0, 1, 2], 7);
+    assert_eq!(grid[&[0, 1, 2]], 7);
+    assert_ne!([0, 1, 2].fast_hash(), [2, 1, 0].fast_hash());
+
+    #[derive(PartialEq, Eq, FastHash, SetKey)]
+    struct ChunkCoord {
+        x: i16,
+        y: i16,
+        lod: u8,
+    }
+
+    #[derive(PartialEq, Eq, FastHash, SetKey)]
+    enum ShapeKey {
+        Sphere,
+        Box(u16),
+    }
+
+    #[derive(SetItem)]
+    struct Chunk {
+        #[key]
+        coord: ChunkCoord,
+        dirty: bool,
+    }
+
+    assert_ne!(ShapeKey::Sphere.fast_hash(), ShapeKey::Box(0).fast_hash());
+
+    let mut chunks = Set::new();
+    chunks.insert(Chunk { coord: ChunkCoord { x: 1, y: 2, lod: 0 }, dirty: true });
+    chunks.insert(Chunk { coord: ChunkCoord { x: 2, y: 1, lod: 0 }, dirty: false });
+    let index = chunks.find_first_index(&ChunkCoord { x: 2, y: 1, lod: 0 });
+    assert!(!chunks[index].dirty);
+    assert_eq!(chunks.find_first_index(&ChunkCoord { x: 2, y: 1, lod: 1 }), usize::MAX);
+}
+
+#[test]
+fn stringatom_number_test() {
+    let sphere12: StringAtom = "Sphere_12".into();
+    let sphere = StringAtom::new(b"sphere");
+    assert_eq!(sphere12.base_str(), sphere.base_str());
+    assert_eq!(sphere12.number(), Some(12));
+    assert_eq!(sphere12, sphere.with_number(12));
+    assert_eq!(sphere12, StringAtom::new(b"SPHERE_12"));
+    assert_ne!(sphere12, sphere);
+    assert_ne!(sphere12, sphere.with_number(3));
+    assert_eq!(sphere12.without_number(), sphere);
+    assert_eq!(sphere12.to_string(), "Sphere_12");
+    assert_eq!(format!("{:?}", sphere), "Sphere");
+
+    let leading_zero: StringAtom = "Box_03".into();
+    assert_eq!(leading_zero.number(), None);
+    assert_eq!(leading_zero.base_str(), "Box_03");
+    let no_base: StringAtom = "_7".into();
+    assert_eq!(no_base.number(), None);
+
+    #[derive(SetItem)]
+    struct NamedObject {
+        #[key]
+        name: StringAtom,
+    }
+
+    let mut objects = Set::new();
+    for number in [0, 1, 3] {
+        objects.insert(NamedObject { name: sphere.with_number(number) });
+    }
+    let unique_name = sphere.next_unique_in(&objects);
+    assert_eq!(unique_name.to_string(), "Sphere_2");
+    objects.insert(NamedObject { name: unique_name });
+    assert_eq!(sphere.next_unique_in(&objects).to_string(), "Sphere_4");
+}
+
+#[test]
+fn name_table_test() {
+    use crate::{NameTableWriter, NameTableReader, NameIndex, dump_strings_table};
+
+    let atoms: [StringAtom; 4] = ["Cube_1".into(), "Cube_2".into(), "Light".into(), StringAtom::none()];
+
+    let mut names = NameTableWriter::new();
+    let mut payload = Vec::new();
+    for atom in &atoms {
+        names.add(*atom).write_to(&mut payload).unwrap();
+    }
+    assert_eq!(names.num(), 2);
+
+    let mut file = Vec::new();
+    names.write_to(&mut file).unwrap();
+    file.extend_from_slice(&payload);
+
+    let mut reader = file.as_slice();
+    let loaded_names = NameTableReader::read_from(&mut reader).unwrap();
+    assert_eq!(loaded_names.num(), 2);
+    for atom in &atoms {
+        let name_index = NameIndex::read_from(&mut reader).unwrap();
+        assert_eq!(loaded_names.resolve(name_index).unwrap(), *atom);
+    }
+    assert!(loaded_names.resolve(NameIndex { index: 5, number: 0 }).is_none());
+
+    let corrupted = [1u8, 0, 0, 0, 2, 0xff, 0xfe];
+    assert!(NameTableReader::read_from(&mut corrupted.as_slice()).is_err());
+
+    let dump = dump_strings_table();
+    assert!(dump.contains("\"Cube\""));
+    assert!(dump.contains("\"Light\""));
+}
+
+#[test]
+fn case_sensitive_atom_test() {
+    use crate::CaseSensitiveAtom;
+
+    let path0: CaseSensitiveAtom = "Shaders/SDFCommon.glsl".into();
+    let path1: CaseSensitiveAtom = "shaders/sdfcommon.glsl".into();
+    let path2 = CaseSensitiveAtom::new(b"Shaders/SDFCommon.glsl");
+    assert_ne!(path0, path1);
+    assert_eq!(path0, path2);
+    assert_eq!(path1.base_str(), "shaders/sdfcommon.glsl");
+
+    let name: StringAtom = "MixedCase Name".into();
+    let exact = name.to_case_sensitive();
+    assert_eq!(exact.base_str(), "MixedCase Name");
+    let lower: StringAtom = "mixedcase name".into();
+    assert_eq!(lower.base_str(), "MixedCase Name");
+    assert_ne!(lower.to_case_sensitive(), CaseSensitiveAtom::from("mixedcase name"));
+
+    let numbered: CaseSensitiveAtom = "Light_4".into();
+    assert_eq!(numbered.number(), Some(4));
+    assert_eq!(numbered.to_case_insensitive(), StringAtom::from("LIGHT_4"));
+    assert_eq!(numbered.to_case_insensitive().to_string(), "Light_4");
+}
+
+#[test]
+fn reflect_test() {
+    use std::any::TypeId;
+    use crate::{Reflect, Reflected, TypeInfo, find_type};
+
+    #[derive(Reflect, Clone, Default, Debug, PartialEq)]
+    #[reflect(clone, default)]
+    struct Light {
+        name: StringAtom,
+        intensity: f32,
+        casts_shadows: bool,
+        #[reflect(skip)]
+        cache_index: usize,
+    }
+
+    #[derive(Reflect)]
+    struct LightPair(Light, u32);
+
+    let type_info = Light::type_info();
+    assert!(std::ptr::eq(type_info, Light::type_info()));
+    assert!(std::ptr::eq(find_type(&TypeId::of::<Light>()).unwrap(), type_info));
+    assert_eq!(type_info.get_fields().len(), 3);
+    assert!(type_info.find_field("cache_index".into()).is_none());
+    assert!(type_info.can_clone() && type_info.can_default());
+
+    let mut light = Light { name: "Sun".into(), intensity: 2.0, casts_shadows: true, cache_index: 7 };
+    let reflected: &mut dyn Reflected = &mut light;
+    assert_eq!(reflected.field::<f32>("Intensity".into()), Some(&2.0));
+    assert_eq!(reflected.field::<u32>("intensity".into()), None);
+    *reflected.field_mut::<bool>("casts_shadows".into()).unwrap() = false;
+    assert!(!light.casts_shadows);
+
+    let debug = format!("{:?}", &light as &dyn Reflected);
+    assert!(debug.contains("intensity: 2.0"));
+    assert!(debug.contains("casts_shadows: false"));
+    assert!(!debug.contains("cache_index"));
+
+    let mut cloned = std::mem::MaybeUninit::<Light>::uninit();
+    let mut defaulted = std::mem::MaybeUninit::<Light>::uninit();
+    unsafe {
+        assert!(type_info.clone_into((&light as *const Light).cast(), cloned.as_mut_ptr().cast()));
+        assert!(type_info.default_into(defaulted.as_mut_ptr().cast()));
+        assert_eq!(cloned.assume_init(), light);
+        assert_eq!(defaulted.assume_init(), Light::default());
+    }
+
+    let pair = LightPair(light.clone(), 3);
+    let pair_info: &TypeInfo = LightPair::type_info();
+    assert!(!pair_info.can_clone());
+    let pair_reflected: &dyn Reflected = &pair;
+    assert_eq!(pair_reflected.field::<u32>("1".into()), Some(&3));
+    let inner = pair_info.find_field("0".into()).unwrap();
+    assert!(std::ptr::eq(inner.get_type_info(), type_info));
+    // Fields only read objects of the type they belong to
+    assert_eq!(inner.get::<Light>(&light as &dyn Reflected), None);
+    let intensity = type_info.find_field("intensity".into()).unwrap();
+    assert_eq!(intensity.get::<f32>(&pair as &dyn Reflected), None);
+    assert_eq!(intensity.get::<f32>(&light as &dyn Reflected), Some(&light.intensity));
+
+    // Layouts are checked in release too
+    let overflowing_field = std::panic::catch_unwind(|| {
+        TypeInfo::of::<u32>().with_field(unsafe{ crate::FieldInfo::new::<u32>("x".into(), 2) })
+    });
+    assert!(overflowing_field.is_err());
+    assert!(std::panic::catch_unwind(|| TypeInfo::of::<u32>().with_clone::<u64>()).is_err());
+}
+
+#[test]
+fn any_array_test() {
+    use std::rc::Rc;
+    use crate::{AnyArray, Reflect, TypeInfo, register_type_with};
+
+    #[derive(Clone)]
+    struct Counted(#[allow(dead_code)] Rc<()>);
+
+    unsafe impl Reflect for Counted {
+        fn type_info() -> &'static TypeInfo {
+            register_type_with::<Counted, _>(|| TypeInfo::of::<Counted>().with_clone::<Counted>())
+        }
+    }
+
+    let counter = Rc::new(());
+    let mut counted = AnyArray::new::<Counted>();
+    for _ in 0..5 {
+        counted.push(Counted(counter.clone()));
+    }
+    assert_eq!(Rc::strong_count(&counter), 6);
+    counted.swap_remove(0);
+    counted.remove(1);
+    assert_eq!(Rc::strong_count(&counter), 4);
+
+    let cloned = counted.try_clone().unwrap();
+    assert_eq!(cloned.num(), 3);
+    assert_eq!(Rc::strong_count(&counter), 7);
+    drop(cloned);
+    assert!(counted.take::<Counted>(0).is_some());
+    assert_eq!(Rc::strong_count(&counter), 3);
+    drop(counted);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    let mut values = AnyArray::with_capacity::<u32>(4);
+    values.push(1u32);
+    values.push(2u32);
+    assert!(values.push_default());
+    assert_eq!(values.get::<u32>(1), Some(&2));
+    assert_eq!(values.get::<i32>(1), None);
+    assert_eq!(values.get::<u32>(3), None);
+    *values.get_mut::<u32>(0).unwrap() = 10;
+
+    values.downcast_mut::<u32>().unwrap().push_back(4);
+    assert!(values.downcast_ref::<u64>().is_none());
+    assert_eq!(values.as_slice::<u32>().unwrap(), &[10, 2, 0, 4]);
+    assert_eq!(format!("{:?}", values), "[10, 2, 0, 4]");
+
+    let mut moved = AnyArray::for_type_info(values.get_type_info());
+    let mut tmp = 0u32;
+    unsafe {
+        values.swap_remove_to_ptr(0, (&mut tmp as *mut u32).cast());
+        moved.push_from_ptr((&tmp as *const u32).cast());
+    }
+    assert_eq!(moved.as_slice::<u32>().unwrap(), &[10]);
+    assert_eq!(values.as_slice::<u32>().unwrap(), &[4, 2, 0]);
+
+    let result = std::panic::catch_unwind(move || {
+        let mut strings = AnyArray::new::<String>();
+        strings.push(1u8);
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn job_system_test() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::{JobSystem, JobCounter, with_frame_arena, advance_frame_arenas};
+
+    let job_system = JobSystem::new(4);
+    assert_eq!(job_system.workers_num(), 4);
+    assert_eq!(job_system.current_worker(), usize::MAX);
+
+    let sum = Arc::new(AtomicUsize::new(0));
+    let counter = JobCounter::new();
+    for i in 0..100 {
+        let sum = sum.clone();
+        job_system.spawn_with_counter(&counter, move || { sum.fetch_add(i, Ordering::Relaxed); });
+    }
+    counter.wait(&job_system);
+    assert_eq!(sum.load(Ordering::Relaxed), 4950);
+
+    let handle = job_system.spawn(|| {});
+    handle.wait();
+
+    // Scoped jobs borrow from the stack, nested scopes help instead of blocking the workers
+    let mut values = [0u32; 64];
+    let total = AtomicUsize::new(0);
+    job_system.scope(|scope| {
+        for (i, value) in values.iter_mut().enumerate() {
+            let total = &total;
+            let job_system = &job_system;
+            scope.spawn(move || {
+                *value = i as u32 * 2;
+                job_system.parallel_for(0..10, 3, |j| { total.fetch_add(j, Ordering::Relaxed); });
+            });
+        }
+    });
+    assert!(values.iter().enumerate().all(|(i, value)| *value == i as u32 * 2));
+    assert_eq!(total.load(Ordering::Relaxed), 64 * 45);
+
+    let mut squares = [0usize; 100];
+    job_system.parallel_for_slice(&mut squares, 7, |i, square| *square = i * i);
+    assert!(squares.iter().enumerate().all(|(i, square)| *square == i * i));
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        job_system.scope(|scope| scope.spawn(|| panic!("job panic")));
+    }));
+    assert!(panicked.is_err());
+
+    let (first, second) = with_frame_arena(|arena| {
+        let values = arena.alloc_slice(&[1u32, 2, 3]);
+        values[1] = 5;
+        let big = arena.alloc([7u8; 100_000]);
+        (values.iter().sum::<u32>(), big[99_999] as usize + arena.allocated_size())
+    });
+    assert_eq!(first, 9);
+    assert!(second > 100_000);
+    advance_frame_arenas();
+    assert!(with_frame_arena(|arena| arena.allocated_size()) < 100_000);
+}
+
+#[cfg(not(loom))]
+#[test]
+fn queues_test() {
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+    use std::thread;
+    use crate::{MpmcQueue, spsc_ring, work_stealing_deque};
+
+    let queue = MpmcQueue::new(5);
+    assert_eq!(queue.capacity(), 8);
+    for i in 0..8 {
+        assert!(queue.try_push(i).is_ok());
+    }
+    assert_eq!(queue.try_push(8), Err(8));
+    assert_eq!(queue.try_pop(), Some(0));
+    let mut batch: VecDeque<i32> = (8..12).collect();
+    assert_eq!(queue.try_push_batch(&mut batch), 1);
+    assert_eq!(batch, [9, 10, 11]);
+    let mut popped = Array::new();
+    assert_eq!(queue.try_pop_batch(&mut popped, 100), 8);
+    assert_eq!(popped.as_slice(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(queue.is_empty());
+
+    // Blocking producers and consumers, every item is received exactly once
+    let queue = Arc::new(MpmcQueue::new(4));
+    let producers: Vec<_> = (0..4).map(|producer| {
+        let queue = queue.clone();
+        thread::spawn(move || for i in 0..1000 { queue.push(producer * 1000 + i) })
+    }).collect();
+    let consumers: Vec<_> = (0..2).map(|_| {
+        let queue = queue.clone();
+        thread::spawn(move || (0..2000).map(|_| queue.pop()).collect::<Vec<usize>>())
+    }).collect();
+    producers.into_iter().for_each(|producer| producer.join().unwrap());
+    let mut received: Vec<usize> = consumers.into_iter().flat_map(|consumer| consumer.join().unwrap()).collect();
+    received.sort();
+    assert!(received.iter().copied().eq(0..4000));
+
+    let (mut producer, mut consumer) = spsc_ring::<String>(4);
+    let mut batch: VecDeque<String> = (0..6).map(|i| i.to_string()).collect();
+    assert_eq!(producer.try_push_batch(&mut batch), 4);
+    assert!(producer.try_push("full".to_string()).is_err());
+    assert_eq!(consumer.try_pop().as_deref(), Some("0"));
+    let mut popped = Array::new();
+    assert_eq!(consumer.try_pop_batch(&mut popped, 2), 2);
+    assert_eq!(popped.as_slice(), &["1".to_string(), "2".to_string()]);
+    producer.push("4".to_string());
+
+    let receiver = thread::spawn(move || (0..1000).map(|_| consumer.pop()).collect::<Vec<String>>());
+    for i in 0..998 {
+        producer.push(format!("item {}", i));
+    }
+    let received = receiver.join().unwrap();
+    assert_eq!(received[0], "3");
+    assert_eq!(received[1], "4");
+    assert_eq!(received[999], "item 997");
+
+    // The worker pops the last pushed, stealers the first, growing keeps the items
+    let (mut worker, stealer) = work_stealing_deque::<Box<usize>>();
+    for i in 0..100 {
+        worker.push(Box::new(i));
+    }
+    assert_eq!(worker.num(), 100);
+    assert_eq!(worker.pop().as_deref(), Some(&99));
+    assert_eq!(stealer.steal().as_deref(), Some(&0));
+    assert_eq!(stealer.clone().steal().as_deref(), Some(&1));
+    while worker.pop().is_some() { }
+    assert!(stealer.is_empty() && stealer.steal().is_none());
+    // Left in the deque, dropped with it
+    worker.push(Box::new(100));
+
+    // Every item is taken exactly once while stealers race with the worker
+    let (mut worker, stealer) = work_stealing_deque::<usize>();
+    let stealers: Vec<_> = (0..3).map(|_| {
+        let stealer = stealer.clone();
+        thread::spawn(move || {
+            let mut stolen = Vec::new();
+            let mut misses = 0;
+            while misses < 1000 {
+                match stealer.steal() {
+                    Some(value) => { stolen.push(value); misses = 0; },
+                    None => { misses += 1; thread::yield_now(); },
+                }
+            }
+            stolen
+        })
+    }).collect();
+    let mut received = Vec::new();
+    for i in 0..20000 {
+        worker.push(i);
+        if i % 3 == 0 {
+            received.extend(worker.pop());
+        }
+    }
+    while let Some(value) = worker.pop() {
+        received.push(value);
+    }
+    received.extend(stealers.into_iter().flat_map(|stealer| stealer.join().unwrap()));
+    received.sort();
+    assert!(received.iter().copied().eq(0..20000));
+}
+
+#[cfg(loom)]
+#[test]
+fn queues_loom_test() {
+    use loom::sync::Arc;
+    use loom::thread;
+    use crate::{MpmcQueue, spsc_ring, work_stealing_deque};
+
+    // Without a bound the spinning threads make the explorations run for ages, LOOM_MAX_PREEMPTIONS still overrides it
+    let mut builder = loom::model::Builder::new();
+    if builder.preemption_bound.is_none() {
+        builder.preemption_bound = Some(2);
+    }
+
+    builder.check(|| {
+        let queue = Arc::new(MpmcQueue::new(2));
+        let producers: Vec<_> = (0..2).map(|i| {
+            let queue = queue.clone();
+            thread::spawn(move || while queue.try_push(i).is_err() { thread::yield_now() })
+        }).collect();
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            match queue.try_pop() {
+                Some(value) => received.push(value),
+                None => thread::yield_now(),
+            }
+        }
+        producers.into_iter().for_each(|producer| producer.join().unwrap());
+        received.sort();
+        assert_eq!(received, [0, 1]);
+    });
+
+    builder.check(|| {
+        let (mut worker, stealer) = work_stealing_deque::<usize>();
+        worker.push(0);
+        worker.push(1);
+        let thief = thread::spawn(move || stealer.steal());
+        let mut received: Vec<usize> = worker.pop().into_iter().collect();
+        received.extend(worker.pop());
+        received.extend(thief.join().unwrap());
+        received.sort();
+        assert_eq!(received, [0, 1]);
+    });
+
+    builder.check(|| {
+        let (mut producer, mut consumer) = spsc_ring::<Box<usize>>(2);
+        let sender = thread::spawn(move || {
+            for i in 0..3 {
+                producer.push(Box::new(i));
+            }
+        });
+        for i in 0..3 {
+            assert_eq!(*consumer.pop(), i);
+        }
+        sender.join().unwrap();
+    });
+}
+
+#[test]
+fn events_test() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::{Event, EventQueue};
+
+    let event = Event::<usize>::new();
+    let total = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let first = {
+        let total = total.clone();
+        event.subscribe(move |value| { total.fetch_add(*value, Ordering::Relaxed); })
+    };
+    let second = {
+        let calls = calls.clone();
+        event.subscribe(move |_| { calls.fetch_add(1, Ordering::Relaxed); })
+    };
+    assert_eq!(event.listeners_num(), 2);
+    event.broadcast(&5);
+    assert_eq!(total.load(Ordering::Relaxed), 5);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    // Dropping the subscription unsubscribes, the freed slot is reused without touching the new listener
+    drop(first);
+    assert_eq!(event.listeners_num(), 1);
+    let third = {
+        let total = total.clone();
+        event.subscribe(move |value| { total.fetch_add(*value * 10, Ordering::Relaxed); })
+    };
+    event.broadcast(&1);
+    assert_eq!(total.load(Ordering::Relaxed), 15);
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    second.unsubscribe();
+    third.detach();
+    assert_eq!(event.listeners_num(), 1);
+
+    // Deferred dispatch through a queue
+    let queue = EventQueue::new();
+    queue.add(&event);
+    event.post(2);
+    event.post(3);
+    assert_eq!(event.posted_num(), 2);
+    assert_eq!(total.load(Ordering::Relaxed), 15);
+    assert_eq!(queue.process(), 2);
+    assert_eq!(total.load(Ordering::Relaxed), 65);
+    assert_eq!(queue.process(), 0);
+
+    // Listeners can post during dispatch, posted arguments wait for the next process
+    let names = Arc::new(Event::<String>::new());
+    queue.add(&names);
+    let _echo = {
+        let weak_names = Arc::downgrade(&names);
+        names.subscribe(move |name| if name.len() < 3 {
+            weak_names.upgrade().unwrap().post(format!("{}!", name));
+        })
+    };
+    names.post("a".to_string());
+    assert_eq!(queue.process(), 1);
+    assert_eq!(names.posted_num(), 1);
+    assert_eq!(queue.process(), 1);
+    assert_eq!(queue.process(), 1);
+    assert_eq!(queue.process(), 0);
+
+    // Subscriptions outliving their event are inert
+    let orphan = event.subscribe(|_| {});
+    drop(event);
+    assert!(!orphan.is_subscribed());
+    drop(orphan);
+    assert_eq!(queue.events_num(), 2);
+    queue.process();
+    assert_eq!(queue.events_num(), 1);
+}
+
+#[test]
+fn cvars_test() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use crate::{CVars, CVarValue, CVarError};
+
+    let cvars = CVars::new();
+
+    // Set before being registered, applied on registration
+    assert_eq!(cvars.apply_command_line(["game.exe", "+r.size=64", "-verbose", "+r.name=\"main view\""]).unwrap(), 2);
+    assert_eq!(cvars.load_config_str("# Renderer\nr.shadows = off // no shadows\nr.quality high\n").unwrap(), 2);
+
+    cvars.register_int("r.size".into(), 128, 8..=512, "Cascade size in voxels");
+    cvars.register_bool("r.shadows".into(), true, "Enables shadows");
+    cvars.register_float("r.scale".into(), 1.0, 0.5..=2.0, "Resolution scale");
+    cvars.register_string("r.name".into(), "default", "View name");
+    cvars.register_enum("r.quality".into(), "low".into(), &["Low".into(), "Medium".into(), "High".into()], "Quality preset");
+    cvars.register_int("r.size".into(), 32, 8..=512, "Registered again");
+    assert_eq!(cvars.num(), 5);
+
+    assert_eq!(cvars.get_int("r.size".into()), 64);
+    assert!(!cvars.get_bool("r.shadows".into()));
+    assert_eq!(cvars.get_float("r.scale".into()), 1.0);
+    assert_eq!(cvars.get_string("r.name".into()), "main view");
+    assert_eq!(cvars.get_enum("r.quality".into()), "high".into());
+
+    // Ranges and parse errors leave the value untouched
+    assert!(matches!(cvars.set_from_str("r.size".into(), "1024"), Err(CVarError::OutOfRange { .. })));
+    assert!(matches!(cvars.set_from_str("r.scale".into(), "big"), Err(CVarError::InvalidValue { .. })));
+    assert!(matches!(cvars.set_from_str("r.unknown".into(), "1"), Err(CVarError::UnknownVariable(_))));
+    assert!(matches!(cvars.load_config_str("r.size = 16\nr.size\n"), Err(CVarError::Config { line: 2, .. })));
+    assert_eq!(cvars.get_int("r.size".into()), 16);
+
+    // Change callbacks
+    let last_size = Arc::new(AtomicI64::new(0));
+    let subscription = {
+        let last_size = last_size.clone();
+        cvars.subscribe("r.size".into(), move |value| if let CVarValue::Int(value) = value {
+            last_size.store(*value, Ordering::Relaxed);
+        })
+    };
+    cvars.set("r.size".into(), CVarValue::Int(256)).unwrap();
+    assert_eq!(last_size.load(Ordering::Relaxed), 256);
+    assert!(matches!(cvars.set("r.size".into(), CVarValue::Float(2.0)), Err(CVarError::InvalidValue { .. })));
+    assert_eq!(cvars.get_int("r.size".into()), 256);
+    drop(subscription);
+    cvars.reset("r.size".into()).unwrap();
+    assert_eq!(cvars.get_int("r.size".into()), 128);
+    assert_eq!(last_size.load(Ordering::Relaxed), 256);
+
+    // Console
+    assert_eq!(cvars.execute("r.size").unwrap(), "r.size = 128");
+    assert_eq!(cvars.execute("r.size 32").unwrap(), "r.size = 32");
+    assert_eq!(cvars.execute("r.scale=1.5").unwrap(), "r.scale = 1.5");
+    assert_eq!(cvars.execute("r.quality 1").unwrap(), "r.quality = Medium");
+    assert_eq!(cvars.execute("r.name \"second view\"").unwrap(), "r.name = \"second view\"");
+    assert_eq!(cvars.execute("reset r.scale").unwrap(), "r.scale = 1");
+    assert_eq!(cvars.execute("help r.size").unwrap(), "r.size = 32 (default 128) [8, 512] // Cascade size in voxels");
+    assert_eq!(cvars.execute("list sha").unwrap(), "r.shadows = false");
+    assert_eq!(cvars.execute("list").unwrap().lines().count(), 5);
+    assert!(cvars.execute("r.missing").is_err());
+
+    // Names too long for an atom are unknown instead of overflowing the strings table
+    let long_name = "r.".repeat(100);
+    assert!(matches!(cvars.execute(&long_name), Err(CVarError::UnknownVariable(_))));
+    assert!(matches!(cvars.execute(&format!("help {}", long_name)), Err(CVarError::UnknownVariable(_))));
+    assert!(matches!(cvars.execute(&format!("reset {}", long_name)), Err(CVarError::UnknownVariable(_))));
+    assert!(matches!(cvars.load_config_str(&format!("{} = 1", long_name)), Err(CVarError::Config { line: 1, .. })));
+    assert!(matches!(cvars.apply_command_line([format!("+{}=1", long_name)]), Err(CVarError::UnknownVariable(_))));
+}
+
+#[test]
+fn undo_test() {
+    use std::any::Any;
+    use crate::{Reflect, Snapshot, SnapshotCommand, UndoStack, UndoableCommand};
+
+    #[derive(Reflect, Clone, Debug, PartialEq)]
+    #[reflect(clone)]
+    struct Material {
+        name: String,
+        roughness: f32,
+    }
+
+    #[derive(Default)]
+    struct Document {
+        values: Array<i32>,
+        materials: Array<Material>,
+    }
+
+    struct SetValue {
+        index: usize,
+        before: i32,
+        after: i32,
+    }
+
+    impl UndoableCommand<Document> for SetValue {
+        fn get_name(&self) -> &str {
+            "Set value"
+        }
+
+        fn apply(&mut self, document: &mut Document) {
+            document.values[self.index] = self.after;
+        }
+
+        fn revert(&mut self, document: &mut Document) {
+            document.values[self.index] = self.before;
+        }
+
+        fn merge(&mut self, next: &dyn UndoableCommand<Document>) -> bool {
+            match (next as &dyn Any).downcast_ref::<SetValue>() {
+                Some(next) if next.index == self.index => {
+                    self.after = next.after;
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
+
+    let set_value = |index, before, after| -> Box<dyn UndoableCommand<Document>> { Box::new(SetValue { index, before, after }) };
+
+    let mut document = Document { values: [0, 0, 0].iter().collect(), ..Default::default() };
+    let mut stack = UndoStack::new();
+
+    // A dragged value merges in a single entry until sealed
+    for value in 1..=5 {
+        stack.execute(set_value(0, value - 1, value), &mut document);
+    }
+    assert_eq!(stack.undo_num(), 1);
+    stack.execute(set_value(1, 0, 7), &mut document);
+    assert_eq!(stack.undo_num(), 2);
+    stack.seal();
+    stack.execute(set_value(1, 7, 8), &mut document);
+    assert_eq!(stack.undo_num(), 3);
+    assert_eq!(document.values.as_slice(), &[5, 8, 0]);
+
+    assert!(stack.undo(&mut document));
+    assert!(stack.undo(&mut document));
+    assert_eq!(document.values.as_slice(), &[5, 0, 0]);
+    assert_eq!(stack.get_redo_name(), Some("Set value"));
+    assert!(stack.redo(&mut document));
+    assert_eq!(document.values.as_slice(), &[5, 7, 0]);
+    assert!(stack.undo(&mut document));
+    assert!(stack.undo(&mut document));
+    assert!(!stack.undo(&mut document));
+    assert_eq!(document.values.as_slice(), &[0, 0, 0]);
+
+    // Transactions are undone together and recording clears the redo entries
+    stack.begin_transaction("Set all");
+    stack.execute(set_value(0, 0, 1), &mut document);
+    stack.begin_transaction("Nested");
+    stack.execute(set_value(1, 0, 2), &mut document);
+    assert!(!stack.commit());
+    stack.execute(set_value(2, 0, 3), &mut document);
+    assert!(stack.commit());
+    assert_eq!(stack.redo_num(), 0);
+    assert_eq!(stack.get_undo_name(), Some("Set all"));
+    stack.undo(&mut document);
+    assert_eq!(document.values.as_slice(), &[0, 0, 0]);
+    stack.redo(&mut document);
+    assert_eq!(document.values.as_slice(), &[1, 2, 3]);
+
+    stack.begin_transaction("Cancelled");
+    stack.execute(set_value(0, 1, 10), &mut document);
+    stack.cancel(&mut document);
+    assert_eq!(document.values.as_slice(), &[1, 2, 3]);
+    assert_eq!(stack.undo_num(), 1);
+
+    // Snapshots of reflected objects, merged by key
+    document.materials.push_back(Material { name: "Stone".to_string(), roughness: 0.5 });
+    for roughness in [0.6, 0.7] {
+        let before = Snapshot::take(&document.materials[0]);
+        document.materials[0].roughness = roughness;
+        let after = Snapshot::take(&document.materials[0]);
+        let command = SnapshotCommand::new("Edit material", before, after, |document: &mut Document| &mut document.materials[0]).with_merge_key(1);
+        stack.record(Box::new(command));
+    }
+    assert_eq!(stack.undo_num(), 2);
+    stack.undo(&mut document);
+    assert_eq!(document.materials[0], Material { name: "Stone".to_string(), roughness: 0.5 });
+    stack.redo(&mut document);
+    assert_eq!(document.materials[0].roughness, 0.7);
+
+    // The memory limit drops the oldest entries but keeps the last one
+    let memory_size = stack.memory_size();
+    stack.set_memory_limit(memory_size - 1);
+    assert_eq!(stack.undo_num(), 1);
+    assert!(stack.memory_size() < memory_size);
+    stack.set_memory_limit(0);
+    assert_eq!(stack.undo_num(), 1);
+    stack.undo(&mut document);
+    assert_eq!(document.materials[0].roughness, 0.5);
+    assert_eq!(document.values.as_slice(), &[1, 2, 3]);
+}
+
+#[test]
+fn archive_test() {
+    use crate::{Archive, ArchiveError, ArchiveReader, ArchiveResult, ArchiveWriter, Serializable};
+
+    #[derive(Clone, Default, Debug, PartialEq)]
+    struct Light {
+        name: StringAtom,
+        intensity: f32,
+        tags: Array<String>,
+        radius: f32, // added in version 1
+    }
+
+    impl Serializable for Light {
+        const VERSION: u32 = 1;
+
+        fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+            ar.serialize(&mut self.name)?;
+            ar.serialize(&mut self.intensity)?;
+            ar.serialize(&mut self.tags)?;
+            if ar.get_version() >= 1 {
+                ar.serialize(&mut self.radius)?;
+            }
+            Ok(())
+        }
+
+        fn upgrade(&mut self, from_version: u32) {
+            if from_version < 1 {
+                self.radius = self.intensity * 2.0;
+            }
+        }
+    }
+
+    // Same layout as Light version 0
+    #[derive(Default)]
+    struct OldLight(Light);
+
+    impl Serializable for OldLight {
+        fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+            ar.serialize(&mut self.0.name)?;
+            ar.serialize(&mut self.0.intensity)?;
+            ar.serialize(&mut self.0.tags)
+        }
+    }
+
+    let sun = Light { name: "Sun_2".into(), intensity: 3.5, tags: ["outdoor", "main"].iter().map(|tag| tag.to_string()).collect(), radius: 10.0 };
+    let mut numbers: Map<StringAtom, Array<i32>> = Map::new();
+    numbers.insert("Odd".into(), [1, 3, 5].iter().collect());
+    numbers.insert("Even".into(), [2, 4].iter().collect());
+    #[derive(SetItem, Clone, Default)]
+    struct Id {
+        #[key]
+        value: u64,
+    }
+
+    impl Serializable for Id {
+        fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+            ar.serialize(&mut self.value)
+        }
+    }
+
+    let mut ids: Set<Id> = Set::new();
+    ids.insert(Id { value: 7 });
+    ids.insert(Id { value: 42 });
+
+    let mut writer = ArchiveWriter::new();
+    writer.serialize_versioned(&mut OldLight(Light { radius: 0.0, ..sun.clone() })).unwrap();
+    writer.chunk(*b"NEW_", |ar| ar.serialize(&mut String::from("unknown to the reader"))).unwrap();
+    writer.chunk(*b"LGHT", |ar| {
+        ar.serialize_versioned(&mut sun.clone())?;
+        ar.serialize(&mut 99u8)
+    }).unwrap();
+    writer.chunk(*b"COLL", |ar| {
+        ar.serialize(&mut numbers)?;
+        ar.serialize(&mut ids)?;
+        ar.serialize(&mut [true, false])
+    }).unwrap();
+    let mut last = usize::MAX;
+    writer.serialize(&mut last).unwrap();
+    let bytes = writer.finish();
+    assert_eq!(&bytes[..4], b"RLAR");
+
+    let mut reader = ArchiveReader::new(&bytes).unwrap();
+    let mut upgraded = Light::default();
+    reader.serialize_versioned(&mut upgraded).unwrap();
+    assert_eq!(upgraded, Light { radius: 7.0, ..sun.clone() });
+
+    // Missing chunks aren't found, unknown ones are skipped and trailing chunk data is ignored
+    assert!(!reader.chunk(*b"MISS", |_| Ok(())).unwrap());
+    let mut loaded = Light::default();
+    assert!(reader.chunk(*b"LGHT", |ar| ar.serialize_versioned(&mut loaded)).unwrap());
+    assert_eq!(loaded, sun);
+    assert_eq!(loaded.name.to_string(), "Sun_2");
+
+    let mut loaded_numbers: Map<StringAtom, Array<i32>> = Map::new();
+    let mut loaded_ids: Set<Id> = Set::new();
+    let mut flags = [false, true];
+    assert!(reader.chunk(*b"COLL", |ar| {
+        ar.serialize(&mut loaded_numbers)?;
+        ar.serialize(&mut loaded_ids)?;
+        ar.serialize(&mut flags)
+    }).unwrap());
+    assert_eq!(loaded_numbers.num(), 2);
+    assert_eq!(loaded_numbers.get(&"odd".into()).unwrap().as_slice(), &[1, 3, 5]);
+    assert_eq!(loaded_numbers.get(&"EVEN".into()).unwrap().as_slice(), &[2, 4]);
+    assert!(loaded_ids.find_first_index(&42) != usize::MAX && loaded_ids.num() == 2);
+    assert_eq!(flags, [true, false]);
+    last = 0;
+    reader.serialize(&mut last).unwrap();
+    assert_eq!(last, usize::MAX);
+    assert!(reader.is_at_end());
+    assert!(matches!(reader.serialize(&mut last), Err(ArchiveError::UnexpectedEnd)));
+
+    // Reading past the end of a chunk fails
+    let mut reader = ArchiveReader::new(&bytes).unwrap();
+    reader.serialize_versioned(&mut Light::default()).unwrap();
+    let result = reader.chunk(*b"NEW_", |ar| ar.serialize(&mut [0u64; 8]));
+    assert!(matches!(result, Err(ArchiveError::UnexpectedEnd)));
+
+    // Newer versions and corrupted data are rejected
+    let mut writer = ArchiveWriter::new();
+    writer.serialize(&mut 5u32).unwrap();
+    let newer = writer.finish();
+    let result = ArchiveReader::new(&newer).unwrap().serialize_versioned(&mut Light::default());
+    assert!(matches!(result, Err(ArchiveError::NewerVersion { version: 5, supported: 1, .. })));
+
+    // Lengths are checked before allocating
+    let mut writer = ArchiveWriter::new();
+    let mut huge_len = u32::MAX;
+    writer.serialize(&mut huge_len).unwrap();
+    let huge_string = writer.finish();
+    let result = ArchiveReader::new(&huge_string).unwrap().serialize(&mut String::new());
+    assert!(matches!(result, Err(ArchiveError::UnexpectedEnd)));
+
+    let mut corrupted = bytes.clone();
+    corrupted[10] ^= 1;
+    assert!(matches!(ArchiveReader::new(&corrupted), Err(ArchiveError::ChecksumMismatch)));
+    assert!(matches!(ArchiveReader::new(&bytes[..12]), Err(ArchiveError::UnexpectedEnd)));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_test() {
+    use serde::{Serialize, de::DeserializeOwned};
+    use crate::{CaseSensitiveAtom, KeyValuePair};
+
+    // Loaded back from JSON and from RON
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> [T; 2] {
+        [
+            serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap(),
+            ron::from_str(&ron::to_string(value).unwrap()).unwrap(),
+        ]
+    }
+
+    #[derive(SetItem, serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Tag {
+        #[key]
+        name: CaseSensitiveAtom,
+        weight: f32,
+    }
+
+    let mut scores: Map<StringAtom, Array<u32>> = Map::new();
+    scores.insert("Player_1".into(), [10, 20].iter().collect());
+    scores.insert("Player_2".into(), Array::new());
+    let mut tags: Set<Tag> = Set::new();
+    tags.insert(Tag { name: "Metal".into(), weight: 0.5 });
+    tags.insert(Tag { name: "metal".into(), weight: 1.0 });
+    let names: Array<StringAtom> = ["Sphere_12".into(), "Box".into(), StringAtom::none()].iter().collect();
+    let pair = KeyValuePair::new(StringAtom::from("Light"), 3.5f32);
+
+    // Maps are maps and atoms are strings
+    let json = serde_json::to_string(&scores).unwrap();
+    assert!(json.contains(r#""Player_1":[10,20]"#) && json.contains(r#""Player_2":[]"#));
+    assert_eq!(serde_json::to_string(&names).unwrap(), r#"["Sphere_12","Box",""]"#);
+    assert_eq!(serde_json::to_string(&pair).unwrap(), r#"["Light",3.5]"#);
+    assert_eq!(ron::to_string(&names).unwrap(), r#"["Sphere_12","Box",""]"#);
+
+    for loaded in round_trip(&scores) {
+        assert_eq!(loaded.num(), 2);
+        assert_eq!(loaded.get(&"player_1".into()), scores.get(&"Player_1".into()));
+        assert!(loaded.get(&"Player_2".into()).unwrap().is_empty());
+    }
+    for loaded in round_trip(&tags) {
+        assert_eq!(loaded.num(), 2);
+        for name in ["Metal", "metal"] {
+            let name = CaseSensitiveAtom::from(name);
+            assert_eq!(loaded[loaded.find_first_index(&name)], tags[tags.find_first_index(&name)]);
+        }
+    }
+    for loaded in round_trip(&names) {
+        assert_eq!(loaded, names);
+        assert_eq!(loaded[0].number(), Some(12));
+    }
+    for loaded in round_trip(&pair) {
+        assert_eq!(loaded.get_key(), pair.get_key());
+        assert_eq!(loaded.get_value(), pair.get_value());
+    }
+}
+
+#[test]
+fn relocatable_test() {
+    use std::mem::offset_of;
+    use crate::{ArchiveError, ArchiveResult, ArchivedArray, ArchivedMap, ArchivedStr, AlignedBuffer, Relocatable, RelocatableBuilder, RelocatableFile, Validator, access_root};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Sphere {
+        center: [f32; 3],
+        radius: f32,
+    }
+
+    unsafe impl Relocatable for Sphere {
+        const PLAIN: bool = true;
+
+        fn validate(_validator: &Validator, _pos: usize) -> ArchiveResult<()> {
+            Ok(())
+        }
+    }
+
+    #[repr(C)]
+    struct Cascade {
+        name: ArchivedStr,
+        spheres: ArchivedArray<Sphere>,
+        voxel_size: f32,
+        visible: bool,
+        _padding: [u8; 3],
+    }
+
+    unsafe impl Relocatable for Cascade {
+        fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+            validator.check::<ArchivedStr>(pos + offset_of!(Cascade, name))?;
+            validator.check::<ArchivedArray<Sphere>>(pos + offset_of!(Cascade, spheres))?;
+            validator.check::<bool>(pos + offset_of!(Cascade, visible))
+        }
+    }
+
+    #[repr(C)]
+    struct Scene {
+        cascades: ArchivedArray<Cascade>,
+        by_name: ArchivedMap<ArchivedStr, u32>,
+        by_id: ArchivedMap<u64, u32>,
+    }
+
+    unsafe impl Relocatable for Scene {
+        fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+            validator.check::<ArchivedArray<Cascade>>(pos + offset_of!(Scene, cascades))?;
+            validator.check::<ArchivedMap<ArchivedStr, u32>>(pos + offset_of!(Scene, by_name))?;
+            validator.check::<ArchivedMap<u64, u32>>(pos + offset_of!(Scene, by_id))
+        }
+    }
+
+    let names = ["Near", "Middle", "Far"];
+    let mut builder = RelocatableBuilder::new();
+    let spheres: Array<_> = (0..3).map(|cascade| {
+        let spheres: Array<Sphere> = (0..=cascade * 50).map(|i| Sphere { center: [i as f32, 0.0, -(i as f32)], radius: cascade as f32 + 1.0 }).collect();
+        builder.push_slice(&spheres)
+    }).collect();
+    let name_slots: Array<_> = names.iter().map(|name| builder.push_str(name)).collect();
+    let cascades = builder.push_array((0..3).map(|cascade| Cascade {
+        name: ArchivedStr::empty(), spheres: ArchivedArray::empty(), voxel_size: (1 << cascade) as f32, visible: cascade != 1, _padding: [0; 3]
+    }));
+    for cascade in 0..3 {
+        builder.resolve(cascades.get_slot(cascade), offset_of!(Cascade, name), &name_slots[cascade]);
+        builder.resolve(cascades.get_slot(cascade), offset_of!(Cascade, spheres), &spheres[cascade]);
+    }
+
+    // String keys are resolved before the map is built from them
+    let keys = builder.push_array(names.iter().map(|_| ArchivedStr::empty()));
+    for (index, name) in name_slots.iter().enumerate() {
+        builder.resolve(keys.get_slot(index), 0, name);
+    }
+    let values = builder.push_slice(&[0u32, 1, 2]);
+    let by_name = builder.push_map(&keys, &values);
+    let ids: Array<u64> = (0..100).map(|i| i * 7919).collect();
+    let indices: Array<u32> = (0..100).collect();
+    let (ids, indices) = (builder.push_slice(&ids), builder.push_slice(&indices));
+    let by_id = builder.push_map(&ids, &indices);
+
+    let scene = builder.push(Scene { cascades: ArchivedArray::empty(), by_name: ArchivedMap::empty(), by_id: ArchivedMap::empty() });
+    builder.resolve(scene, offset_of!(Scene, cascades), &cascades);
+    builder.resolve(scene, offset_of!(Scene, by_name), &by_name);
+    builder.resolve(scene, offset_of!(Scene, by_id), &by_id);
+    let bytes = builder.finish(scene);
+
+    let check_scene = |scene: &Scene| {
+        assert_eq!(scene.cascades.num(), 3);
+        for (index, cascade) in scene.cascades.iter().enumerate() {
+            assert_eq!(cascade.name.as_str(), names[index]);
+            assert_eq!(cascade.spheres.num(), index * 50 + 1);
+            assert_eq!(cascade.spheres[10.min(cascade.spheres.num() - 1)].center[2], -(10.min(index * 50) as f32));
+            assert_eq!(cascade.voxel_size, (1 << index) as f32);
+            assert_eq!(cascade.visible, index != 1);
+        }
+        assert_eq!(scene.by_name.num(), 3);
+        assert_eq!(scene.by_name.get("Far"), Some(&2));
+        assert_eq!(scene.by_name.get("far"), None);
+        assert!(scene.by_name.contains_key("Near"));
+        assert_eq!(scene.by_id.get(&(42 * 7919)), Some(&42));
+        assert_eq!(scene.by_id.get(&5), None);
+        assert!(scene.by_id.iter().all(|(id, &index)| *id == index as u64 * 7919));
+    };
+    check_scene(access_root::<Scene>(&bytes).unwrap());
+
+    // Used in place wherever the bytes are
+    let moved = AlignedBuffer::from_slice(&bytes);
+    check_scene(access_root::<Scene>(&moved).unwrap());
+
+    let path = std::env::temp_dir().join(format!("rl_core_relocatable_test_{}.bin", std::process::id()));
+    bytes.save_to_file(&path).unwrap();
+    check_scene(access_root::<Scene>(&AlignedBuffer::read_file(&path).unwrap()).unwrap());
+    {
+        let file = RelocatableFile::<Scene>::open(&path).unwrap();
+        check_scene(&file);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // Untrusted data
+    let root_pos = scene.get_pos();
+    let corrupted = |pos: usize, value: &[u8]| {
+        let mut corrupted = AlignedBuffer::from_slice(&bytes);
+        corrupted[pos..pos + value.len()].copy_from_slice(value);
+        access_root::<Scene>(&corrupted).map(|_| ())
+    };
+    assert!(matches!(access_root::<Scene>(&bytes[..bytes.len() - 8]), Err(ArchiveError::UnexpectedEnd)));
+    assert!(matches!(access_root::<Scene>(&bytes[..8]), Err(ArchiveError::UnexpectedEnd)));
+    assert!(matches!(corrupted(0, b"NOPE"), Err(ArchiveError::InvalidData(_))));
+    assert!(matches!(corrupted(8, &(bytes.len() as u64).to_ne_bytes()), Err(ArchiveError::UnexpectedEnd)));
+    assert!(matches!(corrupted(8, &(root_pos as u64 - 4).to_ne_bytes()), Err(ArchiveError::InvalidData("misaligned value"))));
+    assert!(matches!(corrupted(root_pos, &(-(root_pos as i64) - 16).to_ne_bytes()), Err(ArchiveError::UnexpectedEnd)));
+    assert!(matches!(corrupted(root_pos + 8, &u64::MAX.to_ne_bytes()), Err(ArchiveError::UnexpectedEnd)));
+
+    let first_cascade = cascades.get_slot(0).get_pos();
+    assert!(matches!(corrupted(first_cascade + offset_of!(Cascade, visible), &[2]), Err(ArchiveError::InvalidData("invalid bool"))));
+    let near_pos = name_slots[0].get_pos();
+    assert!(matches!(corrupted(near_pos, &[0xff]), Err(ArchiveError::InvalidData("invalid UTF-8 string"))));
+    let by_id_buckets = root_pos + offset_of!(Scene, by_id) + 2 * size_of::<ArchivedArray<u32>>();
+    assert!(matches!(corrupted(by_id_buckets + 8, &2u64.to_ne_bytes()), Err(ArchiveError::InvalidData("invalid map table"))));
+}
+
+#[test]
+fn strings_test() {
+    use std::fmt::Write;
+    use crate::{InlineString, RString};
+
+    let mut path = RString::new();
+    let (folder, file, extension) = ("textures", "stone", "png");
+    write!(path, "assets/{}/{}.{}", folder, file, extension).unwrap();
+    assert_eq!(path, "assets/textures/stone.png");
+    path.insert_str(0, "../");
+    path.insert(path.len() - 4, '_');
+    path.push('é');
+    assert_eq!(path.as_str(), "../assets/textures/stone_.pngé");
+    assert_eq!(path.pop(), Some('é'));
+    path.truncate(path.len() - 4);
+    assert!(path.push_utf8(&[0xe2, 0x82, 0xac]).is_ok());
+    assert!(path.push_utf8(&[0xe2, 0x82]).is_err());
+    assert_eq!(path.as_str(), "../assets/textures/stone_€");
+    assert!(std::panic::catch_unwind(|| RString::<DefaultAllocator>::from("€").insert(1, 'a')).is_err());
+
+    let mut name: InlineString<8> = InlineString::from("Rock");
+    assert!(name.is_inline());
+    name.push_str("_02");
+    assert_eq!(name.len(), 7);
+    assert!(name.is_inline());
+    let moved = [name.clone(), name];
+    assert_eq!(moved[1], "Rock_02");
+    let mut name = moved[1].clone();
+    name.insert_str(4, "Large");
+    assert!(!name.is_inline());
+    assert_eq!(name, "RockLarge_02");
+    name.truncate(4);
+    assert_eq!(name.pop(), Some('k'));
+    assert_eq!(format!("{}", name), "Roc");
+
+    // Same keys as the strings they hold
+    let mut sizes: Map<InlineString<16>, u32> = Map::new();
+    sizes.insert("rock".into(), 4);
+    sizes.insert(InlineString::from("a much longer name"), 18);
+    assert_eq!(sizes.get("rock"), Some(&4));
+    assert_eq!(sizes.get("a much longer name"), Some(&18));
+    let mut files: Map<RString, usize> = Map::new();
+    files.insert(path.clone(), 1);
+    assert!(files.contains("../assets/textures/stone_€"));
+
+    assert_eq!(path.to_atom(), StringAtom::from("../Assets/textures/stone_€"));
+    assert_eq!(path.try_to_atom(), Some(path.to_atom()));
+    let long_path = RString::<DefaultAllocator>::from("a/".repeat(100).as_str());
+    assert_eq!(long_path.try_to_atom(), None);
+    assert!(std::panic::catch_unwind(|| long_path.to_atom()).is_err());
+    assert!(std::panic::catch_unwind(|| StringAtom::from(&long_path)).is_err());
+    assert_eq!(StringAtom::from(&moved[0]).to_string(), "Rock_02");
+}
+
+#[test]
+fn vfs_test() {
+    use std::sync::Arc;
+    use crate::{PakCompression, PakFile, PakWriter, Vfs, VfsError, VfsSource};
+
+    let root = std::env::temp_dir().join(format!("rl_core_vfs_test_{}", std::process::id()));
+    let write = |path: &str, text: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    };
+    write("base/shaders/common.glsl", "// base common");
+    write("base/shaders/sdf/sphere.glsl", "// base sphere");
+    write("patch/shaders/sdf/sphere.glsl", &"// patched sphere\n".repeat(100));
+    write("patch/shaders/extra.glsl", "// extra");
+
+    // Compressions that aren't enabled are refused, not silently stored
+    let pak_path = root.join("patch.pak");
+    let mut pak = PakWriter::create(&pak_path, 64).unwrap();
+    pak.add_file("probe", b"probe", PakCompression::None).unwrap();
+    let mut compression = PakCompression::None;
+    for candidate in [PakCompression::Zstd, PakCompression::Lz4] {
+        match pak.add_file("probe", b"probe", candidate) {
+            Ok(()) => compression = candidate,
+            Err(error) => assert!(matches!(error, VfsError::UnsupportedCompression(_))),
+        }
+    }
+    assert_eq!(pak.add_directory(root.join("patch/shaders"), compression).unwrap(), 2);
+    assert!(matches!(pak.add_file("../outside", b"", PakCompression::None), Err(VfsError::InvalidPath(_))));
+    pak.finish().unwrap();
+
+    let pak = PakFile::open(&pak_path).unwrap();
+    assert_eq!(pak.files_num(), 3);
+    let sphere = pak.get_entry("sdf/sphere.glsl").unwrap();
+    assert_eq!(sphere.get_offset() % 64, 0);
+    assert_eq!(sphere.get_compression(), compression);
+    assert_eq!(sphere.get_uncompressed_size(), 1800);
+    // Case sensitive, like the directory it was packed from
+    assert!(!pak.contains("SDF/Sphere.glsl"));
+
+    let vfs = Vfs::new();
+    vfs.mount_directory("shaders", root.join("base/shaders"), 0).unwrap();
+    let patch = vfs.mount("shaders", Arc::new(pak), 10);
+    vfs.mount_directory("scenes", root.join("base"), 0).unwrap();
+    assert_eq!(vfs.mounts_num(), 3);
+
+    assert_eq!(vfs.read_to_string("shaders:/common.glsl").unwrap(), "// base common");
+    assert!(vfs.read_to_string("shaders:/sdf/sphere.glsl").unwrap().starts_with("// patched sphere"));
+    assert_eq!(vfs.read_to_string("shaders:/./sdf\\sphere.glsl").unwrap().len(), 1800);
+    assert!(vfs.exists("shaders:/extra.glsl"));
+    assert!(vfs.exists("scenes:/shaders/common.glsl"));
+    assert!(!vfs.exists("scenes:/extra.glsl"));
+    let files: Array<String> = vfs.list("shaders").iter().map(|file| file.to_string()).collect();
+    assert_eq!(files.as_slice(), ["common.glsl", "extra.glsl", "probe", "sdf/sphere.glsl"]);
+    assert!(vfs.list(&"shaders".repeat(20)).is_empty());
+
+    assert!(matches!(vfs.read("shaders:/missing.glsl"), Err(VfsError::NotFound(_))));
+    assert!(matches!(vfs.read("models:/rock.mesh"), Err(VfsError::UnknownMount(_))));
+    assert!(matches!(vfs.read("shaders:/../secret"), Err(VfsError::InvalidPath(_))));
+    assert!(matches!(vfs.read("no_mount.glsl"), Err(VfsError::InvalidPath(_))));
+
+    // Unmounting the patch uncovers the base file
+    assert!(vfs.unmount(patch));
+    assert!(!vfs.unmount(patch));
+    assert_eq!(vfs.read_to_string("shaders:/sdf/sphere.glsl").unwrap(), "// base sphere");
+    assert!(!vfs.exists("shaders:/extra.glsl"));
+
+    // Corrupted data is detected when read
+    let mut bytes = std::fs::read(&pak_path).unwrap();
+    let extra = PakFile::open(&pak_path).unwrap().get_entry("extra.glsl").unwrap().get_offset() as usize;
+    bytes[extra] ^= 1;
+    std::fs::write(&pak_path, &bytes).unwrap();
+    let corrupted = PakFile::open(&pak_path).unwrap();
+    assert!(matches!(corrupted.read("extra.glsl"), Err(VfsError::Corrupted(_))));
+    let len = bytes.len();
+    bytes[len - 1] ^= 1;
+    std::fs::write(&pak_path, &bytes).unwrap();
+    assert!(matches!(PakFile::open(&pak_path), Err(VfsError::Archive(_))));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn assets_test() {
+    use crate::{AssetError, AssetLoader, AssetManager, JobSystem, LoadContext, LoadState, RString, Vfs};
+
+    struct Text {
+        text: RString,
+        includes: Array<crate::AssetHandle<Text>>,
+    }
+
+    // "#include <path>" lines are dependencies, "#panic" panics
+    struct TextLoader;
+
+    impl AssetLoader for TextLoader {
+        type Asset = Text;
+
+        fn load(&self, data: &[u8], context: &mut LoadContext) -> Result<Text, AssetError> {
+            let text = std::str::from_utf8(data).map_err(|error| AssetError::Load(error.to_string()))?;
+            assert!(!text.starts_with("#panic"));
+            let includes = text.lines()
+                .filter_map(|line| line.strip_prefix("#include "))
+                .map(|path| context.load_dependency::<Text>(path))
+                .collect();
+            Ok(Text { text: RString::from(text), includes })
+        }
+    }
+
+    let root = std::env::temp_dir().join(format!("rl_core_assets_test_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("common.txt"), "common").unwrap();
+    std::fs::write(root.join("main.txt"), "#include data:/common.txt\nmain").unwrap();
+    std::fs::write(root.join("broken.txt"), "#include data:/missing.txt").unwrap();
+    std::fs::write(root.join("panic.txt"), "#panic").unwrap();
+
+    let vfs: &'static Vfs = Box::leak(Box::new(Vfs::new()));
+    vfs.mount_directory("data", &root, 0).unwrap();
+    let assets = AssetManager::new(vfs, JobSystem::global());
+
+    let no_loader = assets.load::<Text>("data:/main.txt");
+    assert!(matches!(no_loader.wait(), LoadState::Failed(AssetError::NoLoader(_))));
+    drop(no_loader);
+    assets.register_loader(TextLoader);
+
+    // Same path and type share the asset, the dependency is kept alive by its dependent
+    let main = assets.load::<Text>("data:/main.txt");
+    assert_eq!(main, assets.load::<Text>("DATA:/Main.txt"));
+    assert!(matches!(main.wait(), LoadState::Loaded));
+    let common = assets.find::<Text>("data:/common.txt").unwrap();
+    assert!(assets.find::<Text>(&format!("data:/{}", "a".repeat(200))).is_none());
+    common.wait();
+    assert!(matches!(main.get_state_with_dependencies(), LoadState::Loaded));
+    assert_eq!(main.get().unwrap().text, "#include data:/common.txt\nmain");
+    assert_eq!(main.get().unwrap().includes[0], common);
+    assert_eq!(common.get_dependents()[0], main.untyped());
+    assert!(main.untyped().typed::<RString>().is_none());
+    assert_eq!(main.untyped().typed::<Text>().unwrap(), main);
+    assert_eq!(assets.assets_num(), 2);
+
+    let broken = assets.load::<Text>("data:/broken.txt");
+    assert!(matches!(broken.wait(), LoadState::Loaded));
+    broken.get_dependencies()[0].typed::<Text>().unwrap().wait();
+    assert!(matches!(broken.get_state_with_dependencies(), LoadState::Failed(AssetError::Dependency { .. })));
+    assert!(matches!(assets.load::<Text>("data:/panic.txt").wait(), LoadState::Failed(AssetError::Panicked)));
+    assert!(matches!(assets.load::<Text>("no_mount").get_state(), LoadState::Failed(AssetError::InvalidPath(_))));
+
+    // Reloads go from the changed file to its dependents, a failed reload keeps the last good asset
+    std::fs::write(root.join("common.txt"), "common 2").unwrap();
+    assert_eq!(assets.reload("data:/common.txt"), 1);
+    assert!(matches!(common.wait(), LoadState::Loaded));
+    main.wait();
+    assert_eq!(common.get().unwrap().text, "common 2");
+    assert_eq!((common.get_version(), main.get_version()), (2, 2));
+    std::fs::write(root.join("main.txt"), "#panic").unwrap();
+    assert_eq!(assets.reload("data:/main.txt"), 1);
+    assert!(matches!(main.wait(), LoadState::Loaded));
+    assert!(matches!(main.get_reload_error(), Some(AssetError::Panicked)));
+    assert_eq!(main.get_version(), 2);
+    assert_eq!(main.get().unwrap().includes[0], common);
+
+    // Unloaded with the last handle
+    drop(broken);
+    drop(common);
+    assert_eq!(assets.assets_num(), 2);
+    drop(main);
+    assert_eq!(assets.assets_num(), 0);
+    assert!(assets.find::<Text>("data:/common.txt").is_none());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(all(feature = "hot-reload", target_os = "linux"))]
+#[test]
+fn hot_reload_test() {
+    use std::time::{Duration, Instant};
+    use crate::{AssetError, AssetLoader, AssetManager, HotReloader, JobSystem, LoadContext, RString, Vfs};
+
+    struct TextLoader;
+
+    impl AssetLoader for TextLoader {
+        type Asset = RString;
+
+        fn load(&self, data: &[u8], _context: &mut LoadContext) -> Result<RString, AssetError> {
+            let mut text = RString::new();
+            text.push_utf8(data).map_err(|error| AssetError::Load(error.to_string()))?;
+            Ok(text)
+        }
+    }
+
+    let root = std::env::temp_dir().join(format!("rl_core_hot_reload_test_{}", std::process::id()));
+    std::fs::create_dir_all(root.join("sdf")).unwrap();
+    std::fs::write(root.join("sdf/sphere.glsl"), "v1").unwrap();
+
+    let vfs: &'static Vfs = Box::leak(Box::new(Vfs::new()));
+    vfs.mount_directory("shaders", &root, 0).unwrap();
+    let assets = AssetManager::new(vfs, JobSystem::global());
+    assets.register_loader(TextLoader);
+    let sphere = assets.load::<RString>("shaders:/sdf/sphere.glsl");
+    sphere.wait();
+
+    let mut reloader = HotReloader::new(Duration::from_millis(50)).unwrap();
+    reloader.watch("shaders", &root).unwrap();
+    assert_eq!(reloader.watch(&"shaders".repeat(20), &root).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(reloader.directories_num(), 2);
+
+    // Written twice, reloaded once after the debounce delay
+    std::fs::write(root.join("sdf/sphere.glsl"), "v2").unwrap();
+    std::fs::write(root.join("sdf/sphere.glsl"), "v3").unwrap();
+    let start = Instant::now();
+    let mut reloaded = reloader.update(&assets).unwrap();
+    assert!(reloaded.is_empty());
+    while reloaded.is_empty() && start.elapsed() < Duration::from_secs(5) {
+        std::thread::sleep(Duration::from_millis(10));
+        reloaded = reloader.update(&assets).unwrap();
+    }
+    assert_eq!(reloaded.as_slice(), &[StringAtom::from("shaders:/sdf/sphere.glsl")]);
+    sphere.wait();
+    assert_eq!(*sphere.get().unwrap(), "v3");
+    assert_eq!(sphere.get_version(), 2);
+    assert_eq!(reloader.changes_num(), 0);
+
+    // Subdirectories created after watch are watched too
+    std::fs::create_dir(root.join("new")).unwrap();
+    reloader.update(&assets).unwrap();
+    assert_eq!(reloader.directories_num(), 3);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn profiler_test() {
+    use crate::{ProfileScope, Profiler};
+
+    let profiler = Profiler::global();
+    let frame = |outer_calls: usize| {
+        for _ in 0..outer_calls {
+            let _outer = ProfileScope::new(StringAtom::from("Cull \"cascade\""));
+            let _inner = ProfileScope::new(StringAtom::from("Build chunk"));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        crate::profile_scope!("Compiled out without the feature");
+    };
+    std::thread::Builder::new().name("Profiled".to_string()).spawn(move || frame(2)).unwrap().join().unwrap();
+    profiler.end_frame();
+    frame(1);
+    profiler.set_recording(false);
+    frame(5);
+    profiler.set_recording(true);
+    profiler.end_frame();
+    assert_eq!(profiler.frames_num(), 2);
+    assert!(profiler.get_thread_names().iter().any(|name| name == "Profiled"));
+
+    let stats = profiler.get_stats();
+    let cull = stats.iter().find(|scope| scope.name == StringAtom::from("Cull \"cascade\"")).unwrap();
+    assert_eq!(cull.calls, 3);
+    assert!(cull.min <= cull.avg && cull.avg <= cull.max && cull.min >= std::time::Duration::from_millis(1));
+    assert_eq!(stats[0].name, cull.name);
+    assert_eq!(stats.iter().any(|scope| scope.name == StringAtom::from("Compiled out without the feature")), cfg!(feature = "profiler"));
+
+    let mut json = Vec::new();
+    profiler.write_chrome_trace(&mut json).unwrap();
+    let trace: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    let events = trace["traceEvents"].as_array().unwrap();
+    let inner: Vec<_> = events.iter().filter(|event| event["name"] == "Build chunk").collect();
+    assert_eq!(inner.len(), 3);
+    assert!(inner.iter().all(|event| event["ph"] == "X" && event["dur"].as_f64().unwrap() >= 1000.0));
+    assert!(events.iter().any(|event| event["name"] == "Cull \"cascade\""));
+    assert!(events.iter().any(|event| event["name"] == "Frame 1"));
+}
@@ -1,27 +1,144 @@
+use core::fmt;
 use std::any::{Any, TypeId, type_name};
 use std::alloc::Layout;
+use std::sync::{OnceLock, RwLock};
+
+use crate::{Array, Map, StringAtom, CaseSensitiveAtom};
 
 type PruneFunction = fn(*mut u8);
+type CloneFunction = fn(*const u8, *mut u8);
+type DefaultFunction = fn(*mut u8);
+type DebugFunction = fn(*const u8, &mut fmt::Formatter<'_>) -> fmt::Result;
+type TypeInfoFunction = fn() -> &'static TypeInfo;
+
+pub struct FieldInfo {
+    name: StringAtom,
+    offset: usize,
+    size: usize,
+    type_info_fn: TypeInfoFunction, // resolved lazily so types can refer to each other
+    owner: Option<TypeId>, // type the field was added to by TypeInfo::with_field
+}
+
+impl FieldInfo {
+    /// # Safety
+    /// `offset` must be the offset of a field of type `T` in the type whose TypeInfo gets this field,
+    /// the typed accessors read a `T` there.
+    #[inline]
+    pub unsafe fn new<T: Reflect>(name: StringAtom, offset: usize) -> Self {
+        FieldInfo { name, offset, size: std::mem::size_of::<T>(), type_info_fn: T::type_info, owner: None }
+    }
+
+    #[inline]
+    pub fn get_name(&self) -> StringAtom {
+        self.name
+    }
+
+    #[inline]
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    #[inline]
+    pub fn get_type_info(&self) -> &'static TypeInfo {
+        (self.type_info_fn)()
+    }
+
+    #[inline]
+    pub fn get_ptr(&self, object: *const u8) -> *const u8 {
+        object.wrapping_add(self.offset)
+    }
+
+    #[inline]
+    pub fn get_ptr_mut(&self, object: *mut u8) -> *mut u8 {
+        object.wrapping_add(self.offset)
+    }
+
+    #[inline]
+    fn is_field_of(&self, object: &dyn Reflected) -> bool {
+        self.owner == Some(*object.get_type_info().get_id())
+    }
+
+    // Typed access, None if the object doesn't have this field or F is not the field type
+    pub fn get<'a, F: Any>(&self, object: &'a dyn Reflected) -> Option<&'a F> {
+        if !self.is_field_of(object) || *self.get_type_info().get_id() != TypeId::of::<F>() {
+            return None;
+        }
+        let object_ptr = (object as *const dyn Reflected).cast::<u8>();
+        Some(unsafe{ &*self.get_ptr(object_ptr).cast::<F>() })
+    }
+
+    pub fn get_mut<'a, F: Any>(&self, object: &'a mut dyn Reflected) -> Option<&'a mut F> {
+        if !self.is_field_of(object) || *self.get_type_info().get_id() != TypeId::of::<F>() {
+            return None;
+        }
+        let object_ptr = (object as *mut dyn Reflected).cast::<u8>();
+        Some(unsafe{ &mut *self.get_ptr_mut(object_ptr).cast::<F>() })
+    }
+}
 
 pub struct TypeInfo {
     id: TypeId,
     name: &'static str,
     layout: Layout,
     prune_fn: PruneFunction,
+    clone_fn: Option<CloneFunction>,
+    default_fn: Option<DefaultFunction>,
+    debug_fn: Option<DebugFunction>,
+    fields: Array<FieldInfo>,
 }
 
 impl TypeInfo {
-    pub/* const*/ fn of<T: Any>() -> TypeInfo { // Use T: Object to get functions
+    pub/* const*/ fn of<T: Any>() -> TypeInfo {
         TypeInfo {
             id: TypeId::of::<T>(),
             name: type_name::<T>(),
             layout: Layout::new::<T>(),
             prune_fn: |ptr: *mut u8| unsafe {
                 std::ptr::drop_in_place(ptr.cast::<T>())
-            }
+            },
+            clone_fn: None,
+            default_fn: None,
+            debug_fn: None,
+            fields: Array::new(),
         }
     }
 
+    #[inline]
+    pub fn with_clone<T: Any + Clone>(mut self) -> Self {
+        assert!(self.id == TypeId::of::<T>(), "TypeInfo of {} built with {}", self.name, type_name::<T>());
+        self.clone_fn = Some(|src: *const u8, dst: *mut u8| unsafe {
+            std::ptr::write(dst.cast::<T>(), (*src.cast::<T>()).clone())
+        });
+        self
+    }
+
+    #[inline]
+    pub fn with_default<T: Any + Default>(mut self) -> Self {
+        assert!(self.id == TypeId::of::<T>(), "TypeInfo of {} built with {}", self.name, type_name::<T>());
+        self.default_fn = Some(|dst: *mut u8| unsafe {
+            std::ptr::write(dst.cast::<T>(), T::default())
+        });
+        self
+    }
+
+    #[inline]
+    pub fn with_debug<T: Any + fmt::Debug>(mut self) -> Self {
+        assert!(self.id == TypeId::of::<T>(), "TypeInfo of {} built with {}", self.name, type_name::<T>());
+        self.debug_fn = Some(|ptr: *const u8, f: &mut fmt::Formatter<'_>| unsafe {
+            fmt::Debug::fmt(&*ptr.cast::<T>(), f)
+        });
+        self
+    }
+
+    #[inline]
+    pub fn with_field(mut self, mut field: FieldInfo) -> Self {
+        assert!(field.offset.checked_add(field.size).is_some_and(|end| end <= self.layout.size()),
+            "Field {} of {} is out of the type", field.name, self.name);
+        field.owner = Some(self.id);
+        self.fields.push_back(field);
+        self
+    }
+
     pub fn get_id(&self) -> &TypeId {
         &self.id
     }
@@ -34,7 +151,210 @@ impl TypeInfo {
         &self.layout
     }
 
-    pub fn drop_in_place(&self, ptr: *mut u8) {
+    pub fn get_fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    pub fn find_field(&self, name: StringAtom) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// # Safety
+    /// `ptr` must point to a valid value of this type, which must not be used afterwards.
+    pub unsafe fn drop_in_place(&self, ptr: *mut u8) {
         (self.prune_fn)(ptr);
     }
+
+    #[inline]
+    pub fn can_clone(&self) -> bool {
+        self.clone_fn.is_some()
+    }
+
+    #[inline]
+    pub fn can_default(&self) -> bool {
+        self.default_fn.is_some()
+    }
+
+    /// Writes a clone of src in the uninitialized dst, false if the type is not cloneable.
+    ///
+    /// # Safety
+    /// `src` must point to a valid, aligned value of this type and `dst` be valid for an aligned write of it.
+    /// Whatever dst held is overwritten without being dropped.
+    pub unsafe fn clone_into(&self, src: *const u8, dst: *mut u8) -> bool {
+        match self.clone_fn {
+            Some(clone_fn) => { clone_fn(src, dst); true },
+            None => false,
+        }
+    }
+
+    /// Writes the default value in the uninitialized dst, false if the type has no default.
+    ///
+    /// # Safety
+    /// `dst` must be valid for an aligned write of this type, whatever it held is overwritten without being dropped.
+    pub unsafe fn default_into(&self, dst: *mut u8) -> bool {
+        match self.default_fn {
+            Some(default_fn) => { default_fn(dst); true },
+            None => false,
+        }
+    }
+
+    /// Formats with the type Debug, or its reflected fields when it has none.
+    ///
+    /// # Safety
+    /// `ptr` must point to a valid, aligned value of this type.
+    pub unsafe fn debug_fmt(&self, ptr: *const u8, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.debug_fn {
+            Some(debug_fn) => debug_fn(ptr, f),
+            None => {
+                let mut debug_struct = f.debug_struct(self.name);
+                for field in &self.fields {
+                    let field_name = field.name.to_string();
+                    let field_value = DebugPtr(field.get_type_info(), field.get_ptr(ptr));
+                    debug_struct.field(&field_name, &field_value);
+                }
+                debug_struct.finish()
+            }
+        }
+    }
+}
+
+pub(crate) struct DebugPtr(pub(crate) &'static TypeInfo, pub(crate) *const u8);
+
+impl fmt::Debug for DebugPtr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        unsafe{ self.0.debug_fmt(self.1, f) }
+    }
+}
+
+// Global registry, every TypeInfo is built once and lives until the end of the program
+struct TypeRegistry {
+    types: RwLock<Map<TypeId, &'static TypeInfo>>,
+}
+
+static TYPE_REGISTRY: OnceLock<TypeRegistry> = OnceLock::new();
+
+impl TypeRegistry {
+    #[inline]
+    fn get() -> &'static TypeRegistry {
+        TYPE_REGISTRY.get_or_init(|| TypeRegistry { types: RwLock::new(Map::new()) })
+    }
+}
+
+pub fn register_type_with<T: Any, F>(build_fn: F) -> &'static TypeInfo where
+    F: FnOnce() -> TypeInfo
+{
+    let registry = TypeRegistry::get();
+    let type_id = TypeId::of::<T>();
+
+    if let Some(type_info) = registry.types.read().unwrap().get(&type_id) {
+        return type_info;
+    }
+
+    // Build outside of the lock, fields could register other types
+    let new_type_info = build_fn();
+    assert!(*new_type_info.get_id() == type_id, "TypeInfo of {} registered for {}", new_type_info.get_name(), type_name::<T>());
+
+    let mut types = registry.types.write().unwrap();
+    if let Some(type_info) = types.get(&type_id) {
+        return type_info;
+    }
+    let type_info: &'static TypeInfo = Box::leak(Box::new(new_type_info));
+    types.insert(type_id, type_info);
+    type_info
+}
+
+pub fn find_type(type_id: &TypeId) -> Option<&'static TypeInfo> {
+    TypeRegistry::get().types.read().unwrap().get(type_id).copied()
+}
+
+pub fn registered_types_num() -> usize {
+    TypeRegistry::get().types.read().unwrap().num()
+}
+
+/// # Safety
+/// `type_info` must return the TypeInfo of `Self`, built from `TypeInfo::of::<Self>()`:
+/// reflected objects are cloned, dropped and accessed through it.
+/// Implement it with `#[derive(Reflect)]` rather than by hand.
+pub unsafe trait Reflect : Any {
+    fn type_info() -> &'static TypeInfo;
+}
+
+/// Object safe side of Reflect, implemented for every Reflect type
+///
+/// # Safety
+/// Same as Reflect, `get_type_info` must return the TypeInfo of `Self`.
+pub unsafe trait Reflected : Any {
+    fn get_type_info(&self) -> &'static TypeInfo;
+}
+
+unsafe impl<T: Reflect> Reflected for T {
+    #[inline]
+    fn get_type_info(&self) -> &'static TypeInfo {
+        T::type_info()
+    }
+}
+
+impl dyn Reflected {
+    #[inline]
+    pub fn find_field(&self, name: StringAtom) -> Option<&FieldInfo> {
+        self.get_type_info().find_field(name)
+    }
+
+    #[inline]
+    pub fn field<F: Any>(&self, name: StringAtom) -> Option<&F> {
+        self.get_type_info().find_field(name)?.get::<F>(self)
+    }
+
+    #[inline]
+    pub fn field_mut<F: Any>(&mut self, name: StringAtom) -> Option<&mut F> {
+        self.get_type_info().find_field(name)?.get_mut::<F>(self)
+    }
+}
+
+impl fmt::Debug for dyn Reflected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ptr = (self as *const dyn Reflected).cast::<u8>();
+        unsafe{ self.get_type_info().debug_fmt(ptr, f) }
+    }
+}
+
+macro_rules! impl_reflect_value {
+    ($($t:ty),*) => {
+        $(
+            unsafe impl Reflect for $t {
+                fn type_info() -> &'static TypeInfo {
+                    register_type_with::<$t, _>(|| {
+                        TypeInfo::of::<$t>()
+                            .with_clone::<$t>()
+                            .with_default::<$t>()
+                            .with_debug::<$t>()
+                    })
+                }
+            }
+        )*
+    }
+}
+
+impl_reflect_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, bool, char, String);
+
+unsafe impl Reflect for StringAtom {
+    fn type_info() -> &'static TypeInfo {
+        register_type_with::<StringAtom, _>(|| {
+            TypeInfo::of::<StringAtom>()
+                .with_clone::<StringAtom>()
+                .with_default::<StringAtom>()
+                .with_debug::<StringAtom>()
+        })
+    }
+}
+
+unsafe impl Reflect for CaseSensitiveAtom {
+    fn type_info() -> &'static TypeInfo {
+        register_type_with::<CaseSensitiveAtom, _>(|| {
+            TypeInfo::of::<CaseSensitiveAtom>()
+                .with_clone::<CaseSensitiveAtom>()
+                .with_default::<CaseSensitiveAtom>()
+                .with_debug::<CaseSensitiveAtom>()
+        })
+    }
 }
@@ -0,0 +1,370 @@
+use std::any::Any;
+use std::mem;
+use std::ptr;
+
+use crate::{AnyArray, Array, Reflected, TypeInfo};
+
+/// Reversible edit of a T. Commands are recorded once already applied, `apply` is only used by redo.
+pub trait UndoableCommand<T: ?Sized>: Any {
+    fn get_name(&self) -> &str;
+
+    fn apply(&mut self, target: &mut T);
+
+    fn revert(&mut self, target: &mut T);
+
+    // Approximate size kept alive by the command, used for the history memory limit
+    fn memory_size(&self) -> usize {
+        mem::size_of_val(self)
+    }
+
+    // Absorbs the next command of the same continuous edit (a dragged slider records one command per frame).
+    // Only called with the command recorded right after this one.
+    fn merge(&mut self, _next: &dyn UndoableCommand<T>) -> bool {
+        false
+    }
+}
+
+struct Transaction<T: ?Sized> {
+    name: String,
+    commands: Array<Box<dyn UndoableCommand<T>>>,
+    memory_size: usize,
+}
+
+impl<T: ?Sized + 'static> Transaction<T> {
+    fn new(name: &str) -> Self {
+        Transaction { name: name.to_string(), commands: Array::new(), memory_size: 0 }
+    }
+
+    // Merges with the last command when possible
+    fn push(&mut self, command: Box<dyn UndoableCommand<T>>) {
+        let merged = self.commands.last_mut().is_some_and(|last| last.merge(command.as_ref()));
+        if !merged {
+            self.commands.push_back(command);
+        }
+        self.update_memory_size();
+    }
+
+    fn update_memory_size(&mut self) {
+        self.memory_size = mem::size_of::<Self>() + self.name.capacity() + self.commands.iter().map(|command| command.memory_size()).sum::<usize>();
+    }
+
+    fn revert(&mut self, target: &mut T) {
+        for command in self.commands.iter_mut().rev() {
+            command.revert(target);
+        }
+    }
+
+    fn apply(&mut self, target: &mut T) {
+        for command in self.commands.iter_mut() {
+            command.apply(target);
+        }
+    }
+}
+
+/// Undo/redo history of the edits of a T. Commands recorded inside begin_transaction/commit are undone together,
+/// commands recorded outside a transaction are their own entry and consecutive ones merge until `seal`.
+pub struct UndoStack<T: ?Sized + 'static> {
+    undo: Array<Transaction<T>>, // oldest first
+    redo: Array<Transaction<T>>, // next redo last
+    open: Option<Transaction<T>>,
+    depth: usize,
+    mergeable: bool, // the last undo entry was recorded alone and can absorb the next command
+    memory_size: usize,
+    memory_limit: usize,
+}
+
+impl<T: ?Sized + 'static> UndoStack<T> {
+    pub fn new() -> Self {
+        Self::with_memory_limit(usize::MAX)
+    }
+
+    /// Oldest entries are dropped when the history gets bigger than the limit, the last one is always kept
+    pub fn with_memory_limit(memory_limit: usize) -> Self {
+        UndoStack {
+            undo: Array::new(),
+            redo: Array::new(),
+            open: None,
+            depth: 0,
+            mergeable: false,
+            memory_size: 0,
+            memory_limit,
+        }
+    }
+
+    #[inline]
+    pub fn get_memory_limit(&self) -> usize {
+        self.memory_limit
+    }
+
+    pub fn set_memory_limit(&mut self, memory_limit: usize) {
+        self.memory_limit = memory_limit;
+        self.enforce_memory_limit();
+    }
+
+    #[inline]
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    #[inline]
+    pub fn undo_num(&self) -> usize {
+        self.undo.num()
+    }
+
+    #[inline]
+    pub fn redo_num(&self) -> usize {
+        self.redo.num()
+    }
+
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        self.open.is_none() && !self.undo.is_empty()
+    }
+
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        self.open.is_none() && !self.redo.is_empty()
+    }
+
+    #[inline]
+    pub fn get_undo_name(&self) -> Option<&str> {
+        self.undo.last().map(|transaction| transaction.name.as_str())
+    }
+
+    #[inline]
+    pub fn get_redo_name(&self) -> Option<&str> {
+        self.redo.last().map(|transaction| transaction.name.as_str())
+    }
+
+    #[inline]
+    pub fn is_in_transaction(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Transactions nest, only the outermost commit adds an entry to the history
+    pub fn begin_transaction(&mut self, name: &str) {
+        if self.depth == 0 {
+            self.open = Some(Transaction::new(name));
+        }
+        self.depth += 1;
+    }
+
+    /// Records an already applied command
+    pub fn record(&mut self, command: Box<dyn UndoableCommand<T>>) {
+        if let Some(open) = &mut self.open {
+            open.push(command);
+            return;
+        }
+
+        self.clear_redo();
+        if self.mergeable {
+            let last = self.undo.last_mut().unwrap();
+            if last.commands.last_mut().unwrap().merge(command.as_ref()) {
+                self.memory_size -= last.memory_size;
+                last.update_memory_size();
+                self.memory_size += last.memory_size;
+                self.enforce_memory_limit();
+                return;
+            }
+        }
+        self.push_entry(Transaction::new(command.get_name()), Some(command));
+        self.mergeable = true;
+    }
+
+    /// Applies the command to the target and records it
+    pub fn execute(&mut self, mut command: Box<dyn UndoableCommand<T>>, target: &mut T) {
+        command.apply(target);
+        self.record(command);
+    }
+
+    /// Ends the current transaction, false if it was nested or empty
+    pub fn commit(&mut self) -> bool {
+        assert!(self.depth > 0, "Committing without a transaction");
+        self.depth -= 1;
+        if self.depth > 0 {
+            return false;
+        }
+
+        let transaction = self.open.take().unwrap();
+        if transaction.commands.is_empty() {
+            return false;
+        }
+        self.clear_redo();
+        self.push_entry(transaction, None);
+        self.mergeable = false;
+        true
+    }
+
+    /// Reverts the commands recorded in the current transaction and closes it, nested ones included
+    pub fn cancel(&mut self, target: &mut T) {
+        assert!(self.depth > 0, "Cancelling without a transaction");
+        if let Some(mut transaction) = self.open.take() {
+            transaction.revert(target);
+        }
+        self.depth = 0;
+    }
+
+    /// The next recorded command starts a new entry instead of merging, called when a continuous edit ends
+    #[inline]
+    pub fn seal(&mut self) {
+        self.mergeable = false;
+    }
+
+    pub fn undo(&mut self, target: &mut T) -> bool {
+        assert!(self.open.is_none(), "Undoing during a transaction");
+        if self.undo.is_empty() {
+            return false;
+        }
+        let mut transaction = self.undo.pop_back();
+        transaction.revert(target);
+        self.redo.push_back(transaction);
+        self.mergeable = false;
+        true
+    }
+
+    pub fn redo(&mut self, target: &mut T) -> bool {
+        assert!(self.open.is_none(), "Redoing during a transaction");
+        if self.redo.is_empty() {
+            return false;
+        }
+        let mut transaction = self.redo.pop_back();
+        transaction.apply(target);
+        self.undo.push_back(transaction);
+        self.mergeable = false;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        assert!(self.open.is_none(), "Clearing during a transaction");
+        self.undo.clear();
+        self.redo.clear();
+        self.memory_size = 0;
+        self.mergeable = false;
+    }
+
+    fn push_entry(&mut self, mut transaction: Transaction<T>, command: Option<Box<dyn UndoableCommand<T>>>) {
+        if let Some(command) = command {
+            transaction.commands.push_back(command);
+        }
+        transaction.update_memory_size();
+        self.memory_size += transaction.memory_size;
+        self.undo.push_back(transaction);
+        self.enforce_memory_limit();
+    }
+
+    fn clear_redo(&mut self) {
+        self.memory_size -= self.redo.iter().map(|transaction| transaction.memory_size).sum::<usize>();
+        self.redo.clear();
+    }
+
+    fn enforce_memory_limit(&mut self) {
+        while self.memory_size > self.memory_limit && self.redo.num() + self.undo.num() > 1 {
+            // Redo entries are the least likely to be used again
+            let dropped = if !self.redo.is_empty() { self.redo.remove(0) } else { self.undo.remove(0) };
+            self.memory_size -= dropped.memory_size;
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Default for UndoStack<T> {
+    fn default() -> Self {
+        UndoStack::new()
+    }
+}
+
+/// Clone of a reflected object, its TypeInfo needs a clone function
+pub struct Snapshot {
+    storage: AnyArray,
+}
+
+impl Snapshot {
+    pub fn take(object: &dyn Reflected) -> Self {
+        let type_info = object.get_type_info();
+        let mut storage = AnyArray::for_type_info(type_info);
+        let cloned = unsafe{ storage.push_clone_from_ptr((object as *const dyn Reflected).cast::<u8>()) };
+        assert!(cloned, "Snapshot of {} which can't be cloned", type_info.get_name());
+        Snapshot { storage }
+    }
+
+    #[inline]
+    pub fn get_type_info(&self) -> &'static TypeInfo {
+        self.storage.get_type_info()
+    }
+
+    #[inline]
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.storage.get::<T>(0)
+    }
+
+    /// Overwrites the object with a clone of the snapshot
+    pub fn restore(&self, object: &mut dyn Reflected) {
+        assert!(object.get_type_info().get_id() == self.get_type_info().get_id(),
+            "Restoring a snapshot of {} into a {}", self.get_type_info().get_name(), object.get_type_info().get_name());
+        // The old value ends up in the copy and is dropped with it
+        let mut copy = self.storage.try_clone().unwrap();
+        unsafe {
+            ptr::swap_nonoverlapping(copy.get_ptr_mut(0), (object as *mut dyn Reflected).cast::<u8>(), self.get_type_info().get_layout().size());
+        }
+    }
+}
+
+impl Clone for Snapshot {
+    fn clone(&self) -> Self {
+        Snapshot { storage: self.storage.try_clone().unwrap() }
+    }
+}
+
+/// Fallback command for objects without dedicated commands, restores whole snapshots of a reflected object
+pub struct SnapshotCommand<T: ?Sized> {
+    name: String,
+    locate: Box<dyn for<'a> Fn(&'a mut T) -> &'a mut dyn Reflected>,
+    before: Snapshot,
+    after: Snapshot,
+    merge_key: Option<u64>,
+}
+
+impl<T: ?Sized + 'static> SnapshotCommand<T> {
+    /// `locate` finds the edited object in the target
+    pub fn new<F>(name: &str, before: Snapshot, after: Snapshot, locate: F) -> Self
+    where
+        F: 'static + for<'a> Fn(&'a mut T) -> &'a mut dyn Reflected
+    {
+        assert!(before.get_type_info().get_id() == after.get_type_info().get_id());
+        SnapshotCommand { name: name.to_string(), locate: Box::new(locate), before, after, merge_key: None }
+    }
+
+    /// Consecutive snapshot commands with the same key merge, keeping the first before and the last after
+    pub fn with_merge_key(mut self, merge_key: u64) -> Self {
+        self.merge_key = Some(merge_key);
+        self
+    }
+}
+
+impl<T: ?Sized + 'static> UndoableCommand<T> for SnapshotCommand<T> {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, target: &mut T) {
+        self.after.restore((self.locate)(target));
+    }
+
+    fn revert(&mut self, target: &mut T) {
+        self.before.restore((self.locate)(target));
+    }
+
+    fn memory_size(&self) -> usize {
+        mem::size_of::<Self>() + self.name.capacity() + 2 * self.before.get_type_info().get_layout().size()
+    }
+
+    fn merge(&mut self, next: &dyn UndoableCommand<T>) -> bool {
+        let Some(next) = (next as &dyn Any).downcast_ref::<Self>() else {
+            return false;
+        };
+        if self.merge_key.is_none() || self.merge_key != next.merge_key {
+            return false;
+        }
+        self.after = next.after.clone();
+        true
+    }
+}
@@ -0,0 +1,581 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::strings_table::STRINGS_TABLE_ENTRY_MAX_LEN;
+use crate::{Archive, ArchiveError, ArchiveReader, ArchiveResult, ArchiveWriter, Array, CaseSensitiveAtom, Map, RString, Serializable, StringAtom, xxh64_hash};
+
+// Pak layout (little endian):
+// header   u8[4] magic, u32 format version, u32 alignment, u32 reserved, u64 toc offset, u64 toc size
+// data     files, each one starting at a multiple of the alignment
+// toc      Archive of the entries keyed by path
+const PAK_MAGIC: [u8; 4] = *b"RLPK";
+const PAK_FORMAT_VERSION: u32 = 2;
+const PAK_HEADER_SIZE: usize = 32;
+const PAK_CHECKSUM_SEED: u64 = 0x524c_504b;
+// Bounds what a corrupted entry can make the reader allocate, files compressing better are stored
+const PAK_MAX_COMPRESSION_RATIO: u64 = 1024;
+
+#[derive(Debug)]
+pub enum VfsError {
+    InvalidPath(String),
+    UnknownMount(String),
+    NotFound(String),
+    UnsupportedCompression(PakCompression),
+    Corrupted(String),
+    Archive(ArchiveError),
+    Io(io::Error),
+}
+
+impl fmt::Display for VfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VfsError::InvalidPath(path) => write!(f, "Invalid path '{}'", path),
+            VfsError::UnknownMount(mount) => write!(f, "Nothing mounted at '{}:/'", mount),
+            VfsError::NotFound(path) => write!(f, "'{}' not found", path),
+            VfsError::UnsupportedCompression(compression) => write!(f, "{:?} compression isn't enabled in this build", compression),
+            VfsError::Corrupted(path) => write!(f, "'{}' is corrupted", path),
+            VfsError::Archive(error) => write!(f, "{}", error),
+            VfsError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for VfsError { }
+
+impl From<io::Error> for VfsError {
+    fn from(error: io::Error) -> Self {
+        VfsError::Io(error)
+    }
+}
+
+impl From<ArchiveError> for VfsError {
+    fn from(error: ArchiveError) -> Self {
+        VfsError::Archive(error)
+    }
+}
+
+pub type VfsResult<T> = Result<T, VfsError>;
+
+/// Path relative to a mount point: `/` separated, without empty, `.` or `..` components
+pub fn normalize_path(path: &str) -> VfsResult<RString> {
+    let mut normalized = RString::with_capacity(path.len());
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => (),
+            ".." => return Err(VfsError::InvalidPath(path.to_string())),
+            _ => {
+                if !normalized.is_empty() {
+                    normalized.push('/');
+                }
+                normalized.push_str(component);
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+// "mount:/relative/path" to the mount atom and the normalized relative path
+fn split_mount(path: &str) -> VfsResult<(StringAtom, RString)> {
+    match path.split_once(":/") {
+        Some((mount, relative)) if !mount.is_empty() && mount.len() <= STRINGS_TABLE_ENTRY_MAX_LEN => {
+            Ok((StringAtom::from(mount), normalize_path(relative)?))
+        }
+        _ => Err(VfsError::InvalidPath(path.to_string())),
+    }
+}
+
+/// Files of a mount point, paths are normalized and relative to it
+pub trait VfsSource : Send + Sync {
+    fn contains(&self, path: &str) -> bool;
+
+    fn read(&self, path: &str) -> VfsResult<Array<u8>>;
+
+    fn list(&self, files: &mut Array<RString>);
+}
+
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(root: impl AsRef<Path>) -> VfsResult<Self> {
+        let root = root.as_ref();
+        if !root.is_dir() {
+            return Err(VfsError::NotFound(root.display().to_string()));
+        }
+        Ok(DirectorySource { root: root.to_path_buf() })
+    }
+
+    fn list_directory(directory: &Path, prefix: &str, files: &mut Array<RString>) {
+        let Ok(entries) = fs::read_dir(directory) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let Some(name) = entry.file_name().to_str().map(|name| name.to_string()) else {
+                continue;
+            };
+            let mut path = RString::from(prefix);
+            path.push_str(&name);
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => {
+                    path.push('/');
+                    Self::list_directory(&entry.path(), &path, files);
+                }
+                Ok(_) => files.push_back(path),
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+impl VfsSource for DirectorySource {
+    fn contains(&self, path: &str) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn read(&self, path: &str) -> VfsResult<Array<u8>> {
+        let mut file = match File::open(self.root.join(path)) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(VfsError::NotFound(path.to_string())),
+            result => result?,
+        };
+        let len = file.metadata()?.len() as usize;
+        let mut data = Array::new();
+        data.insert_range(0..len, 0u8);
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn list(&self, files: &mut Array<RString>) {
+        Self::list_directory(&self.root, "", files);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PakCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl PakCompression {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PakCompression::None),
+            1 => Some(PakCompression::Lz4),
+            2 => Some(PakCompression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+fn compress(compression: PakCompression, data: &[u8]) -> VfsResult<Array<u8>> {
+    let mut compressed = Array::new();
+    match compression {
+        PakCompression::None => compressed.extend_from_slice(data),
+        #[cfg(feature = "lz4")]
+        PakCompression::Lz4 => compressed.extend_from_slice(&lz4_flex::compress(data)),
+        #[cfg(feature = "zstd")]
+        PakCompression::Zstd => compressed.extend_from_slice(&zstd::bulk::compress(data, 0)?),
+        #[allow(unreachable_patterns)]
+        _ => return Err(VfsError::UnsupportedCompression(compression)),
+    }
+    Ok(compressed)
+}
+
+fn decompress(compression: PakCompression, data: &[u8], output: &mut [u8]) -> VfsResult<bool> {
+    match compression {
+        PakCompression::None => {
+            output.copy_from_slice(data);
+            Ok(true)
+        }
+        #[cfg(feature = "lz4")]
+        PakCompression::Lz4 => Ok(lz4_flex::decompress_into(data, output).is_ok_and(|len| len == output.len())),
+        #[cfg(feature = "zstd")]
+        PakCompression::Zstd => Ok(zstd::bulk::decompress_to_buffer(data, output).is_ok_and(|len| len == output.len())),
+        #[allow(unreachable_patterns)]
+        _ => Err(VfsError::UnsupportedCompression(compression)),
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct PakEntry {
+    offset: u64,
+    size: u64,
+    uncompressed_size: u64,
+    compression: PakCompression,
+    checksum: u64, // xxh64 of the uncompressed data
+}
+
+impl PakEntry {
+    #[inline]
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[inline]
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    pub fn get_uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    #[inline]
+    pub fn get_compression(&self) -> PakCompression {
+        self.compression
+    }
+
+    // Sizes the writer can produce
+    fn has_valid_sizes(&self) -> bool {
+        match self.compression {
+            PakCompression::None => self.size == self.uncompressed_size,
+            _ => self.uncompressed_size <= self.size.saturating_mul(PAK_MAX_COMPRESSION_RATIO),
+        }
+    }
+}
+
+impl Serializable for PakEntry {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        ar.serialize(&mut self.offset)?;
+        ar.serialize(&mut self.size)?;
+        ar.serialize(&mut self.uncompressed_size)?;
+        let mut compression = self.compression as u8;
+        ar.serialize(&mut compression)?;
+        self.compression = PakCompression::from_u8(compression).ok_or(ArchiveError::InvalidData("unknown pak compression"))?;
+        ar.serialize(&mut self.checksum)
+    }
+}
+
+// Paths are case sensitive, like the directories they're packed from
+#[derive(Default)]
+struct PakToc {
+    entries: Map<CaseSensitiveAtom, PakEntry>,
+}
+
+// Paths are written as strings, the name table of the archive is case insensitive
+impl Serializable for PakToc {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut len = self.entries.num();
+        ar.serialize_len(&mut len)?;
+        if ar.is_loading() {
+            self.entries.clear();
+            for _ in 0..len {
+                let mut path = String::new();
+                let mut entry = PakEntry::default();
+                ar.serialize(&mut path)?;
+                ar.serialize(&mut entry)?;
+                let key = pak_key(&path).ok_or(ArchiveError::InvalidData("invalid pak path"))?;
+                self.entries.insert(key, entry);
+            }
+            Ok(())
+        } else {
+            self.entries.iter_mut().try_for_each(|(path, entry)| {
+                ar.serialize(&mut path.to_string())?;
+                ar.serialize(entry)
+            })
+        }
+    }
+}
+
+fn pak_key(path: &str) -> Option<CaseSensitiveAtom> {
+    (!path.is_empty() && path.len() <= STRINGS_TABLE_ENTRY_MAX_LEN).then(|| CaseSensitiveAtom::from(path))
+}
+
+/// Writes a pak file, the files are streamed to it and the table of contents is written by `finish`
+pub struct PakWriter {
+    file: BufWriter<File>,
+    toc: PakToc,
+    alignment: u64,
+    pos: u64,
+}
+
+impl PakWriter {
+    pub fn create(path: impl AsRef<Path>, alignment: usize) -> VfsResult<Self> {
+        assert!(alignment.is_power_of_two(), "Pak alignment {} isn't a power of two", alignment);
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&[0; PAK_HEADER_SIZE])?;
+        Ok(PakWriter { file, toc: PakToc::default(), alignment: alignment as u64, pos: PAK_HEADER_SIZE as u64 })
+    }
+
+    #[inline]
+    pub fn files_num(&self) -> usize {
+        self.toc.entries.num()
+    }
+
+    /// Files that don't get smaller are stored uncompressed. Adding a path again replaces it, the old data stays unreferenced.
+    pub fn add_file(&mut self, path: &str, data: &[u8], compression: PakCompression) -> VfsResult<()> {
+        let normalized = normalize_path(path)?;
+        let key = pak_key(&normalized).ok_or_else(|| VfsError::InvalidPath(path.to_string()))?;
+
+        let compressed = compress(compression, data)?;
+        let compressed_len = compressed.num() as u64;
+        let worth_it = compressed_len < data.len() as u64 && compressed_len * PAK_MAX_COMPRESSION_RATIO >= data.len() as u64;
+        let (compression, stored) = match compression != PakCompression::None && worth_it {
+            true => (compression, compressed.as_slice()),
+            false => (PakCompression::None, data),
+        };
+
+        let offset = self.pos.next_multiple_of(self.alignment);
+        io::copy(&mut io::repeat(0).take(offset - self.pos), &mut self.file)?;
+        self.file.write_all(stored)?;
+        self.pos = offset + stored.len() as u64;
+
+        let entry = PakEntry {
+            offset,
+            size: stored.len() as u64,
+            uncompressed_size: data.len() as u64,
+            compression,
+            checksum: xxh64_hash(data, PAK_CHECKSUM_SEED),
+        };
+        self.toc.entries.insert(key, entry);
+        Ok(())
+    }
+
+    /// Adds the files of the directory and its subdirectories, their paths relative to it. Returns how many were added.
+    pub fn add_directory(&mut self, directory: impl AsRef<Path>, compression: PakCompression) -> VfsResult<usize> {
+        let source = DirectorySource::new(directory)?;
+        let mut files = Array::new();
+        source.list(&mut files);
+        files.sort();
+        for path in files.iter() {
+            self.add_file(path, &source.read(path)?, compression)?;
+        }
+        Ok(files.num())
+    }
+
+    pub fn finish(mut self) -> VfsResult<()> {
+        let mut writer = ArchiveWriter::new();
+        writer.serialize_versioned(&mut self.toc)?;
+        let toc = writer.finish();
+        self.file.write_all(&toc)?;
+
+        let mut header = [0u8; PAK_HEADER_SIZE];
+        header[0..4].copy_from_slice(&PAK_MAGIC);
+        header[4..8].copy_from_slice(&PAK_FORMAT_VERSION.to_le_bytes());
+        header[8..12].copy_from_slice(&(self.alignment as u32).to_le_bytes());
+        header[16..24].copy_from_slice(&self.pos.to_le_bytes());
+        header[24..32].copy_from_slice(&(toc.num() as u64).to_le_bytes());
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Memory mapped pak, its table of contents is checked when opened and files are checked when read
+pub struct PakFile {
+    map: memmap2::Mmap,
+    toc: PakToc,
+    alignment: usize,
+}
+
+impl PakFile {
+    /// The file must not be modified while it is open
+    pub fn open(path: impl AsRef<Path>) -> VfsResult<Self> {
+        let file = File::open(path)?;
+        let map = unsafe{ memmap2::Mmap::map(&file)? };
+        if map.len() < PAK_HEADER_SIZE || map[0..4] != PAK_MAGIC {
+            return Err(ArchiveError::InvalidData("not a pak file").into());
+        }
+        let read_u32 = |pos: usize| u32::from_le_bytes(map[pos..pos + 4].try_into().unwrap());
+        let read_u64 = |pos: usize| u64::from_le_bytes(map[pos..pos + 8].try_into().unwrap());
+        if read_u32(4) != PAK_FORMAT_VERSION {
+            return Err(ArchiveError::InvalidData("unsupported pak format version").into());
+        }
+        let alignment = read_u32(8) as usize;
+        let (toc_offset, toc_size) = (read_u64(16), read_u64(24));
+        let toc_end = toc_offset.checked_add(toc_size).ok_or(ArchiveError::UnexpectedEnd)?;
+        if toc_end > map.len() as u64 {
+            return Err(ArchiveError::UnexpectedEnd.into());
+        }
+
+        let mut toc = PakToc::default();
+        ArchiveReader::new(&map[toc_offset as usize..toc_end as usize])?.serialize_versioned(&mut toc)?;
+        let in_bounds = |entry: &PakEntry| entry.offset.checked_add(entry.size).is_some_and(|end| end <= toc_offset);
+        if !toc.entries.iter().all(|(_, entry)| in_bounds(entry)) {
+            return Err(ArchiveError::InvalidData("pak entry out of bounds").into());
+        }
+        if !toc.entries.iter().all(|(_, entry)| entry.has_valid_sizes()) {
+            return Err(ArchiveError::InvalidData("invalid pak entry size").into());
+        }
+        Ok(PakFile { map, toc, alignment })
+    }
+
+    #[inline]
+    pub fn get_alignment(&self) -> usize {
+        self.alignment
+    }
+
+    #[inline]
+    pub fn files_num(&self) -> usize {
+        self.toc.entries.num()
+    }
+
+    pub fn get_entry(&self, path: &str) -> Option<&PakEntry> {
+        self.toc.entries.get(&pak_key(path)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&CaseSensitiveAtom, &PakEntry)> {
+        self.toc.entries.iter()
+    }
+
+    // Stored bytes, compressed or not
+    pub fn get_stored(&self, entry: &PakEntry) -> &[u8] {
+        &self.map[entry.offset as usize..(entry.offset + entry.size) as usize]
+    }
+}
+
+impl VfsSource for PakFile {
+    fn contains(&self, path: &str) -> bool {
+        self.get_entry(path).is_some()
+    }
+
+    fn read(&self, path: &str) -> VfsResult<Array<u8>> {
+        let entry = self.get_entry(path).ok_or_else(|| VfsError::NotFound(path.to_string()))?;
+        // Checked when opened, the size is at most PAK_MAX_COMPRESSION_RATIO times the stored bytes in the file
+        debug_assert!(entry.has_valid_sizes());
+        let size = usize::try_from(entry.uncompressed_size).map_err(|_| VfsError::Corrupted(path.to_string()))?;
+        let mut data = Array::new();
+        data.insert_range(0..size, 0u8);
+        if !decompress(entry.compression, self.get_stored(entry), &mut data)? || xxh64_hash(&data, PAK_CHECKSUM_SEED) != entry.checksum {
+            return Err(VfsError::Corrupted(path.to_string()));
+        }
+        Ok(data)
+    }
+
+    fn list(&self, files: &mut Array<RString>) {
+        for (path, _) in self.iter() {
+            files.push_back(RString::from(path.to_string().as_str()));
+        }
+    }
+}
+
+/// Identifies a mount for unmounting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MountId(usize);
+
+struct Mount {
+    id: MountId,
+    name: StringAtom,
+    priority: i32,
+    source: Arc<dyn VfsSource>,
+}
+
+#[derive(Default)]
+struct VfsInner {
+    mounts: Array<Mount>, // highest priority first, the most recent first among equal priorities
+    next_id: usize,
+}
+
+/// Mount points (`shaders:/`, `scenes:/`...) mapped to overlaid sources: the highest priority source containing a file wins,
+/// so mods and patches mounted above the base files override them
+#[derive(Default)]
+pub struct Vfs {
+    inner: RwLock<VfsInner>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Vfs::default()
+    }
+
+    pub fn global() -> &'static Vfs {
+        static GLOBAL: OnceLock<Vfs> = OnceLock::new();
+        GLOBAL.get_or_init(Vfs::new)
+    }
+
+    pub fn mount(&self, mount: &str, source: Arc<dyn VfsSource>, priority: i32) -> MountId {
+        let mount = mount.trim_end_matches(":/");
+        assert!(!mount.is_empty() && mount.len() <= STRINGS_TABLE_ENTRY_MAX_LEN, "Invalid mount name '{}'", mount);
+        let mut inner = self.inner.write().unwrap();
+        let id = MountId(inner.next_id);
+        inner.next_id += 1;
+        let index = inner.mounts.iter().position(|other| other.priority <= priority).unwrap_or(inner.mounts.num());
+        inner.mounts.insert(index, Mount { id, name: StringAtom::from(mount), priority, source });
+        id
+    }
+
+    pub fn mount_directory(&self, mount: &str, directory: impl AsRef<Path>, priority: i32) -> VfsResult<MountId> {
+        Ok(self.mount(mount, Arc::new(DirectorySource::new(directory)?), priority))
+    }
+
+    pub fn mount_pak(&self, mount: &str, pak: impl AsRef<Path>, priority: i32) -> VfsResult<MountId> {
+        Ok(self.mount(mount, Arc::new(PakFile::open(pak)?), priority))
+    }
+
+    pub fn unmount(&self, id: MountId) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        match inner.mounts.iter().position(|mount| mount.id == id) {
+            Some(index) => {
+                inner.mounts.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn mounts_num(&self) -> usize {
+        self.inner.read().unwrap().mounts.num()
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.find_source(path).is_ok_and(|(source, relative)| source.contains(&relative))
+    }
+
+    pub fn read(&self, path: &str) -> VfsResult<Array<u8>> {
+        let (source, relative) = self.find_source(path)?;
+        source.read(&relative)
+    }
+
+    pub fn read_to_string(&self, path: &str) -> VfsResult<RString> {
+        let data = self.read(path)?;
+        let mut string = RString::with_capacity(data.num());
+        string.push_utf8(&data).map_err(|_| VfsError::Corrupted(path.to_string()))?;
+        Ok(string)
+    }
+
+    /// Sorted paths of the files visible at the mount point, relative to it
+    pub fn list(&self, mount: &str) -> Array<RString> {
+        let mount = mount.trim_end_matches(":/");
+        if mount.len() > STRINGS_TABLE_ENTRY_MAX_LEN {
+            return Array::new();
+        }
+        let mount = StringAtom::from(mount);
+        let mut files = Array::new();
+        for source in self.get_sources(mount).iter() {
+            source.list(&mut files);
+        }
+        files.sort();
+        let mut unique: Array<RString> = Array::with_capacity(files.num());
+        for file in files.iter() {
+            if unique.last() != Some(file) {
+                unique.push_back(file.clone());
+            }
+        }
+        unique
+    }
+
+    // Highest priority source containing the path, the lock isn't held while reading
+    fn find_source(&self, path: &str) -> VfsResult<(Arc<dyn VfsSource>, RString)> {
+        let (mount, relative) = split_mount(path)?;
+        let sources = self.get_sources(mount);
+        if sources.is_empty() {
+            return Err(VfsError::UnknownMount(mount.to_string()));
+        }
+        match sources.iter().find(|source| source.contains(&relative)) {
+            Some(source) => Ok((source.clone(), relative)),
+            None => Err(VfsError::NotFound(path.to_string())),
+        }
+    }
+
+    fn get_sources(&self, mount: StringAtom) -> Array<Arc<dyn VfsSource>> {
+        let inner = self.inner.read().unwrap();
+        inner.mounts.iter().filter(|other| other.name == mount).map(|other| other.source.clone()).collect()
+    }
+}
@@ -0,0 +1,244 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, format_ident};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index, Error};
+
+fn add_trait_bounds(input: &mut DeriveInput, bound: syn::Path) {
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+}
+
+fn hash_fields(accessors: &[TokenStream2]) -> TokenStream2 {
+    let field_hashes = accessors.iter().map(|accessor| {
+        quote! {
+            hash = ::rl_core::hash_combine(hash, ::rl_core::FastHash::fast_hash(#accessor));
+        }
+    });
+    quote! { #(#field_hashes)* }
+}
+
+#[proc_macro_derive(FastHash)]
+pub fn derive_fast_hash(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+    add_trait_bounds(&mut input, parse_quote!(::rl_core::FastHash));
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let accessors: Vec<TokenStream2> = match &data.fields {
+                Fields::Named(fields) => fields.named.iter().map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+                    quote! { &self.#ident }
+                }).collect(),
+                Fields::Unnamed(fields) => (0..fields.unnamed.len()).map(|i| {
+                    let index = Index::from(i);
+                    quote! { &self.#index }
+                }).collect(),
+                Fields::Unit => Vec::new(),
+            };
+            let field_hashes = hash_fields(&accessors);
+            quote! {
+                let mut hash = 0usize;
+                #field_hashes
+                hash
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().enumerate().map(|(variant_index, variant)| {
+                let variant_ident = &variant.ident;
+                let (pattern, accessors) = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let idents: Vec<_> = fields.named.iter().map(|field| field.ident.clone().unwrap()).collect();
+                        (quote! { { #(#idents),* } }, idents.iter().map(|ident| quote! { #ident }).collect::<Vec<_>>())
+                    }
+                    Fields::Unnamed(fields) => {
+                        let idents: Vec<_> = (0..fields.unnamed.len()).map(|i| format_ident!("field{}", i)).collect();
+                        (quote! { ( #(#idents),* ) }, idents.iter().map(|ident| quote! { #ident }).collect::<Vec<_>>())
+                    }
+                    Fields::Unit => (quote! {}, Vec::new()),
+                };
+                let field_hashes = hash_fields(&accessors);
+                quote! {
+                    Self::#variant_ident #pattern => {
+                        let mut hash = #variant_index;
+                        #field_hashes
+                        hash
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Error::new(Span::call_site(), "FastHash cannot be derived for unions")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::rl_core::FastHash for #name #ty_generics #where_clause {
+            #[inline]
+            fn fast_hash(&self) -> usize {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(SetKey)]
+pub fn derive_set_key(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+    add_trait_bounds(&mut input, parse_quote!(::rl_core::SetKey));
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::rl_core::SetKey for #name #ty_generics #where_clause { }
+    }
+    .into()
+}
+
+#[proc_macro_derive(SetItem, attributes(key))]
+pub fn derive_set_item(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Error::new(Span::call_site(), "SetItem can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let key_fields: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field.attrs.iter().any(|attr| attr.path().is_ident("key")))
+        .collect();
+    if key_fields.len() != 1 {
+        return Error::new(Span::call_site(), "SetItem needs exactly one field marked with #[key]")
+            .to_compile_error()
+            .into();
+    }
+
+    let (key_index, key_field) = key_fields[0];
+    let key_type = &key_field.ty;
+    let key_accessor = match &key_field.ident {
+        Some(ident) => quote! { #ident },
+        None => {
+            let index = Index::from(key_index);
+            quote! { #index }
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::rl_core::SetItem for #name #ty_generics #where_clause {
+            type KeyType = #key_type;
+
+            #[inline]
+            fn get_key(&self) -> &Self::KeyType {
+                &self.#key_accessor
+            }
+        }
+    }
+    .into()
+}
+
+fn has_reflect_flag(attrs: &[syn::Attribute], flag: &str) -> syn::Result<bool> {
+    let mut found = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("reflect")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(flag) {
+                found = true;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(found)
+}
+
+// #[reflect(clone, default, debug)] on the type registers the optional functions,
+// #[reflect(skip)] on a field leaves it out of the field descriptors
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+    add_trait_bounds(&mut input, parse_quote!(::rl_core::Reflect));
+
+    match reflect_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn reflect_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let self_type = quote! { #name #ty_generics };
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new(Span::call_site(), "Reflect can only be derived for structs")),
+    };
+
+    let mut field_infos = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        if has_reflect_flag(&field.attrs, "skip")? {
+            continue;
+        }
+
+        let field_type = &field.ty;
+        let (member, field_name) = match &field.ident {
+            Some(ident) => (quote! { #ident }, ident.to_string()),
+            None => {
+                let index = Index::from(i);
+                (quote! { #index }, i.to_string())
+            }
+        };
+        // The offset comes from offset_of! on the field of that type
+        field_infos.push(quote! {
+            .with_field(unsafe{ ::rl_core::FieldInfo::new::<#field_type>(
+                ::rl_core::StringAtom::from(#field_name),
+                ::core::mem::offset_of!(#self_type, #member)) })
+        });
+    }
+
+    let with_clone = if has_reflect_flag(&input.attrs, "clone")? {
+        quote! { .with_clone::<#self_type>() }
+    } else {
+        quote! {}
+    };
+    let with_default = if has_reflect_flag(&input.attrs, "default")? {
+        quote! { .with_default::<#self_type>() }
+    } else {
+        quote! {}
+    };
+    let with_debug = if has_reflect_flag(&input.attrs, "debug")? {
+        quote! { .with_debug::<#self_type>() }
+    } else {
+        quote! {}
+    };
+
+    Ok(quote! {
+        unsafe impl #impl_generics ::rl_core::Reflect for #self_type #where_clause {
+            fn type_info() -> &'static ::rl_core::TypeInfo {
+                ::rl_core::register_type_with::<Self, _>(|| {
+                    ::rl_core::TypeInfo::of::<Self>()
+                        #with_clone
+                        #with_default
+                        #with_debug
+                        #(#field_infos)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,146 @@
+use std::any::TypeId;
+
+use rl_core::{Array, AnyArray, Map, TypeInfo};
+
+use crate::{Component, Entity};
+
+// Table of all the entities sharing the same set of component types, one column per type
+pub struct Archetype {
+    types: Array<TypeId>, // sorted, same order as columns
+    columns: Array<AnyArray>,
+    entities: Array<Entity>,
+    add_edges: Map<TypeId, usize>,
+    remove_edges: Map<TypeId, usize>,
+}
+
+impl Archetype {
+    // type_infos must be sorted by type id without duplicates
+    pub(crate) fn new(type_infos: &[&'static TypeInfo]) -> Self {
+        debug_assert!(type_infos.windows(2).all(|pair| pair[0].get_id() < pair[1].get_id()));
+        Archetype {
+            types: type_infos.iter().map(|type_info| *type_info.get_id()).collect(),
+            columns: type_infos.iter().map(|type_info| AnyArray::for_type_info(type_info)).collect(),
+            entities: Array::new(),
+            add_edges: Map::new(),
+            remove_edges: Map::new(),
+        }
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.entities.num()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    #[inline]
+    pub fn get_types(&self) -> &[TypeId] {
+        &self.types
+    }
+
+    #[inline]
+    pub fn get_entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    #[inline]
+    pub fn get_type_info(&self, column: usize) -> &'static TypeInfo {
+        self.columns[column].get_type_info()
+    }
+
+    // usize::MAX if the archetype has no such component
+    #[inline]
+    pub fn find_column(&self, type_id: &TypeId) -> usize {
+        self.types.binary_search(type_id).unwrap_or(usize::MAX)
+    }
+
+    #[inline]
+    pub fn has(&self, type_id: &TypeId) -> bool {
+        self.types.binary_search(type_id).is_ok()
+    }
+
+    #[inline]
+    pub fn has_component<T: Component>(&self) -> bool {
+        self.has(&TypeId::of::<T>())
+    }
+
+    #[inline]
+    pub fn get_column<T: Component>(&self) -> Option<&[T]> {
+        let column = self.find_column(&TypeId::of::<T>());
+        if column == usize::MAX {
+            None
+        } else {
+            self.columns[column].as_slice::<T>()
+        }
+    }
+
+    // Base pointer of a column, the storage is not borrowed so callers handle aliasing
+    #[inline]
+    pub(crate) fn get_column_ptr(&self, column: usize) -> *mut u8 {
+        self.columns[column].get_base_ptr()
+    }
+
+    #[inline]
+    pub(crate) fn get_entities_ptr(&self) -> *const Entity {
+        self.entities.as_ptr()
+    }
+
+    #[inline]
+    pub(crate) fn get_column_mut(&mut self, column: usize) -> &mut AnyArray {
+        &mut self.columns[column]
+    }
+
+    #[inline]
+    pub(crate) fn push_entity(&mut self, entity: Entity) -> usize {
+        self.entities.push_back(entity);
+        self.entities.num() - 1
+    }
+
+    // Removes the row from every column (dropping the components) and returns the entity moved in its place
+    pub(crate) fn swap_remove(&mut self, row: usize) -> Option<Entity> {
+        for column in self.columns.iter_mut() {
+            column.swap_remove(row);
+        }
+        self.swap_remove_entity(row)
+    }
+
+    // Only the entity list, columns are handled by the caller
+    pub(crate) fn swap_remove_entity(&mut self, row: usize) -> Option<Entity> {
+        self.entities.swap_remove(row);
+        if row < self.entities.num() {
+            Some(self.entities[row])
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for column in self.columns.iter_mut() {
+            column.clear();
+        }
+        self.entities.clear();
+    }
+
+    #[inline]
+    pub(crate) fn get_add_edge(&self, type_id: &TypeId) -> Option<usize> {
+        self.add_edges.get(type_id).copied()
+    }
+
+    #[inline]
+    pub(crate) fn set_add_edge(&mut self, type_id: TypeId, archetype: usize) {
+        self.add_edges.insert(type_id, archetype);
+    }
+
+    #[inline]
+    pub(crate) fn get_remove_edge(&self, type_id: &TypeId) -> Option<usize> {
+        self.remove_edges.get(type_id).copied()
+    }
+
+    #[inline]
+    pub(crate) fn set_remove_edge(&mut self, type_id: TypeId, archetype: usize) {
+        self.remove_edges.insert(type_id, archetype);
+    }
+}
@@ -0,0 +1,84 @@
+use std::any::{Any, TypeId};
+use std::mem::ManuallyDrop;
+use std::sync::{OnceLock, RwLock};
+
+use rl_core::{Array, Map, TypeInfo, find_type};
+
+// Any plain Rust type can be a component, no registration needed
+pub trait Component : Any + Send + Sync { }
+
+impl<T: Any + Send + Sync> Component for T { }
+
+static COMPONENT_TYPES: OnceLock<RwLock<Map<TypeId, &'static TypeInfo>>> = OnceLock::new();
+
+// Reflected types reuse their registered TypeInfo, other types get a minimal one (layout and drop)
+pub fn component_type_info<T: Component>() -> &'static TypeInfo {
+    let type_id = TypeId::of::<T>();
+    let component_types = COMPONENT_TYPES.get_or_init(|| RwLock::new(Map::new()));
+
+    if let Some(type_info) = component_types.read().unwrap().get(&type_id) {
+        return type_info;
+    }
+
+    let mut types = component_types.write().unwrap();
+    if let Some(type_info) = types.get(&type_id) {
+        return type_info;
+    }
+    let type_info = find_type(&type_id).unwrap_or_else(|| Box::leak(Box::new(TypeInfo::of::<T>())));
+    types.insert(type_id, type_info);
+    type_info
+}
+
+/// A set of components spawned together, implemented for tuples of components
+///
+/// # Safety
+/// `collect_type_infos` must list exactly the components `write_components` hands out.
+pub unsafe trait Bundle : Send + Sync + 'static {
+    fn collect_type_infos(type_infos: &mut Array<&'static TypeInfo>);
+
+    /// Moves every component out through the callback.
+    ///
+    /// # Safety
+    /// The callback takes ownership of the pointed values and must copy them out before returning.
+    unsafe fn write_components<W>(self, write_fn: W) where
+        W: FnMut(TypeId, *const u8);
+}
+
+unsafe impl Bundle for () {
+    fn collect_type_infos(_type_infos: &mut Array<&'static TypeInfo>) { }
+
+    unsafe fn write_components<W>(self, _write_fn: W) where
+        W: FnMut(TypeId, *const u8)
+    {
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($name:ident),+) => {
+        unsafe impl<$($name: Component),+> Bundle for ($($name,)+) {
+            fn collect_type_infos(type_infos: &mut Array<&'static TypeInfo>) {
+                $(type_infos.push_back(component_type_info::<$name>());)+
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn write_components<W>(self, mut write_fn: W) where
+                W: FnMut(TypeId, *const u8)
+            {
+                let ($($name,)+) = self;
+                $(
+                    let $name = ManuallyDrop::new($name);
+                    write_fn(TypeId::of::<$name>(), (&*$name as *const $name).cast::<u8>());
+                )+
+            }
+        }
+    }
+}
+
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,122 @@
+use core::fmt;
+
+use rl_core::{Array, FastHash, SetKey};
+
+// Index in the entities table plus a generation, so a stale id never aliases a new entity
+#[derive(Copy, Clone, PartialEq, Eq, FastHash, SetKey)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub const NONE: Entity = Entity { index: u32::MAX, generation: 0 };
+
+    #[inline]
+    pub fn get_index(&self) -> u32 {
+        self.index
+    }
+
+    #[inline]
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.index == u32::MAX
+    }
+}
+
+impl Default for Entity {
+    fn default() -> Self {
+        Entity::NONE
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Entity({}v{})", self.index, self.generation)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct EntityLocation {
+    pub(crate) archetype: u32,
+    pub(crate) row: u32,
+}
+
+impl EntityLocation {
+    const FREE: EntityLocation = EntityLocation { archetype: u32::MAX, row: u32::MAX };
+}
+
+struct EntityMeta {
+    generation: u32,
+    location: EntityLocation,
+}
+
+#[derive(Default)]
+pub(crate) struct Entities {
+    metas: Array<EntityMeta>,
+    free_indices: Array<u32>,
+}
+
+impl Entities {
+    #[inline]
+    pub(crate) fn num(&self) -> usize {
+        self.metas.num() - self.free_indices.num()
+    }
+
+    pub(crate) fn alloc(&mut self) -> Entity {
+        if !self.free_indices.is_empty() {
+            let index = self.free_indices.pop_back();
+            let generation = self.metas[index as usize].generation;
+            return Entity { index, generation };
+        }
+
+        let index = self.metas.num() as u32;
+        assert!(index != u32::MAX, "Too many entities");
+        self.metas.push_back(EntityMeta { generation: 0, location: EntityLocation::FREE });
+        Entity { index, generation: 0 }
+    }
+
+    pub(crate) fn free(&mut self, entity: Entity) {
+        let meta = &mut self.metas[entity.index as usize];
+        debug_assert!(meta.generation == entity.generation);
+        meta.generation = meta.generation.wrapping_add(1);
+        meta.location = EntityLocation::FREE;
+        self.free_indices.push_back(entity.index);
+    }
+
+    #[inline]
+    pub(crate) fn is_alive(&self, entity: Entity) -> bool {
+        (entity.index as usize) < self.metas.num()
+            && self.metas[entity.index as usize].generation == entity.generation
+            && self.metas[entity.index as usize].location.archetype != u32::MAX
+    }
+
+    #[inline]
+    pub(crate) fn get_location(&self, entity: Entity) -> Option<EntityLocation> {
+        if self.is_alive(entity) {
+            Some(self.metas[entity.index as usize].location)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_location(&mut self, entity: Entity, location: EntityLocation) {
+        debug_assert!(self.metas[entity.index as usize].generation == entity.generation);
+        self.metas[entity.index as usize].location = location;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for (index, meta) in self.metas.iter_mut().enumerate() {
+            if meta.location.archetype != u32::MAX {
+                meta.generation = meta.generation.wrapping_add(1);
+                meta.location = EntityLocation::FREE;
+                self.free_indices.push_back(index as u32);
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+mod entity;
+
+pub use entity::Entity;
+
+mod component;
+
+pub use component::Component;
+pub use component::Bundle;
+pub use component::component_type_info;
+
+mod archetype;
+
+pub use archetype::Archetype;
+
+mod query;
+
+pub use query::Query;
+pub use query::ReadOnlyQuery;
+pub use query::QueryFilter;
+pub use query::QueryIter;
+pub use query::{With, Without};
+
+mod world;
+
+pub use world::World;
+
+mod schedule;
+
+pub use schedule::SystemAccess;
+pub use schedule::SystemWorld;
+pub use schedule::System;
+pub use schedule::SystemTiming;
+pub use schedule::Schedule;
+pub use schedule::ScheduleError;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,264 @@
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use rl_core::Array;
+
+use crate::{Archetype, Component, Entity};
+
+/// Something that can be fetched per entity from an archetype: &T, &mut T, Option<..>, Entity and tuples of those
+///
+/// # Safety
+/// `collect_access` must report every component the query reads or writes.
+pub unsafe trait Query {
+    type Item<'w>;
+    type State: Copy;
+
+    // (type, mutable) pairs, used to reject queries aliasing a component mutably
+    fn collect_access(access: &mut Array<(TypeId, bool)>);
+
+    fn matches(archetype: &Archetype) -> bool;
+
+    /// # Safety
+    /// Only called for archetypes that match.
+    unsafe fn prepare(archetype: &Archetype) -> Self::State;
+
+    /// # Safety
+    /// The row must be in the prepared archetype, which must outlive 'w.
+    unsafe fn fetch<'w>(state: Self::State, row: usize) -> Self::Item<'w>;
+}
+
+/// Queries that never write, they can run on a shared World
+///
+/// # Safety
+/// Every access reported by `collect_access` must be read-only.
+pub unsafe trait ReadOnlyQuery : Query { }
+
+pub trait QueryFilter {
+    fn matches(archetype: &Archetype) -> bool;
+}
+
+pub struct With<T: Component>(PhantomData<T>);
+pub struct Without<T: Component>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T> {
+    #[inline]
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has_component::<T>()
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    #[inline]
+    fn matches(archetype: &Archetype) -> bool {
+        !archetype.has_component::<T>()
+    }
+}
+
+impl QueryFilter for () {
+    #[inline]
+    fn matches(_archetype: &Archetype) -> bool {
+        true
+    }
+}
+
+unsafe impl Query for Entity {
+    type Item<'w> = Entity;
+    type State = *const Entity;
+
+    fn collect_access(_access: &mut Array<(TypeId, bool)>) { }
+
+    #[inline]
+    fn matches(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    #[inline]
+    unsafe fn prepare(archetype: &Archetype) -> Self::State {
+        archetype.get_entities_ptr()
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(state: Self::State, row: usize) -> Self::Item<'w> {
+        *state.add(row)
+    }
+}
+
+unsafe impl ReadOnlyQuery for Entity { }
+
+unsafe impl<T: Component> Query for &T {
+    type Item<'w> = &'w T;
+    type State = *const T;
+
+    fn collect_access(access: &mut Array<(TypeId, bool)>) {
+        access.push_back((TypeId::of::<T>(), false));
+    }
+
+    #[inline]
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has_component::<T>()
+    }
+
+    #[inline]
+    unsafe fn prepare(archetype: &Archetype) -> Self::State {
+        archetype.get_column_ptr(archetype.find_column(&TypeId::of::<T>())).cast::<T>()
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(state: Self::State, row: usize) -> Self::Item<'w> {
+        &*state.add(row)
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyQuery for &T { }
+
+unsafe impl<T: Component> Query for &mut T {
+    type Item<'w> = &'w mut T;
+    type State = *mut T;
+
+    fn collect_access(access: &mut Array<(TypeId, bool)>) {
+        access.push_back((TypeId::of::<T>(), true));
+    }
+
+    #[inline]
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has_component::<T>()
+    }
+
+    #[inline]
+    unsafe fn prepare(archetype: &Archetype) -> Self::State {
+        archetype.get_column_ptr(archetype.find_column(&TypeId::of::<T>())).cast::<T>()
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(state: Self::State, row: usize) -> Self::Item<'w> {
+        &mut *state.add(row)
+    }
+}
+
+unsafe impl<Q: Query> Query for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type State = Option<Q::State>;
+
+    fn collect_access(access: &mut Array<(TypeId, bool)>) {
+        Q::collect_access(access);
+    }
+
+    #[inline]
+    fn matches(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    #[inline]
+    unsafe fn prepare(archetype: &Archetype) -> Self::State {
+        if Q::matches(archetype) { Some(Q::prepare(archetype)) } else { None }
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(state: Self::State, row: usize) -> Self::Item<'w> {
+        state.map(|state| Q::fetch(state, row))
+    }
+}
+
+unsafe impl<Q: ReadOnlyQuery> ReadOnlyQuery for Option<Q> { }
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),+) => {
+        unsafe impl<$($name: Query),+> Query for ($($name,)+) {
+            type Item<'w> = ($($name::Item<'w>,)+);
+            type State = ($($name::State,)+);
+
+            fn collect_access(access: &mut Array<(TypeId, bool)>) {
+                $($name::collect_access(access);)+
+            }
+
+            #[inline]
+            fn matches(archetype: &Archetype) -> bool {
+                $($name::matches(archetype))&&+
+            }
+
+            #[inline]
+            unsafe fn prepare(archetype: &Archetype) -> Self::State {
+                ($($name::prepare(archetype),)+)
+            }
+
+            #[inline]
+            #[allow(non_snake_case)]
+            unsafe fn fetch<'w>(state: Self::State, row: usize) -> Self::Item<'w> {
+                let ($($name,)+) = state;
+                ($($name::fetch($name, row),)+)
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyQuery),+> ReadOnlyQuery for ($($name,)+) { }
+
+        impl<$($name: QueryFilter),+> QueryFilter for ($($name,)+) {
+            #[inline]
+            fn matches(archetype: &Archetype) -> bool {
+                $($name::matches(archetype))&&+
+            }
+        }
+    }
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+// Panics if the same component is requested mutably more than once, or both mutably and immutably
+pub(crate) fn check_query_access<Q: Query>() {
+    let mut access = Array::new();
+    Q::collect_access(&mut access);
+    for (i, (type_id, is_mut)) in access.iter().enumerate() {
+        for (other_type_id, other_is_mut) in access[i + 1..].iter() {
+            assert!(type_id != other_type_id || !(*is_mut || *other_is_mut),
+                "Query {} aliases a mutable component", std::any::type_name::<Q>());
+        }
+    }
+}
+
+pub struct QueryIter<'w, Q: Query, F: QueryFilter = ()> {
+    archetypes: &'w [Archetype],
+    archetype_index: usize,
+    state: Option<Q::State>,
+    row: usize,
+    rows_num: usize,
+    marker: PhantomData<F>,
+}
+
+impl<'w, Q: Query, F: QueryFilter> QueryIter<'w, Q, F> {
+    // The caller guarantees the borrow of the archetypes matches the query access
+    #[inline]
+    pub(crate) unsafe fn new(archetypes: &'w [Archetype]) -> Self {
+        QueryIter { archetypes, archetype_index: 0, state: None, row: 0, rows_num: 0, marker: PhantomData }
+    }
+}
+
+impl<'w, Q: Query, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(state) = self.state {
+                if self.row < self.rows_num {
+                    let row = self.row;
+                    self.row += 1;
+                    return Some(unsafe{ Q::fetch(state, row) });
+                }
+                self.state = None;
+            }
+
+            let archetype = self.archetypes.get(self.archetype_index)?;
+            self.archetype_index += 1;
+            if !archetype.is_empty() && Q::matches(archetype) && F::matches(archetype) {
+                self.state = Some(unsafe{ Q::prepare(archetype) });
+                self.row = 0;
+                self.rows_num = archetype.num();
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use core::fmt;
+use std::any::TypeId;
+use std::time::{Duration, Instant};
+
+use rl_core::{Array, BitArray, JobSystem, StringAtom};
+
+use crate::query::check_query_access;
+use crate::{Component, Entity, Query, QueryFilter, QueryIter, World};
+
+// Components a system reads and writes, systems with disjoint access can run at the same time
+#[derive(Clone, Default)]
+pub struct SystemAccess {
+    reads: Array<TypeId>,
+    writes: Array<TypeId>,
+    exclusive: bool,
+}
+
+impl SystemAccess {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Needs the whole World, conflicts with every other system
+    #[inline]
+    pub fn exclusive() -> Self {
+        SystemAccess { exclusive: true, ..Self::default() }
+    }
+
+    pub fn add_read(&mut self, type_id: TypeId) {
+        if !self.reads.contains(&type_id) {
+            self.reads.push_back(type_id);
+        }
+    }
+
+    pub fn add_write(&mut self, type_id: TypeId) {
+        if !self.writes.contains(&type_id) {
+            self.writes.push_back(type_id);
+        }
+    }
+
+    pub fn add_query<Q: Query>(&mut self) {
+        let mut access = Array::new();
+        Q::collect_access(&mut access);
+        for &(type_id, is_mut) in access.iter() {
+            if is_mut { self.add_write(type_id) } else { self.add_read(type_id) }
+        }
+    }
+
+    #[inline]
+    pub fn get_reads(&self) -> &[TypeId] {
+        &self.reads
+    }
+
+    #[inline]
+    pub fn get_writes(&self) -> &[TypeId] {
+        &self.writes
+    }
+
+    #[inline]
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    #[inline]
+    pub fn can_read(&self, type_id: &TypeId) -> bool {
+        self.exclusive || self.reads.contains(type_id) || self.writes.contains(type_id)
+    }
+
+    #[inline]
+    pub fn can_write(&self, type_id: &TypeId) -> bool {
+        self.exclusive || self.writes.contains(type_id)
+    }
+
+    pub fn conflicts_with(&self, other: &SystemAccess) -> bool {
+        self.exclusive
+            || other.exclusive
+            || self.writes.iter().any(|type_id| other.reads.contains(type_id) || other.writes.contains(type_id))
+            || other.writes.iter().any(|type_id| self.reads.contains(type_id))
+    }
+}
+
+// World view given to a parallel system, limited to the declared access
+pub struct SystemWorld<'w> {
+    world: &'w World,
+    access: &'w SystemAccess,
+    name: StringAtom,
+}
+
+impl<'w> SystemWorld<'w> {
+    #[inline]
+    pub fn get_name(&self) -> StringAtom {
+        self.name
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.world.num()
+    }
+
+    #[inline]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.world.is_alive(entity)
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        assert!(self.access.can_read(&TypeId::of::<T>()),
+            "System {} reads {} without declaring it", self.name, std::any::type_name::<T>());
+        self.world.get::<T>(entity)
+    }
+
+    #[inline]
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    pub fn query_filtered<Q: Query, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        check_query_access::<Q>();
+
+        let mut access = Array::new();
+        Q::collect_access(&mut access);
+        for (type_id, is_mut) in access.iter() {
+            let allowed = if *is_mut { self.access.can_write(type_id) } else { self.access.can_read(type_id) };
+            assert!(allowed, "System {} runs query {} outside of its declared access", self.name, std::any::type_name::<Q>());
+        }
+
+        // Systems sharing a stage never conflict, and the borrow of self keeps queries from overlapping
+        unsafe{ self.world.query_unchecked::<Q, F>() }
+    }
+}
+
+enum SystemFunction {
+    Parallel(Box<dyn FnMut(&mut SystemWorld) + Send>),
+    Exclusive(Box<dyn FnMut(&mut World) + Send>),
+}
+
+pub struct System {
+    name: StringAtom,
+    function: SystemFunction,
+    access: SystemAccess,
+    after: Array<StringAtom>,
+    before: Array<StringAtom>,
+}
+
+impl System {
+    pub fn new<F>(name: &str, function: F) -> Self where
+        F: FnMut(&mut SystemWorld) + Send + 'static
+    {
+        System {
+            name: name.into(),
+            function: SystemFunction::Parallel(Box::new(function)),
+            access: SystemAccess::new(),
+            after: Array::new(),
+            before: Array::new(),
+        }
+    }
+
+    pub fn exclusive<F>(name: &str, function: F) -> Self where
+        F: FnMut(&mut World) + Send + 'static
+    {
+        System {
+            name: name.into(),
+            function: SystemFunction::Exclusive(Box::new(function)),
+            access: SystemAccess::exclusive(),
+            after: Array::new(),
+            before: Array::new(),
+        }
+    }
+
+    #[inline]
+    pub fn reads<T: Component>(mut self) -> Self {
+        self.access.add_read(TypeId::of::<T>());
+        self
+    }
+
+    #[inline]
+    pub fn writes<T: Component>(mut self) -> Self {
+        self.access.add_write(TypeId::of::<T>());
+        self
+    }
+
+    #[inline]
+    pub fn with_query<Q: Query>(mut self) -> Self {
+        self.access.add_query::<Q>();
+        self
+    }
+
+    #[inline]
+    pub fn after(mut self, name: &str) -> Self {
+        self.after.push_back(name.into());
+        self
+    }
+
+    #[inline]
+    pub fn before(mut self, name: &str) -> Self {
+        self.before.push_back(name.into());
+        self
+    }
+
+    #[inline]
+    pub fn get_name(&self) -> StringAtom {
+        self.name
+    }
+
+    #[inline]
+    pub fn get_access(&self) -> &SystemAccess {
+        &self.access
+    }
+
+    fn run(&mut self, world: &World) {
+        match &mut self.function {
+            SystemFunction::Parallel(function) => {
+                let mut system_world = SystemWorld { world, access: &self.access, name: self.name };
+                function(&mut system_world);
+            },
+            SystemFunction::Exclusive(_) => unreachable!("Exclusive systems need a mutable World"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SystemTiming {
+    pub last: Duration,
+    pub average: Duration,
+}
+
+impl SystemTiming {
+    fn add_sample(&mut self, duration: Duration) {
+        // Exponential moving average, stable enough to display every frame
+        self.average = if self.average.is_zero() { duration } else { (self.average * 15 + duration) / 16 };
+        self.last = duration;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    DuplicatedSystem(StringAtom),
+    UnknownSystem(StringAtom),
+    Cycle(Array<StringAtom>),
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::DuplicatedSystem(name) => write!(f, "system {} is added twice", name),
+            ScheduleError::UnknownSystem(name) => write!(f, "ordering refers to unknown system {}", name),
+            ScheduleError::Cycle(names) => {
+                write!(f, "ordering cycle between systems")?;
+                for name in names.iter() {
+                    write!(f, " {}", name)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError { }
+
+// Systems are ordered by explicit before/after constraints, conflicting systems without one
+// keep the insertion order. Each stage only holds systems that can run in parallel.
+#[derive(Default)]
+pub struct Schedule {
+    systems: Array<System>,
+    timings: Array<SystemTiming>,
+    stages: Array<Array<usize>>,
+    ambiguities: Array<(StringAtom, StringAtom)>,
+    is_built: bool,
+}
+
+impl Schedule {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_system(&mut self, system: System) -> &mut Self {
+        self.systems.push_back(system);
+        self.timings.push_back(SystemTiming::default());
+        self.is_built = false;
+        self
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.systems.num()
+    }
+
+    fn find_system(&self, name: StringAtom) -> Result<usize, ScheduleError> {
+        self.systems
+            .iter()
+            .position(|system| system.name == name)
+            .ok_or(ScheduleError::UnknownSystem(name))
+    }
+
+    pub fn build(&mut self) -> Result<(), ScheduleError> {
+        let systems_num = self.systems.num();
+        for (i, system) in self.systems.iter().enumerate() {
+            if self.systems[..i].iter().any(|other| other.name == system.name) {
+                return Err(ScheduleError::DuplicatedSystem(system.name));
+            }
+        }
+
+        // Explicit edges, then the transitive closure in reachable[i] (systems that must run after i)
+        let mut successors: Array<Array<usize>> = (0..systems_num).map(|_| Array::new()).collect();
+        for (i, system) in self.systems.iter().enumerate() {
+            for name in system.after.iter() {
+                successors[self.find_system(*name)?].push_back(i);
+            }
+            for name in system.before.iter() {
+                successors[i].push_back(self.find_system(*name)?);
+            }
+        }
+
+        let order = topological_order(&successors).map_err(|cycle| {
+            ScheduleError::Cycle(cycle.iter().map(|&i| self.systems[i].name).collect())
+        })?;
+
+        let mut reachable: Array<BitArray> = (0..systems_num).map(|_| BitArray::with_num(systems_num, false)).collect();
+        for &i in order.iter().rev() {
+            for &successor in successors[i].iter() {
+                let successor_reachable = reachable[successor].clone();
+                reachable[i] |= &successor_reachable;
+                reachable[i].set(successor);
+            }
+        }
+
+        // Conflicting systems without an ordering path are ambiguous, order them by insertion
+        self.ambiguities.clear();
+        for i in 0..systems_num {
+            for j in i + 1..systems_num {
+                if !self.systems[i].access.conflicts_with(&self.systems[j].access) {
+                    continue;
+                }
+                if reachable[i].get(j) || reachable[j].get(i) {
+                    continue;
+                }
+
+                if cfg!(debug_assertions) {
+                    self.ambiguities.push_back((self.systems[i].name, self.systems[j].name));
+                }
+
+                successors[i].push_back(j);
+                let mut after_j = reachable[j].clone();
+                after_j.set(j);
+                for k in 0..systems_num {
+                    if k == i || reachable[k].get(i) {
+                        reachable[k] |= &after_j;
+                    }
+                }
+            }
+        }
+
+        // Each system goes one stage after the latest of its predecessors
+        let order = topological_order(&successors).expect("Implicit orderings can't make cycles");
+        let mut system_stages: Array<usize> = (0..systems_num).map(|_| 0).collect();
+        for &i in order.iter() {
+            for &successor in successors[i].iter() {
+                system_stages[successor] = system_stages[successor].max(system_stages[i] + 1);
+            }
+        }
+
+        self.stages.clear();
+        for (i, &stage) in system_stages.iter().enumerate() {
+            while self.stages.num() <= stage {
+                self.stages.push_back(Array::new());
+            }
+            self.stages[stage].push_back(i);
+        }
+
+        self.is_built = true;
+        Ok(())
+    }
+
+    // Builds the schedule first if systems changed, panics on ordering errors
+    pub fn run(&mut self, world: &mut World) {
+        if !self.is_built {
+            if let Err(error) = self.build() {
+                panic!("Invalid schedule: {}", error);
+            }
+        }
+
+        for stage in self.stages.iter() {
+            if let [system_index] = stage.as_slice() {
+                let system = &mut self.systems[*system_index];
+                let start = Instant::now();
+                match &mut system.function {
+                    SystemFunction::Exclusive(function) => function(world),
+                    SystemFunction::Parallel(_) => system.run(world),
+                }
+                self.timings[*system_index].add_sample(start.elapsed());
+                continue;
+            }
+
+            // Systems of a stage don't conflict, they all share the World
+            let world: &World = world;
+            let mut stage_systems: Array<(&mut System, &mut SystemTiming)> = self.systems
+                .iter_mut()
+                .zip(self.timings.iter_mut())
+                .enumerate()
+                .filter(|(i, _)| stage.contains(i))
+                .map(|(_, pair)| pair)
+                .collect();
+
+            JobSystem::global().scope(|scope| {
+                let (first, others) = stage_systems.split_first_mut().unwrap();
+                for (system, timing) in others.iter_mut() {
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        system.run(world);
+                        timing.add_sample(start.elapsed());
+                    });
+                }
+
+                let start = Instant::now();
+                first.0.run(world);
+                first.1.add_sample(start.elapsed());
+            });
+        }
+    }
+
+    // System names per stage, in execution order
+    pub fn get_stages(&self) -> impl Iterator<Item = Array<StringAtom>> + '_ {
+        self.stages.iter().map(|stage| stage.iter().map(|&i| self.systems[i].name).collect())
+    }
+
+    // Conflicting pairs ordered only by insertion, only collected in debug builds
+    #[inline]
+    pub fn get_ambiguities(&self) -> &[(StringAtom, StringAtom)] {
+        &self.ambiguities
+    }
+
+    pub fn get_timings(&self) -> impl Iterator<Item = (StringAtom, SystemTiming)> + '_ {
+        self.systems.iter().zip(self.timings.iter()).map(|(system, timing)| (system.name, *timing))
+    }
+}
+
+// Kahn's algorithm, lowest index first among ready nodes. Err holds the nodes left in a cycle.
+fn topological_order(successors: &[Array<usize>]) -> Result<Array<usize>, Array<usize>> {
+    let nodes_num = successors.len();
+    let mut predecessors_num: Array<usize> = (0..nodes_num).map(|_| 0).collect();
+    for node_successors in successors.iter() {
+        for &successor in node_successors.iter() {
+            predecessors_num[successor] += 1;
+        }
+    }
+
+    let mut ready: Array<usize> = (0..nodes_num).rev().filter(|&i| predecessors_num[i] == 0).collect();
+    let mut order = Array::with_capacity(nodes_num);
+    while !ready.is_empty() {
+        let node = ready.pop_back();
+        order.push_back(node);
+        for &successor in successors[node].iter() {
+            predecessors_num[successor] -= 1;
+            if predecessors_num[successor] == 0 {
+                ready.push_back(successor);
+            }
+        }
+    }
+
+    if order.num() == nodes_num {
+        Ok(order)
+    } else {
+        Err((0..nodes_num).filter(|&i| predecessors_num[i] > 0).collect())
+    }
+}
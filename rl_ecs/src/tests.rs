@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use std::sync::Mutex;
+
+use rl_core::StringAtom;
+
+use crate::{Entity, World, With, Without, System, Schedule, ScheduleError};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Position(f32, f32, f32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Velocity(f32, f32, f32);
+
+// Stand-ins for the render side types, anything Send + Sync can be a component
+#[derive(Clone, Debug, PartialEq)]
+enum SDFShape {
+    Sphere { radius: f32 },
+    Box { half_size: [f32; 3] },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct SDFPrimitive {
+    shape: SDFShape,
+    group_id: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Camera {
+    fov: f32,
+    near: f32,
+}
+
+#[test]
+fn entity_test() {
+    let mut world = World::new();
+    let entity0 = world.spawn();
+    let entity1 = world.spawn();
+    assert_ne!(entity0, entity1);
+    assert_eq!(world.num(), 2);
+
+    assert!(world.despawn(entity0));
+    assert!(!world.despawn(entity0));
+    assert!(!world.is_alive(entity0));
+
+    // The index is reused with a new generation, the stale id stays dead
+    let entity2 = world.spawn();
+    assert_eq!(entity2.get_index(), entity0.get_index());
+    assert_ne!(entity2, entity0);
+    assert!(world.is_alive(entity2));
+    assert!(!world.is_alive(entity0));
+    assert!(world.get::<Position>(entity0).is_none());
+    assert!(Entity::NONE.is_none());
+}
+
+#[test]
+fn archetype_moves_test() {
+    let mut world = World::new();
+    let entities: Vec<Entity> = (0..10).map(|i| world.spawn_with((Position(i as f32, 0.0, 0.0),))).collect();
+
+    for entity in entities.iter().step_by(2) {
+        world.insert(*entity, Velocity(1.0, 0.0, 0.0));
+    }
+    world.insert(entities[0], Velocity(2.0, 0.0, 0.0));
+    assert_eq!(world.get::<Velocity>(entities[0]), Some(&Velocity(2.0, 0.0, 0.0)));
+
+    for (i, entity) in entities.iter().enumerate() {
+        assert_eq!(world.get::<Position>(*entity), Some(&Position(i as f32, 0.0, 0.0)));
+        assert_eq!(world.has::<Velocity>(*entity), i % 2 == 0);
+    }
+
+    assert_eq!(world.remove::<Velocity>(entities[4]), Some(Velocity(1.0, 0.0, 0.0)));
+    assert_eq!(world.remove::<Velocity>(entities[4]), None);
+    assert!(world.despawn(entities[2]));
+    for (i, entity) in entities.iter().enumerate().filter(|(i, _)| *i != 2) {
+        assert_eq!(world.get::<Position>(*entity), Some(&Position(i as f32, 0.0, 0.0)));
+    }
+
+    // Components are dropped exactly once whatever path they take
+    let counter = Arc::new(());
+    let tracked = world.spawn_with((Position(0.0, 0.0, 0.0), counter.clone()));
+    world.insert(tracked, Velocity(0.0, 0.0, 0.0));
+    world.remove::<Position>(tracked);
+    assert_eq!(Arc::strong_count(&counter), 2);
+    let taken = world.remove::<Arc<()>>(tracked).unwrap();
+    assert_eq!(Arc::strong_count(&counter), 2);
+    drop(taken);
+    world.insert(tracked, counter.clone());
+    world.despawn(tracked);
+    world.spawn_with((counter.clone(), Velocity(0.0, 0.0, 0.0)));
+    world.clear();
+    assert_eq!(Arc::strong_count(&counter), 1);
+    assert!(world.is_empty());
+}
+
+#[test]
+fn query_test() {
+    let mut world = World::new();
+    let camera = world.spawn_with((Position(0.0, 1.0, -5.0), Camera { fov: 60.0, near: 0.1 }));
+    for i in 0..4 {
+        world.spawn_with((
+            Position(i as f32, 0.0, 0.0),
+            SDFPrimitive { shape: SDFShape::Sphere { radius: 1.0 }, group_id: i },
+        ));
+    }
+    let moving = world.spawn_with((
+        Position(0.0, 0.0, 0.0),
+        Velocity(0.0, 1.0, 0.0),
+        SDFPrimitive { shape: SDFShape::Box { half_size: [1.0; 3] }, group_id: 7 },
+    ));
+
+    for (position, primitive) in world.query::<(&Position, &mut SDFPrimitive)>() {
+        primitive.group_id += position.0 as u32 * 10;
+    }
+    let mut group_ids: Vec<u32> = world.query_ref::<&SDFPrimitive>().map(|primitive| primitive.group_id).collect();
+    group_ids.sort();
+    assert_eq!(group_ids, [0, 7, 11, 22, 33]);
+
+    for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+        position.1 += velocity.1;
+    }
+    assert_eq!(world.get::<Position>(moving), Some(&Position(0.0, 1.0, 0.0)));
+
+    let static_primitives = world.query_ref_filtered::<(Entity, &SDFPrimitive), Without<Velocity>>().count();
+    assert_eq!(static_primitives, 4);
+    let cameras: Vec<Entity> = world.query_ref_filtered::<Entity, (With<Camera>, With<Position>)>().collect();
+    assert_eq!(cameras, [camera]);
+
+    let with_optional: Vec<(Entity, Option<&Camera>)> = world
+        .query_ref::<(Entity, Option<&Camera>)>()
+        .filter(|(_, camera)| camera.is_some())
+        .collect();
+    assert_eq!(with_optional.len(), 1);
+    assert_eq!(with_optional[0].1.unwrap().fov, 60.0);
+    assert_eq!(world.query_ref::<&Position>().count(), 6);
+}
+
+#[test]
+#[should_panic]
+fn query_aliasing_test() {
+    let mut world = World::new();
+    world.spawn_with((Position(0.0, 0.0, 0.0),));
+    let _ = world.query::<(&mut Position, &Position)>().count();
+}
+
+#[test]
+fn schedule_test() {
+    let mut world = World::new();
+    for i in 0..100 {
+        world.spawn_with((Position(i as f32, 0.0, 0.0), Velocity(1.0, 0.0, 0.0)));
+    }
+    world.spawn_with((Position(0.0, 0.0, 0.0), Camera { fov: 60.0, near: 0.1 }));
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut schedule = Schedule::new();
+
+    let system_log = log.clone();
+    schedule.add_system(System::new("integrate", move |world| {
+        for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+            position.0 += velocity.0;
+        }
+        system_log.lock().unwrap().push("integrate");
+    }).with_query::<(&mut Position, &Velocity)>());
+
+    let system_log = log.clone();
+    schedule.add_system(System::new("camera", move |world| {
+        for camera in world.query::<&mut Camera>() {
+            camera.fov = 90.0;
+        }
+        system_log.lock().unwrap().push("camera");
+    }).writes::<Camera>().after("spawner"));
+
+    let system_log = log.clone();
+    schedule.add_system(System::new("bounds", move |world| {
+        let max_x = world.query::<&Position>().map(|position| position.0).fold(0.0, f32::max);
+        assert_eq!(max_x, 100.0);
+        system_log.lock().unwrap().push("bounds");
+    }).reads::<Position>().after("integrate"));
+
+    let system_log = log.clone();
+    schedule.add_system(System::exclusive("spawner", move |world| {
+        world.spawn_with((Position(0.0, 0.0, 0.0),));
+        system_log.lock().unwrap().push("spawner");
+    }).before("integrate"));
+
+    schedule.build().unwrap();
+    let stages: Vec<Vec<StringAtom>> = schedule.get_stages().map(|stage| stage.iter().copied().collect()).collect();
+    assert_eq!(stages.len(), 3);
+    assert_eq!(stages[0], [StringAtom::from("spawner")]);
+    assert_eq!(stages[1], [StringAtom::from("integrate"), StringAtom::from("camera")]);
+    assert_eq!(stages[2], [StringAtom::from("bounds")]);
+    assert!(schedule.get_ambiguities().is_empty());
+
+    schedule.run(&mut world);
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 4);
+    assert_eq!(log[0], "spawner");
+    assert_eq!(log[3], "bounds");
+    assert_eq!(world.num(), 102);
+    assert_eq!(world.query_ref::<&Camera>().next().unwrap().fov, 90.0);
+    assert!(schedule.get_timings().all(|(_, timing)| timing.average == timing.last));
+}
+
+#[test]
+fn schedule_ordering_test() {
+    // Conflicting systems without an explicit ordering keep the insertion order and are reported
+    let mut schedule = Schedule::new();
+    schedule.add_system(System::new("a", |_| {}).writes::<Position>());
+    schedule.add_system(System::new("b", |_| {}).reads::<Position>());
+    schedule.add_system(System::new("c", |_| {}).reads::<Position>().before("a"));
+    schedule.build().unwrap();
+
+    let stages: Vec<Vec<StringAtom>> = schedule.get_stages().map(|stage| stage.iter().copied().collect()).collect();
+    assert_eq!(stages, [vec![StringAtom::from("c")], vec![StringAtom::from("a")], vec![StringAtom::from("b")]]);
+    if cfg!(debug_assertions) {
+        assert_eq!(schedule.get_ambiguities(), [(StringAtom::from("a"), StringAtom::from("b"))]);
+    }
+
+    schedule.add_system(System::new("d", |_| {}).after("a").before("c"));
+    assert!(matches!(schedule.build(), Err(ScheduleError::Cycle(_))));
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(System::new("a", |_| {}).after("missing"));
+    assert_eq!(schedule.build(), Err(ScheduleError::UnknownSystem("missing".into())));
+}
+
+#[test]
+#[should_panic]
+fn schedule_undeclared_access_test() {
+    let mut world = World::new();
+    world.spawn_with((Position(0.0, 0.0, 0.0),));
+    let mut schedule = Schedule::new();
+    schedule.add_system(System::new("reader", |world| {
+        let _ = world.query::<&mut Position>().count();
+    }).reads::<Position>());
+    schedule.run(&mut world);
+}
@@ -0,0 +1,287 @@
+use std::any::TypeId;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use rl_core::{Array, Map, TypeInfo, FastHash};
+
+use crate::entity::{Entities, EntityLocation};
+use crate::query::check_query_access;
+use crate::{Archetype, Bundle, Component, Entity, Query, QueryFilter, QueryIter, ReadOnlyQuery, component_type_info};
+
+const EMPTY_ARCHETYPE: usize = 0;
+
+pub struct World {
+    entities: Entities,
+    archetypes: Array<Archetype>,
+    archetypes_by_hash: Map<usize, Array<usize>>, // hash of the sorted component types
+}
+
+// Components are Send + Sync
+unsafe impl Send for World { }
+unsafe impl Sync for World { }
+
+impl World {
+    pub fn new() -> Self {
+        let mut world = World {
+            entities: Entities::default(),
+            archetypes: Array::new(),
+            archetypes_by_hash: Map::new(),
+        };
+        let empty_archetype = world.find_or_add_archetype(&[]);
+        debug_assert!(empty_archetype == EMPTY_ARCHETYPE);
+        world
+    }
+
+    #[inline]
+    pub fn num(&self) -> usize {
+        self.entities.num()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.num() == 0
+    }
+
+    #[inline]
+    pub fn get_archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    #[inline]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    // type_infos must be sorted by type id
+    fn find_or_add_archetype(&mut self, type_infos: &[&'static TypeInfo]) -> usize {
+        let types: Array<TypeId> = type_infos.iter().map(|type_info| *type_info.get_id()).collect();
+        let hash = types.as_slice().fast_hash();
+
+        if let Some(candidates) = self.archetypes_by_hash.get(&hash) {
+            for &archetype in candidates.iter() {
+                if self.archetypes[archetype].get_types() == types.as_slice() {
+                    return archetype;
+                }
+            }
+        }
+
+        let archetype = self.archetypes.num();
+        self.archetypes.push_back(Archetype::new(type_infos));
+        self.archetypes_by_hash.get_or_insert_default_mut(hash).push_back(archetype);
+        archetype
+    }
+
+    fn archetype_type_infos(&self, archetype: usize) -> Array<&'static TypeInfo> {
+        let archetype = &self.archetypes[archetype];
+        (0..archetype.get_types().len()).map(|column| archetype.get_type_info(column)).collect()
+    }
+
+    fn archetype_with(&mut self, archetype: usize, type_info: &'static TypeInfo) -> usize {
+        let type_id = *type_info.get_id();
+        if let Some(target) = self.archetypes[archetype].get_add_edge(&type_id) {
+            return target;
+        }
+
+        let mut type_infos = self.archetype_type_infos(archetype);
+        type_infos.push_back(type_info);
+        type_infos.sort_by_key(|type_info| *type_info.get_id());
+        let target = self.find_or_add_archetype(&type_infos);
+
+        self.archetypes[archetype].set_add_edge(type_id, target);
+        self.archetypes[target].set_remove_edge(type_id, archetype);
+        target
+    }
+
+    fn archetype_without(&mut self, archetype: usize, type_id: TypeId) -> usize {
+        if let Some(target) = self.archetypes[archetype].get_remove_edge(&type_id) {
+            return target;
+        }
+
+        let type_infos: Array<&'static TypeInfo> = self.archetype_type_infos(archetype)
+            .iter()
+            .copied()
+            .filter(|type_info| *type_info.get_id() != type_id)
+            .collect();
+        let target = self.find_or_add_archetype(&type_infos);
+
+        self.archetypes[archetype].set_remove_edge(type_id, target);
+        self.archetypes[target].set_add_edge(type_id, archetype);
+        target
+    }
+
+    fn two_archetypes_mut(&mut self, a: usize, b: usize) -> (&mut Archetype, &mut Archetype) {
+        debug_assert!(a != b);
+        if a < b {
+            let (left, right) = self.archetypes.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        } else {
+            let (left, right) = self.archetypes.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        }
+    }
+
+    // Moves the entity row to another archetype, shared components are moved, the others are dropped
+    // except the one written in removed_dst if not null
+    unsafe fn move_entity(&mut self, entity: Entity, location: EntityLocation, target: usize, removed_dst: *mut u8) -> usize {
+        let source = location.archetype as usize;
+        let row = location.row as usize;
+        let (source_archetype, target_archetype) = self.two_archetypes_mut(source, target);
+
+        for column in 0..source_archetype.get_types().len() {
+            let type_id = source_archetype.get_types()[column];
+            let target_column = target_archetype.find_column(&type_id);
+            if target_column != usize::MAX {
+                source_archetype.get_column_mut(column).swap_remove_into(row, target_archetype.get_column_mut(target_column));
+            } else if !removed_dst.is_null() {
+                source_archetype.get_column_mut(column).swap_remove_to_ptr(row, removed_dst);
+            } else {
+                source_archetype.get_column_mut(column).swap_remove(row);
+            }
+        }
+
+        let moved_entity = source_archetype.swap_remove_entity(row);
+        let target_row = target_archetype.push_entity(entity);
+
+        if let Some(moved_entity) = moved_entity {
+            self.entities.set_location(moved_entity, EntityLocation { archetype: location.archetype, row: location.row });
+        }
+        self.entities.set_location(entity, EntityLocation { archetype: target as u32, row: target_row as u32 });
+        target_row
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        let entity = self.entities.alloc();
+        let row = self.archetypes[EMPTY_ARCHETYPE].push_entity(entity);
+        self.entities.set_location(entity, EntityLocation { archetype: EMPTY_ARCHETYPE as u32, row: row as u32 });
+        entity
+    }
+
+    pub fn spawn_with<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let mut type_infos = Array::new();
+        B::collect_type_infos(&mut type_infos);
+        type_infos.sort_by_key(|type_info| *type_info.get_id());
+        assert!(type_infos.windows(2).all(|pair| pair[0].get_id() != pair[1].get_id()),
+            "Bundle {} has duplicated components", std::any::type_name::<B>());
+
+        let archetype_index = self.find_or_add_archetype(&type_infos);
+        let entity = self.entities.alloc();
+        let archetype = &mut self.archetypes[archetype_index];
+        unsafe {
+            bundle.write_components(|type_id, src| {
+                let column = archetype.find_column(&type_id);
+                archetype.get_column_mut(column).push_from_ptr(src);
+            });
+        }
+        let row = archetype.push_entity(entity);
+        self.entities.set_location(entity, EntityLocation { archetype: archetype_index as u32, row: row as u32 });
+        entity
+    }
+
+    // False if the entity was already despawned
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        let Some(location) = self.entities.get_location(entity) else {
+            return false;
+        };
+
+        if let Some(moved_entity) = self.archetypes[location.archetype as usize].swap_remove(location.row as usize) {
+            self.entities.set_location(moved_entity, location);
+        }
+        self.entities.free(entity);
+        true
+    }
+
+    // Replaces the component if the entity already has one
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) {
+        let location = self.entities.get_location(entity).expect("Inserting a component on a dead entity");
+
+        if let Some(existing) = self.get_mut::<T>(entity) {
+            *existing = component;
+            return;
+        }
+
+        let target = self.archetype_with(location.archetype as usize, component_type_info::<T>());
+        let row = unsafe{ self.move_entity(entity, location, target, ptr::null_mut()) };
+
+        let archetype = &mut self.archetypes[target];
+        let column = archetype.find_column(&TypeId::of::<T>());
+        let component_column = archetype.get_column_mut(column);
+        component_column.push(component);
+        debug_assert!(component_column.num() == row + 1);
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        let location = self.entities.get_location(entity)?;
+        let type_id = TypeId::of::<T>();
+        if !self.archetypes[location.archetype as usize].has(&type_id) {
+            return None;
+        }
+
+        let target = self.archetype_without(location.archetype as usize, type_id);
+        let mut removed = MaybeUninit::<T>::uninit();
+        unsafe {
+            self.move_entity(entity, location, target, removed.as_mut_ptr().cast());
+            Some(removed.assume_init())
+        }
+    }
+
+    #[inline]
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.entities
+            .get_location(entity)
+            .is_some_and(|location| self.archetypes[location.archetype as usize].has_component::<T>())
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let location = self.entities.get_location(entity)?;
+        let archetype = &self.archetypes[location.archetype as usize];
+        archetype.get_column::<T>().map(|column| &column[location.row as usize])
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        let location = self.entities.get_location(entity)?;
+        let archetype = &mut self.archetypes[location.archetype as usize];
+        let column = archetype.find_column(&TypeId::of::<T>());
+        if column == usize::MAX {
+            return None;
+        }
+        archetype.get_column_mut(column).get_mut::<T>(location.row as usize)
+    }
+
+    #[inline]
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    pub fn query_filtered<Q: Query, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        check_query_access::<Q>();
+        unsafe{ QueryIter::new(&self.archetypes) }
+    }
+
+    #[inline]
+    pub fn query_ref<Q: ReadOnlyQuery>(&self) -> QueryIter<'_, Q> {
+        self.query_ref_filtered::<Q, ()>()
+    }
+
+    pub fn query_ref_filtered<Q: ReadOnlyQuery, F: QueryFilter>(&self) -> QueryIter<'_, Q, F> {
+        unsafe{ QueryIter::new(&self.archetypes) }
+    }
+
+    // The caller guarantees no other live borrow conflicts with the query access
+    pub(crate) unsafe fn query_unchecked<Q: Query, F: QueryFilter>(&self) -> QueryIter<'_, Q, F> {
+        QueryIter::new(&self.archetypes)
+    }
+
+    // Despawns every entity, archetypes are kept
+    pub fn clear(&mut self) {
+        for archetype in self.archetypes.iter_mut() {
+            archetype.clear();
+        }
+        self.entities.clear();
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World::new()
+    }
+}
@@ -1,7 +1,9 @@
 use nalgebra_glm::{Vec3, Vec4, Mat4x3, min2, max2};//, all, less_than_equal, greater_than_equal};
+use rl_core::{Archive, ArchiveResult, Serializable};
 use crate::{VEC3_ONE, transform_vec4};
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AABB {
     pub min: Vec3,
     pub max: Vec3,
@@ -76,7 +78,7 @@ impl AABB {
     #[inline]
     pub fn transform(&self, xform: &Mat4x3) -> AABB {
         let mut new_aabb = Self::new();
-        for i in 0..7 {
+        for i in 0..8 {
             new_aabb.encapsulate_point(
                 &transform_vec4(xform, &Vec4::new(
                     if 1 == (i & 1) { self.min.x } else { self.max.x },
@@ -87,3 +89,10 @@ impl AABB {
         new_aabb
     }
 }
+
+impl Serializable for AABB {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        ar.serialize(&mut self.min)?;
+        ar.serialize(&mut self.max)
+    }
+}
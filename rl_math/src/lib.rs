@@ -8,7 +8,10 @@ mod aabb;
 
 pub use aabb::AABB;
 
-// TODO: make a Transform struct with Vec3 Location Quaternion Rotation Vec3 Scale because using glm directly is unbearable
+mod transform;
+
+pub use transform::Transform;
+pub use transform::DecomposeError;
 
 pub fn transform_vec4(m: &Mat4x3, v: &Vec4) -> Vec3 {
     Vec3::new(m.column(0).dot(v), m.column(1).dot(v), m.column(2).dot(v))
@@ -21,3 +24,6 @@ pub fn transform_vector(xform: &Mat4x3, vector: &Vec3) -> Vec3 {
 pub fn transform_point(xform: &Mat4x3, point: &Vec3) -> Vec3 {
     transform_vec4(xform, &Vec4::new(point.x, point.y, point.z, 1.0))
 }
+
+#[cfg(test)]
+mod tests;
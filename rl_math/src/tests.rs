@@ -0,0 +1,90 @@
+#[cfg(feature = "serde")]
+#[test]
+fn aabb_serde_test() {
+    use nalgebra_glm::Vec3;
+    use crate::AABB;
+
+    let aabb = AABB::from_min_max(Vec3::new(-1.0, 0.0, 2.5), Vec3::new(3.0, 4.0, 5.0));
+
+    let json = serde_json::to_string(&aabb).unwrap();
+    assert_eq!(json, r#"{"min":[-1.0,0.0,2.5],"max":[3.0,4.0,5.0]}"#);
+    let from_json: AABB = serde_json::from_str(&json).unwrap();
+    let from_ron: AABB = ron::from_str(&ron::to_string(&aabb).unwrap()).unwrap();
+    for loaded in [from_json, from_ron] {
+        assert_eq!(loaded.min, aabb.min);
+        assert_eq!(loaded.max, aabb.max);
+    }
+}
+
+#[test]
+fn transform_test() {
+    use nalgebra_glm::{Vec3, Mat4x4, inverse, look_at, quat_angle_axis, rotation, scaling, translation};
+    use crate::{AABB, DecomposeError, Transform, transform_point};
+
+    let close = |a: &Vec3, b: &Vec3| (a - b).norm() < 1e-4;
+    let close_matrix = |a: &Mat4x4, b: &Mat4x4| (a - b).norm() < 1e-4;
+
+    let rotation_z = quat_angle_axis(std::f32::consts::FRAC_PI_2, &Vec3::z());
+    let xform = Transform::new(Vec3::new(1.0, 2.0, 3.0), rotation_z, Vec3::new(2.0, 3.0, 4.0));
+    let matrix = translation(&xform.location) * rotation(std::f32::consts::FRAC_PI_2, &Vec3::z()) * scaling(&xform.scale);
+    assert!(close_matrix(&xform.to_mat4x4(), &matrix));
+    assert!(close_matrix(&xform.to_inverse_mat4x4(), &inverse(&matrix)));
+
+    // Mat4x3 columns are the rows used by transform_point and the shaders
+    let point = Vec3::new(1.0, 1.0, 1.0);
+    assert!(close(&xform.transform_point(&point), &Vec3::new(-2.0, 4.0, 7.0)));
+    assert!(close(&transform_point(&xform.to_mat4x3(), &point), &Vec3::new(-2.0, 4.0, 7.0)));
+    assert!(close(&transform_point(&xform.to_inverse_mat4x3(), &Vec3::new(-2.0, 4.0, 7.0)), &point));
+    assert!(close(&xform.inverse_transform_point(&Vec3::new(-2.0, 4.0, 7.0)), &point));
+    assert!(close(&xform.transform_vector(&point), &Vec3::new(-3.0, 2.0, 4.0)));
+
+    // Composition applies the right side first, the inverse is exact with a uniform scale
+    let parent = Transform::new(Vec3::new(0.0, 0.0, 5.0), rotation_z, Vec3::new(2.0, 2.0, 2.0));
+    let child = Transform::from_location(Vec3::new(1.0, 0.0, 0.0));
+    assert!(close(&(parent * child).transform_point(&point), &parent.transform_point(&child.transform_point(&point))));
+    assert!(close_matrix(&(parent * parent.inverse()).to_mat4x4(), &Mat4x4::identity()));
+
+    let decomposed = Transform::from_mat4x4(&matrix).unwrap();
+    assert!(close(&decomposed.location, &xform.location) && close(&decomposed.scale, &xform.scale));
+    assert!(close_matrix(&decomposed.to_mat4x4(), &matrix));
+    assert!(close_matrix(&Transform::from_mat4x3(&xform.to_mat4x3()).unwrap().to_mat4x4(), &matrix));
+    let mirrored = Transform::from_mat4x4(&scaling(&Vec3::new(1.0, -1.0, 1.0))).unwrap();
+    assert!(close(&mirrored.scale, &Vec3::new(-1.0, 1.0, 1.0)) && close_matrix(&mirrored.to_mat4x4(), &scaling(&Vec3::new(1.0, -1.0, 1.0))));
+    let mut sheared = Mat4x4::identity();
+    sheared[(0, 1)] = 0.5;
+    assert_eq!(Transform::from_mat4x4(&sheared), Err(DecomposeError::Shear));
+    assert_eq!(Transform::from_mat4x4(&scaling(&Vec3::new(1.0, 0.0, 1.0))), Err(DecomposeError::ZeroScale));
+    let mut projective = Mat4x4::identity();
+    projective[(3, 2)] = -1.0;
+    assert_eq!(Transform::from_mat4x4(&projective), Err(DecomposeError::Projective));
+
+    let aabb = xform.transform_aabb(&AABB::from_min_max(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)));
+    assert!(close(&aabb.min, &Vec3::new(-2.0, 0.0, -1.0)) && close(&aabb.max, &Vec3::new(4.0, 4.0, 7.0)));
+    let from_matrix = AABB::from_min_max(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)).transform(&xform.to_mat4x3());
+    assert!(close(&from_matrix.min, &aabb.min) && close(&from_matrix.max, &aabb.max));
+
+    // Halfway of a quarter turn, both interpolations agree on the middle
+    let start = Transform::identity();
+    let end = Transform::new(Vec3::new(2.0, 0.0, 0.0), rotation_z, Vec3::new(3.0, 3.0, 3.0));
+    for middle in [start.lerp(&end, 0.5), start.slerp(&end, 0.5)] {
+        assert!(close(&middle.location, &Vec3::new(1.0, 0.0, 0.0)) && close(&middle.scale, &Vec3::new(2.0, 2.0, 2.0)));
+        assert!(close(&middle.transform_vector(&Vec3::x()), &(Vec3::new(1.0, 1.0, 0.0).normalize() * 2.0)));
+    }
+    assert!(close(&start.slerp(&end, 0.25).transform_vector(&Vec3::x()), &(Vec3::new(std::f32::consts::FRAC_PI_8.cos(), std::f32::consts::FRAC_PI_8.sin(), 0.0) * 1.5)));
+
+    let eye = Vec3::new(1.0, 2.0, 3.0);
+    let camera = Transform::look_at(&eye, &Vec3::new(4.0, -1.0, 0.0), &Vec3::y());
+    assert!(close_matrix(&camera.to_mat4x4(), &inverse(&look_at(&eye, &Vec3::new(4.0, -1.0, 0.0), &Vec3::y()))));
+}
+
+#[test]
+fn aabb_transform_test() {
+    use nalgebra_glm::{Vec3, Vec4, Mat4x3};
+    use crate::AABB;
+
+    // x' = x + y + z reaches its minimum on the min corner only, so all 8 corners must be transformed
+    let sum = Mat4x3::from_columns(&[Vec4::new(1.0, 1.0, 1.0, 0.0), Vec4::new(0.0, 1.0, 0.0, 0.0), Vec4::new(0.0, 0.0, 1.0, 0.0)]);
+    let aabb = AABB::from_min_max(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)).transform(&sum);
+    assert_eq!(aabb.min, Vec3::new(0.0, 0.0, 0.0));
+    assert_eq!(aabb.max, Vec3::new(3.0, 1.0, 1.0));
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::ops::Mul;
+use nalgebra_glm::{Vec3, Vec4, Mat3, Mat4x3, Mat4x4, Quat, abs, cross, dot, length, lerp, mat3_to_quat, normalize, quat_conjugate, quat_identity, quat_normalize, quat_rotate_vec3, quat_to_mat3};
+use rl_core::{Archive, ArchiveResult, Serializable};
+use crate::{AABB, VEC3_ONE, VEC3_ZERO};
+
+// Below it a scale is zero and two axes are considered perpendicular
+const DECOMPOSE_EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecomposeError {
+    Projective,
+    ZeroScale,
+    Shear,
+}
+
+impl fmt::Display for DecomposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecomposeError::Projective => write!(f, "The matrix has a projection"),
+            DecomposeError::ZeroScale => write!(f, "The matrix has a zero scale"),
+            DecomposeError::Shear => write!(f, "The matrix has a shear"),
+        }
+    }
+}
+
+impl std::error::Error for DecomposeError { }
+
+/// Scale, then rotation, then location.
+/// Mat4x3 hold the first 3 rows of the Mat4x4 in their columns, like transform_vec4 and the shaders read them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub location: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    #[inline]
+    pub fn new(location: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self { location, rotation, scale }
+    }
+
+    #[inline]
+    pub fn identity() -> Self {
+        Self::new(VEC3_ZERO, quat_identity(), VEC3_ONE)
+    }
+
+    #[inline]
+    pub fn from_location(location: Vec3) -> Self {
+        Self { location, ..Self::identity() }
+    }
+
+    #[inline]
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self { rotation, ..Self::identity() }
+    }
+
+    #[inline]
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self { scale, ..Self::identity() }
+    }
+
+    /// Placed at eye with its -Z axis toward target, like a camera of glm::look_at. up must not be along the view direction.
+    pub fn look_at(eye: &Vec3, target: &Vec3, up: &Vec3) -> Self {
+        let z = normalize(&(eye - target));
+        let x = normalize(&cross(up, &z));
+        let y = cross(&z, &x);
+        Self::from_rotation(mat3_to_quat(&Mat3::from_columns(&[x, y, z]))).with_location(*eye)
+    }
+
+    #[inline]
+    pub fn with_location(self, location: Vec3) -> Self {
+        Self { location, ..self }
+    }
+
+    #[inline]
+    pub fn has_uniform_scale(&self) -> bool {
+        (self.scale.x - self.scale.y).abs() <= DECOMPOSE_EPSILON && (self.scale.x - self.scale.z).abs() <= DECOMPOSE_EPSILON
+    }
+
+    #[inline]
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        self.location + self.transform_vector(point)
+    }
+
+    #[inline]
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        quat_rotate_vec3(&self.rotation, &self.scale.component_mul(vector))
+    }
+
+    // Exact with any scale, unlike the inverse
+    #[inline]
+    pub fn inverse_transform_point(&self, point: &Vec3) -> Vec3 {
+        self.inverse_transform_vector(&(point - self.location))
+    }
+
+    #[inline]
+    pub fn inverse_transform_vector(&self, vector: &Vec3) -> Vec3 {
+        quat_rotate_vec3(&quat_conjugate(&self.rotation), vector).component_div(&self.scale)
+    }
+
+    // Box around the transformed box
+    pub fn transform_aabb(&self, aabb: &AABB) -> AABB {
+        let linear = abs(&(quat_to_mat3(&self.rotation) * Mat3::from_diagonal(&self.scale)));
+        AABB::from_center_extents(&self.transform_point(&aabb.center()), &(linear * aabb.extents()))
+    }
+
+    /// Only exact with a uniform scale, a non uniform scale followed by a rotation is a shear in the inverse.
+    /// Use to_inverse_mat4x4 or inverse_transform_point when it matters.
+    pub fn inverse(&self) -> Self {
+        let rotation = quat_conjugate(&self.rotation);
+        let scale = VEC3_ONE.component_div(&self.scale);
+        let location = -quat_rotate_vec3(&rotation, &self.location).component_mul(&scale);
+        Self { location, rotation, scale }
+    }
+
+    pub fn to_mat4x4(&self) -> Mat4x4 {
+        let linear = quat_to_mat3(&self.rotation) * Mat3::from_diagonal(&self.scale);
+        let mut matrix = linear.to_homogeneous();
+        matrix.fixed_view_mut::<3, 1>(0, 3).copy_from(&self.location);
+        matrix
+    }
+
+    pub fn to_mat4x3(&self) -> Mat4x3 {
+        mat4x4_to_mat4x3(&self.to_mat4x4())
+    }
+
+    // Exact inverse of to_mat4x4, whatever the scale
+    pub fn to_inverse_mat4x4(&self) -> Mat4x4 {
+        let rotation = quat_to_mat3(&self.rotation);
+        let mut matrix = Mat4x4::identity();
+        for row in 0..3 {
+            let axis = rotation.column(row) / self.scale[row];
+            for column in 0..3 {
+                matrix[(row, column)] = axis[column];
+            }
+            matrix[(row, 3)] = -dot(&axis, &self.location);
+        }
+        matrix
+    }
+
+    pub fn to_inverse_mat4x3(&self) -> Mat4x3 {
+        mat4x4_to_mat4x3(&self.to_inverse_mat4x4())
+    }
+
+    /// Splits an affine matrix, a negative determinant gives a negative X scale
+    pub fn from_mat4x4(matrix: &Mat4x4) -> Result<Self, DecomposeError> {
+        if matrix.row(3) != Vec4::new(0.0, 0.0, 0.0, 1.0).transpose() {
+            return Err(DecomposeError::Projective);
+        }
+
+        let mut axes = [VEC3_ZERO; 3];
+        let mut scale = VEC3_ZERO;
+        for (index, axis) in axes.iter_mut().enumerate() {
+            *axis = matrix.fixed_view::<3, 1>(0, index).into_owned();
+            scale[index] = length(axis);
+            if scale[index] <= DECOMPOSE_EPSILON {
+                return Err(DecomposeError::ZeroScale);
+            }
+            *axis /= scale[index];
+        }
+        if [(0, 1), (0, 2), (1, 2)].iter().any(|(a, b)| dot(&axes[*a], &axes[*b]).abs() > DECOMPOSE_EPSILON) {
+            return Err(DecomposeError::Shear);
+        }
+        if dot(&cross(&axes[0], &axes[1]), &axes[2]) < 0.0 {
+            axes[0] = -axes[0];
+            scale.x = -scale.x;
+        }
+
+        let rotation = quat_normalize(&mat3_to_quat(&Mat3::from_columns(&axes)));
+        Ok(Self { location: matrix.fixed_view::<3, 1>(0, 3).into_owned(), rotation, scale })
+    }
+
+    pub fn from_mat4x3(matrix: &Mat4x3) -> Result<Self, DecomposeError> {
+        let mut matrix4 = Mat4x4::identity();
+        for row in 0..3 {
+            matrix4.set_row(row, &matrix.column(row).transpose());
+        }
+        Self::from_mat4x4(&matrix4)
+    }
+
+    // Rotations are normalized linear interpolations, faster than slerp and close to it for near rotations
+    pub fn lerp(&self, other: &Transform, t: f32) -> Self {
+        let other_rotation = shortest_rotation(&self.rotation, &other.rotation);
+        Self {
+            location: lerp(&self.location, &other.location, t),
+            rotation: quat_normalize(&(self.rotation * (1.0 - t) + other_rotation * t)),
+            scale: lerp(&self.scale, &other.scale, t),
+        }
+    }
+
+    // Rotations at constant angular speed
+    pub fn slerp(&self, other: &Transform, t: f32) -> Self {
+        let other_rotation = shortest_rotation(&self.rotation, &other.rotation);
+        let cos_angle = self.rotation.dot(&other_rotation).min(1.0);
+        if cos_angle > 1.0 - DECOMPOSE_EPSILON {
+            return self.lerp(other, t);
+        }
+        let angle = cos_angle.acos();
+        let sin_angle = angle.sin();
+        let rotation = self.rotation * (((1.0 - t) * angle).sin() / sin_angle) + other_rotation * ((t * angle).sin() / sin_angle);
+        Self { rotation: quat_normalize(&rotation), ..self.lerp(other, t) }
+    }
+}
+
+// Same rotation in the hemisphere of from, so interpolations take the shortest way
+#[inline]
+fn shortest_rotation(from: &Quat, to: &Quat) -> Quat {
+    match from.dot(to) < 0.0 {
+        true => -to,
+        false => *to,
+    }
+}
+
+#[inline]
+fn mat4x4_to_mat4x3(matrix: &Mat4x4) -> Mat4x3 {
+    Mat4x3::from_columns(&[matrix.row(0).transpose(), matrix.row(1).transpose(), matrix.row(2).transpose()])
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// `a * b` applies b then a. The scales are multiplied, exact unless a has a non uniform scale and b a rotation.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        Transform {
+            location: self.transform_point(&other.location),
+            rotation: quat_normalize(&(self.rotation * other.rotation)),
+            scale: self.scale.component_mul(&other.scale),
+        }
+    }
+}
+
+impl Serializable for Transform {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        ar.serialize(&mut self.location)?;
+        ar.serialize(&mut self.rotation.coords)?;
+        ar.serialize(&mut self.scale)
+    }
+}
@@ -0,0 +1,222 @@
+use std::mem::offset_of;
+use std::sync::Arc;
+use rl_core::{Archive, ArchiveReader, ArchiveWriter, ArchiveResult, AlignedBuffer, Array, ArchivedArray, AssetError, AssetHandle, AssetLoader, AssetManager, LoadContext, LoadState, Map, RString, Relocatable, StringAtom, Validator, access_root, access_root_unchecked};
+use crate::SDFPrimitivesList;
+
+/// GLSL source, its `#include "file"` are loaded as dependencies
+pub struct ShaderSource {
+    source: RString,
+    includes: Array<AssetHandle<ShaderSource>>,
+}
+
+impl ShaderSource {
+    #[inline]
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    #[inline]
+    pub fn get_includes(&self) -> &[AssetHandle<ShaderSource>] {
+        &self.includes
+    }
+}
+
+// Included paths are relative to the directory of the including file
+fn resolve_include(path: &str, include: &str) -> String {
+    let directory = match path.rfind('/') {
+        Some(index) => &path[..=index],
+        None => "",
+    };
+    format!("{}{}", directory, include)
+}
+
+pub struct ShaderSourceLoader;
+
+impl AssetLoader for ShaderSourceLoader {
+    type Asset = ShaderSource;
+
+    fn load(&self, data: &[u8], context: &mut LoadContext) -> Result<ShaderSource, AssetError> {
+        let mut source = RString::new();
+        source.push_utf8(data).map_err(|error| AssetError::Load(error.to_string()))?;
+
+        let path = context.get_path().to_string();
+        let mut includes = Array::new();
+        for line in source.lines() {
+            let Some(include) = line.trim_start().strip_prefix("#include") else {
+                continue;
+            };
+            let include = include.trim();
+            let Some(include) = include.strip_prefix('"').and_then(|include| include.strip_suffix('"')) else {
+                return Err(AssetError::Load(format!("Invalid include {}", include)));
+            };
+            includes.push_back(context.load_dependency::<ShaderSource>(&resolve_include(&path, include)));
+        }
+        Ok(ShaderSource { source, includes })
+    }
+}
+
+/// SPIR-V compiled at runtime from a GLSL file, its source and includes are dependencies
+/// so the shader is compiled again when one of them is reloaded
+pub struct CompiledShader {
+    spirv: Array<u32>,
+}
+
+impl CompiledShader {
+    #[inline]
+    pub fn get_spirv(&self) -> &[u32] {
+        &self.spirv
+    }
+}
+
+// The stage comes from the extension, sources in .glsl files are compute shaders unless they have a #pragma shader_stage
+fn get_shader_kind(path: &str) -> shaderc::ShaderKind {
+    match path.rsplit('.').next() {
+        Some("vert") => shaderc::ShaderKind::Vertex,
+        Some("frag") => shaderc::ShaderKind::Fragment,
+        Some("comp") => shaderc::ShaderKind::Compute,
+        _ => shaderc::ShaderKind::DefaultCompute,
+    }
+}
+
+// Waits for the source and its includes
+fn collect_shader_sources(source: &AssetHandle<ShaderSource>, sources: &mut Map<StringAtom, Arc<ShaderSource>>) -> Result<(), AssetError> {
+    if sources.contains(&source.get_path()) {
+        return Ok(());
+    }
+    if let LoadState::Failed(error) = source.wait() {
+        return Err(AssetError::Dependency { path: source.get_path(), error: Box::new(error) });
+    }
+    let loaded = source.get().unwrap();
+    sources.insert(source.get_path(), loaded.clone());
+    loaded.get_includes().iter().try_for_each(|include| collect_shader_sources(include, sources))
+}
+
+pub struct CompiledShaderLoader {
+    compiler: shaderc::Compiler,
+    defines: Array<(String, String)>,
+}
+
+impl CompiledShaderLoader {
+    pub fn new() -> Self {
+        CompiledShaderLoader { compiler: shaderc::Compiler::new().unwrap(), defines: Array::new() }
+    }
+
+    // Defined in all the compiled shaders, like the define of vulkano_shaders::shader!
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push_back((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Default for CompiledShaderLoader {
+    fn default() -> Self {
+        CompiledShaderLoader::new()
+    }
+}
+
+impl AssetLoader for CompiledShaderLoader {
+    type Asset = CompiledShader;
+
+    fn load(&self, _data: &[u8], context: &mut LoadContext) -> Result<CompiledShader, AssetError> {
+        let path = context.get_path().to_string();
+        let source = context.load_dependency::<ShaderSource>(&path);
+        let mut sources = Map::new();
+        collect_shader_sources(&source, &mut sources)?;
+
+        let mut options = shaderc::CompileOptions::new().ok_or(AssetError::Load("Can't create the shader compile options".to_string()))?;
+        for (name, value) in self.defines.iter() {
+            options.add_macro_definition(name, Some(value.as_str()));
+        }
+        options.set_include_callback(|include, _include_type, including, _depth| {
+            let include_path = resolve_include(including, include);
+            match sources.get(&StringAtom::from(include_path.as_str())) {
+                Some(include) => Ok(shaderc::ResolvedInclude { resolved_name: include_path, content: include.get_source().to_string() }),
+                None => Err(format!("{} isn't loaded", include_path)),
+            }
+        });
+
+        let source = sources.get(&context.get_path()).unwrap().get_source();
+        let artifact = self.compiler.compile_into_spirv(source, get_shader_kind(&path), &path, "main", Some(&options))
+            .map_err(|error| AssetError::Load(error.to_string()))?;
+        Ok(CompiledShader { spirv: artifact.as_binary().iter().collect() })
+    }
+}
+
+const SDF_SCENE_TAG: [u8; 4] = *b"SDFS";
+
+// Primitives saved in a versioned chunk of an archive
+pub struct SDFSceneLoader;
+
+impl SDFSceneLoader {
+    pub fn save(primitives: &mut SDFPrimitivesList) -> ArchiveResult<Array<u8>> {
+        let mut writer = ArchiveWriter::new();
+        writer.chunk(SDF_SCENE_TAG, |ar| ar.serialize_versioned(primitives))?;
+        Ok(writer.finish())
+    }
+}
+
+impl AssetLoader for SDFSceneLoader {
+    type Asset = SDFPrimitivesList;
+
+    fn load(&self, data: &[u8], _context: &mut LoadContext) -> Result<SDFPrimitivesList, AssetError> {
+        let mut reader = ArchiveReader::new(data)?;
+        let mut primitives = SDFPrimitivesList::default();
+        if !reader.chunk(SDF_SCENE_TAG, |ar| ar.serialize_versioned(&mut primitives))? {
+            return Err(AssetError::Load("No SDF scene in the archive".to_string()));
+        }
+        Ok(primitives)
+    }
+}
+
+/// Root of the relocatable data of a baked volume, distances are stored x first then y then z
+#[repr(C)]
+pub struct BakedVolumeData {
+    pub dims: [u32; 3],
+    pub voxel_size: f32,
+    pub origin: [f32; 3],
+    pub _padding: u32,
+    pub distances: ArchivedArray<f32>,
+}
+
+unsafe impl Relocatable for BakedVolumeData {
+    fn validate(validator: &Validator, pos: usize) -> ArchiveResult<()> {
+        validator.check::<ArchivedArray<f32>>(pos + offset_of!(BakedVolumeData, distances))
+    }
+}
+
+/// Baked volume used in place in its loaded buffer
+pub struct BakedVolume {
+    buffer: AlignedBuffer,
+}
+
+impl BakedVolume {
+    #[inline]
+    pub fn get_data(&self) -> &BakedVolumeData {
+        // Validated by the loader
+        unsafe{ access_root_unchecked(&self.buffer) }
+    }
+}
+
+pub struct BakedVolumeLoader;
+
+impl AssetLoader for BakedVolumeLoader {
+    type Asset = BakedVolume;
+
+    fn load(&self, data: &[u8], _context: &mut LoadContext) -> Result<BakedVolume, AssetError> {
+        // The loaded bytes aren't aligned, the copy is
+        let buffer = AlignedBuffer::from_slice(data);
+        let volume = access_root::<BakedVolumeData>(&buffer)?;
+        let voxels_num = volume.dims.iter().map(|dim| *dim as usize).product::<usize>();
+        if volume.distances.num() != voxels_num {
+            return Err(AssetError::Load(format!("{} distances for {} voxels", volume.distances.num(), voxels_num)));
+        }
+        Ok(BakedVolume { buffer })
+    }
+}
+
+pub fn register_asset_loaders(assets: &AssetManager) {
+    assets.register_loader(ShaderSourceLoader);
+    assets.register_loader(CompiledShaderLoader::new().with_define("GROUP_SIZE", "4"));
+    assets.register_loader(SDFSceneLoader);
+    assets.register_loader(BakedVolumeLoader);
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
-use std::mem::MaybeUninit;
 use nalgebra_glm::Vec3;
+use rl_core::{Array, CVars, JobSystem, StringAtom};
 use rl_math::{AABB, VEC3_ONE};
 use crate::{SDFPrimitivesList, cs_globalsdf};
 
@@ -23,13 +23,52 @@ use vulkano::{
     VulkanLibrary,
 };
 
-const GLOBALSDF_CASCADE_SIZE: usize = 128;
-const GLOBALSDF_CHUNKS_PER_SIDE: usize = 4;
+const DEFAULT_CASCADE_SIZE: i64 = 128;
+const DEFAULT_CHUNKS_PER_SIDE: i64 = 4;
 
-const GLOBALSDF_CHUNK_SIZE: usize = GLOBALSDF_CASCADE_SIZE / GLOBALSDF_CHUNKS_PER_SIDE;
-const GLOBALSDF_CHUNKS_NUM: usize = GLOBALSDF_CHUNKS_PER_SIDE * GLOBALSDF_CHUNKS_PER_SIDE * GLOBALSDF_CHUNKS_PER_SIDE;
+// Tunables, changed from the console, a config file or the command line.
+// Registered once at startup, before the cascades are built.
+pub fn register_globalsdf_cvars() {
+    let cvars = CVars::global();
+    cvars.register_int(StringAtom::new(b"r.globalsdf.cascade_size"), DEFAULT_CASCADE_SIZE, 16..=1024, "Voxels per side of a global SDF cascade");
+    cvars.register_int(StringAtom::new(b"r.globalsdf.chunks_per_side"), DEFAULT_CHUNKS_PER_SIDE, 1..=16, "Chunks per side of a cascade, must divide the cascade size");
+    cvars.register_int(StringAtom::new(b"r.globalsdf.max_dist_voxels"), 4, 1..=32, "Distance stored around the primitives, in voxels");
+}
+
+pub(crate) struct GlobalSDFSettings {
+    pub cascade_size: usize,
+    pub chunks_per_side: usize,
+    pub max_dist_voxels: i32,
+}
+
+impl GlobalSDFSettings {
+    // Each cvar is valid on its own, a cascade size the chunks don't divide falls back to the default sizes
+    pub fn from_cvars() -> Self {
+        let cvars = CVars::global();
+        let mut settings = Self {
+            cascade_size: cvars.get_int(StringAtom::new(b"r.globalsdf.cascade_size")) as usize,
+            chunks_per_side: cvars.get_int(StringAtom::new(b"r.globalsdf.chunks_per_side")) as usize,
+            max_dist_voxels: cvars.get_int(StringAtom::new(b"r.globalsdf.max_dist_voxels")) as i32,
+        };
+        if settings.cascade_size % settings.chunks_per_side != 0 {
+            log::warn!("The global SDF cascade size {} isn't a multiple of the chunks per side {}, using {} and {}",
+                settings.cascade_size, settings.chunks_per_side, DEFAULT_CASCADE_SIZE, DEFAULT_CHUNKS_PER_SIDE);
+            settings.cascade_size = DEFAULT_CASCADE_SIZE as usize;
+            settings.chunks_per_side = DEFAULT_CHUNKS_PER_SIDE as usize;
+        }
+        settings
+    }
 
-pub(crate) const GLOBALSDF_MAX_DIST_VOXELS: i32 = 4;
+    #[inline]
+    pub fn chunk_size(&self) -> usize {
+        self.cascade_size / self.chunks_per_side
+    }
+
+    #[inline]
+    pub fn chunks_num(&self) -> usize {
+        self.chunks_per_side * self.chunks_per_side * self.chunks_per_side
+    }
+}
 
 struct GlobalSDFChunk {
     aabb: AABB,
@@ -38,8 +77,9 @@ struct GlobalSDFChunk {
 }
 
 impl GlobalSDFChunk {
-    pub fn new(cascade_primitives: &SDFPrimitivesList, cascade_voxel_size: f32, aabb: AABB) -> Self {
-        let extended_aabb = aabb.expand(cascade_voxel_size * (GLOBALSDF_MAX_DIST_VOXELS as f32));
+    pub fn new(cascade_primitives: &SDFPrimitivesList, cascade_voxel_size: f32, max_dist_voxels: i32, aabb: AABB) -> Self {
+        rl_core::profile_scope!("Build global SDF chunk");
+        let extended_aabb = aabb.expand(cascade_voxel_size * (max_dist_voxels as f32));
 
         let mut primitives = cascade_primitives.cull(&extended_aabb);
         primitives.sort_by_group_id();
@@ -56,40 +96,45 @@ pub struct GlobalSDFCascade {
     voxel_size: f32,
     aabb: AABB,
     extended_aabb: AABB,
-    chunks: [MaybeUninit<GlobalSDFChunk>; GLOBALSDF_CHUNKS_NUM],
+    chunks: Array<GlobalSDFChunk>,
 }
 
 impl GlobalSDFCascade {
     pub fn new(scene_primitives: &SDFPrimitivesList, aabb: AABB) -> Self {
-        let voxel_size = aabb.size().x / (GLOBALSDF_CASCADE_SIZE as f32);
-        let extended_aabb = aabb.expand(voxel_size * (GLOBALSDF_MAX_DIST_VOXELS as f32));
+        rl_core::profile_scope!("Build global SDF cascade");
+        let settings = GlobalSDFSettings::from_cvars();
+        let chunks_per_side = settings.chunks_per_side;
+
+        let voxel_size = aabb.size().x / (settings.cascade_size as f32);
+        let extended_aabb = aabb.expand(voxel_size * (settings.max_dist_voxels as f32));
 
         let primitives = scene_primitives.cull(&extended_aabb);
 
-        let chunk_size = voxel_size * (GLOBALSDF_CHUNK_SIZE as f32);
+        let chunk_size = voxel_size * (settings.chunk_size() as f32);
         let half_chunk_size = chunk_size * 0.5;
         let chunk_extends = VEC3_ONE * half_chunk_size;
 
         let first_chunk_center = aabb.min + VEC3_ONE * half_chunk_size;
 
-        let mut chunks: [MaybeUninit<GlobalSDFChunk>; GLOBALSDF_CHUNKS_NUM] = unsafe {
-            MaybeUninit::uninit().assume_init()
-        };
-        for i in 0..GLOBALSDF_CHUNKS_NUM {
-            let chunk_x = i % GLOBALSDF_CHUNKS_PER_SIDE;
-            let chunk_y = (i / GLOBALSDF_CHUNKS_PER_SIDE) % GLOBALSDF_CHUNKS_PER_SIDE;
-            let chunk_z = i / (GLOBALSDF_CHUNKS_PER_SIDE * GLOBALSDF_CHUNKS_PER_SIDE);
+        let mut chunks: Array<Option<GlobalSDFChunk>> = (0..settings.chunks_num()).map(|_| None).collect();
+        // Chunks only read the cascade primitives, build them on the workers
+        JobSystem::global().parallel_for_slice(&mut chunks, 1, |i, chunk| {
+            let chunk_x = i % chunks_per_side;
+            let chunk_y = (i / chunks_per_side) % chunks_per_side;
+            let chunk_z = i / (chunks_per_side * chunks_per_side);
 
             let chunk_center = first_chunk_center + Vec3::new
                 ( chunk_size * (chunk_x as f32)
                 , chunk_size * (chunk_y as f32)
                 , chunk_size * (chunk_z as f32));
 
-            chunks[i].write(GlobalSDFChunk::new(
+            *chunk = Some(GlobalSDFChunk::new(
                 &primitives,
                 voxel_size,
+                settings.max_dist_voxels,
                 AABB::from_center_extents(&chunk_center, &chunk_extends)));
-        }
+        });
+        let chunks = chunks.iter_mut().map(|chunk| chunk.take().unwrap()).collect();
 
         Self {
             voxel_size,
@@ -108,8 +153,7 @@ impl GlobalSDFCascade {
         A: DescriptorSetAllocator
     {
         // TODO: should use a BumpAllocator for allocating (buffer_allocator) the primitives buffers, release it when finished
-        for elem in &self.chunks[..] {
-            let chunk = unsafe{ elem.assume_init_ref() };
+        for chunk in self.chunks.iter() {
 /*
             let data_buffer =
                 Buffer::new_slice::<crate::cs_globalsdf::SDFPrimitive>(
@@ -4,17 +4,32 @@ pub use sdfshapes::SDFShape;
 pub use sdfshapes::SDFPrimitive;
 pub use sdfshapes::SDFPrimitivesList;
 
+mod sdfcommands;
+
+pub use sdfcommands::{AddSDFPrimitive, RemoveSDFPrimitive, TransformSDFPrimitive};
+
 mod globalsdf;
 
 pub use globalsdf::GlobalSDFCascade;
+pub use globalsdf::register_globalsdf_cvars;
+
+mod assets;
+
+pub use assets::{ShaderSource, ShaderSourceLoader};
+pub use assets::{CompiledShader, CompiledShaderLoader};
+pub use assets::SDFSceneLoader;
+pub use assets::{BakedVolume, BakedVolumeData, BakedVolumeLoader};
+pub use assets::register_asset_loaders;
 
 pub mod cs_globalsdf {
     use std::sync::Arc;
     use vulkano::{
         device::Device,
         pipeline::ComputePipeline,
+        shader::ShaderModule,
     };
-    use crate::globalsdf::GLOBALSDF_MAX_DIST_VOXELS;
+    use crate::CompiledShader;
+    use crate::globalsdf::GlobalSDFSettings;
 
     vulkano_shaders::shader! {
         ty: "compute",
@@ -25,22 +40,30 @@ pub mod cs_globalsdf {
         //dump: true,
     }
 
-    fn create_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
-        let pipeline = {
-            let shader = load(device.clone()).unwrap();
-            let spec_consts = SpecializationConstants {
-                GLOBALSDF_MAX_DIST_VOXELS,
-            };
-            ComputePipeline::new(
-                device.clone(),
-                shader.entry_point("main").unwrap(),
-                &spec_consts,
-                None,
-                |_| {},
-            )
-            .unwrap()
+    pub fn create_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
+        let shader = load(device.clone()).unwrap();
+        create_pipeline_with_shader(device, shader).unwrap()
+    }
+
+    // From the shader compiled again by the hot reload, None if it doesn't match the layout built in
+    pub fn create_hot_reloaded_pipeline(device: Arc<Device>, shader: &CompiledShader) -> Option<Arc<ComputePipeline>> {
+        let shader = unsafe { ShaderModule::from_words(device.clone(), shader.get_spirv()) }.ok()?;
+        create_pipeline_with_shader(device, shader)
+    }
+
+    fn create_pipeline_with_shader(device: Arc<Device>, shader: Arc<ShaderModule>) -> Option<Arc<ComputePipeline>> {
+        // Baked in the pipeline, changing the CVar needs the pipeline to be created again
+        let spec_consts = SpecializationConstants {
+            GLOBALSDF_MAX_DIST_VOXELS: GlobalSDFSettings::from_cvars().max_dist_voxels,
         };
-        pipeline
+        ComputePipeline::new(
+            device,
+            shader.entry_point("main")?,
+            &spec_consts,
+            None,
+            |_| {},
+        )
+        .ok()
     }
 }
 
@@ -69,3 +92,6 @@ impl From<&SDFPrimitive> for cs_globalsdf::SDFPrimitive {
 }
 
 //mod sdfscene;
+
+#[cfg(test)]
+mod tests;
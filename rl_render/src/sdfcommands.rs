@@ -0,0 +1,95 @@
+use std::any::Any;
+use rl_math::Transform;
+use rl_core::UndoableCommand;
+use crate::{SDFPrimitive, SDFPrimitivesList};
+
+// Undoable edits of a SDFPrimitivesList, built before being executed on the list they were created from
+
+pub struct AddSDFPrimitive {
+    index: usize,
+    primitive: SDFPrimitive,
+}
+
+impl AddSDFPrimitive {
+    pub fn new(index: usize, primitive: SDFPrimitive) -> Self {
+        Self { index, primitive }
+    }
+}
+
+impl UndoableCommand<SDFPrimitivesList> for AddSDFPrimitive {
+    fn get_name(&self) -> &str {
+        "Add primitive"
+    }
+
+    fn apply(&mut self, list: &mut SDFPrimitivesList) {
+        list.insert(self.index, self.primitive.clone());
+    }
+
+    fn revert(&mut self, list: &mut SDFPrimitivesList) {
+        list.remove(self.index);
+    }
+}
+
+pub struct RemoveSDFPrimitive {
+    index: usize,
+    primitive: SDFPrimitive,
+}
+
+impl RemoveSDFPrimitive {
+    pub fn new(list: &SDFPrimitivesList, index: usize) -> Self {
+        Self { index, primitive: list.get(index).clone() }
+    }
+}
+
+impl UndoableCommand<SDFPrimitivesList> for RemoveSDFPrimitive {
+    fn get_name(&self) -> &str {
+        "Remove primitive"
+    }
+
+    fn apply(&mut self, list: &mut SDFPrimitivesList) {
+        list.remove(self.index);
+    }
+
+    fn revert(&mut self, list: &mut SDFPrimitivesList) {
+        list.insert(self.index, self.primitive.clone());
+    }
+}
+
+// Consecutive transforms of the same primitive merge, a gizmo drag is a single undo entry
+pub struct TransformSDFPrimitive {
+    index: usize,
+    before: SDFPrimitive,
+    after: SDFPrimitive,
+}
+
+impl TransformSDFPrimitive {
+    pub fn new(list: &SDFPrimitivesList, index: usize, transform: &Transform) -> Self {
+        let before = list.get(index).clone();
+        let after = before.with_transform(transform);
+        Self { index, before, after }
+    }
+}
+
+impl UndoableCommand<SDFPrimitivesList> for TransformSDFPrimitive {
+    fn get_name(&self) -> &str {
+        "Transform primitive"
+    }
+
+    fn apply(&mut self, list: &mut SDFPrimitivesList) {
+        list.replace(self.index, self.after.clone());
+    }
+
+    fn revert(&mut self, list: &mut SDFPrimitivesList) {
+        list.replace(self.index, self.before.clone());
+    }
+
+    fn merge(&mut self, next: &dyn UndoableCommand<SDFPrimitivesList>) -> bool {
+        match (next as &dyn Any).downcast_ref::<Self>() {
+            Some(next) if next.index == self.index => {
+                self.after = next.after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,11 +1,12 @@
 use core::iter::IntoIterator;
 use std::ops::Index;
 use nalgebra_glm::{Vec3, Mat4x3, Mat4x4, inverse};
-use rl_core::Array;
-use rl_math::{AABB, VEC3_ZERO, VEC3_ONE, VEC3_HALF};
+use rl_core::{Archive, ArchiveError, ArchiveResult, Array, Serializable};
+use rl_math::{AABB, Transform, VEC3_ZERO, VEC3_ONE, VEC3_HALF};
 use crate::cs_globalsdf::SDFPrimitive as SDFPrimitiveGPU;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SDFShape {
     Sphere{ radius: f32 },
     Box{ half_size: Vec3 },
@@ -29,7 +30,42 @@ impl SDFShape {
     }
 }
 
+// Placeholder read over when loading
+impl Default for SDFShape {
+    fn default() -> Self {
+        SDFShape::Sphere { radius: 0.0 }
+    }
+}
+
+impl Serializable for SDFShape {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        let mut tag = match self {
+            SDFShape::Sphere { .. } => 0u8,
+            SDFShape::Box { .. } => 1u8,
+            SDFShape::RoundedBox { .. } => 2u8,
+        };
+        ar.serialize(&mut tag)?;
+        if ar.is_loading() {
+            *self = match tag {
+                0 => SDFShape::Sphere { radius: 0.0 },
+                1 => SDFShape::Box { half_size: VEC3_ZERO },
+                2 => SDFShape::RoundedBox { half_size: VEC3_ZERO, radius: 0.0 },
+                _ => return Err(ArchiveError::InvalidData("unknown SDF shape")),
+            };
+        }
+        match self {
+            SDFShape::Sphere { radius } => ar.serialize(radius),
+            SDFShape::Box { half_size } => ar.serialize(half_size),
+            SDFShape::RoundedBox { half_size, radius } => {
+                ar.serialize(half_size)?;
+                ar.serialize(radius)
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SDFPrimitive {
     shape: SDFShape,
 
@@ -42,21 +78,22 @@ pub struct SDFPrimitive {
 }
 
 impl SDFPrimitive {
-    pub fn new(shape: SDFShape, transform: &Mat4x4, group_id: u32) -> Self {
-        let inv_transform = inverse(transform);
-        let xform = Mat4x3::from_columns(
-            &[transform.column(0)
-            , transform.column(1)
-            , transform.column(2)]);
-        let inv_xform = Mat4x3::from_columns(
-            &[inv_transform.column(0)
-            , inv_transform.column(1)
-            , inv_transform.column(2)]);
-
-        let aabb = shape.get_local_aabb().transform(&xform);
+    pub fn new(shape: SDFShape, transform: &Transform, group_id: u32) -> Self {
+        Self::from_parts(shape, &transform.to_mat4x3(), transform.to_inverse_mat4x3(), group_id)
+    }
+
+    // For the affine matrices a Transform can't hold, like shears
+    pub fn from_matrix(shape: SDFShape, transform: &Mat4x4, group_id: u32) -> Self {
+        // Mat4x3 columns are the matrix rows, see Transform
+        let rows = |matrix: &Mat4x4| Mat4x3::from_columns(&[matrix.row(0).transpose(), matrix.row(1).transpose(), matrix.row(2).transpose()]);
+        Self::from_parts(shape, &rows(transform), rows(&inverse(transform)), group_id)
+    }
+
+    fn from_parts(shape: SDFShape, xform: &Mat4x3, inv_xform: Mat4x3, group_id: u32) -> Self {
+        let aabb = shape.get_local_aabb().transform(xform);
 
         let inv_dist_scaling_factor = AABB::from_center_extents(&VEC3_ZERO, &VEC3_HALF)
-            .transform(&xform)
+            .transform(xform)
             .size()
             .min();
         let distance_scaling_factor = 1.0 / inv_dist_scaling_factor;
@@ -73,6 +110,11 @@ impl SDFPrimitive {
         }
     }
 
+    // Same shape and group, placed with another transform
+    pub fn with_transform(&self, transform: &Transform) -> Self {
+        Self::new(self.shape.clone(), transform, self.group_id)
+    }
+
     pub fn get_shape(&self) -> &SDFShape {
         &self.shape
     }
@@ -81,6 +123,10 @@ impl SDFPrimitive {
         &self.inv_xform
     }
 
+    pub fn get_aabb(&self) -> &AABB {
+        &self.aabb
+    }
+
     pub fn get_dist_scaling_factor(&self) -> f32 {
         self.distance_scaling_factor
     }
@@ -90,6 +136,23 @@ impl SDFPrimitive {
     }
 }
 
+impl Default for SDFPrimitive {
+    fn default() -> Self {
+        SDFPrimitive::new(SDFShape::default(), &Transform::identity(), 0)
+    }
+}
+
+// The derived values are saved too, loading doesn't invert the transforms again
+impl Serializable for SDFPrimitive {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        ar.serialize(&mut self.shape)?;
+        ar.serialize(&mut self.inv_xform)?;
+        ar.serialize(&mut self.aabb)?;
+        ar.serialize(&mut self.distance_scaling_factor)?;
+        ar.serialize(&mut self.group_id)
+    }
+}
+
 pub struct SendSDFPrimitivesToGPUIter<'a> {
     list: &'a Array<SDFPrimitive>,
     index: usize,
@@ -121,11 +184,29 @@ pub struct SDFPrimitivesList {
 }
 
 impl SDFPrimitivesList {
-    pub fn add(&mut self, shape: SDFShape, transform: &Mat4x4, group_id: u32) {
+    pub fn add(&mut self, shape: SDFShape, transform: &Transform, group_id: u32) {
         self.primitives.push_back(SDFPrimitive::new(shape, transform, group_id));
     }
 
+    pub fn insert(&mut self, index: usize, primitive: SDFPrimitive) {
+        self.primitives.insert(index, primitive);
+    }
+
+    pub fn remove(&mut self, index: usize) -> SDFPrimitive {
+        self.primitives.remove(index)
+    }
+
+    // Returns the previous primitive
+    pub fn replace(&mut self, index: usize, primitive: SDFPrimitive) -> SDFPrimitive {
+        std::mem::replace(&mut self.primitives[index], primitive)
+    }
+
+    pub fn get(&self, index: usize) -> &SDFPrimitive {
+        &self.primitives[index]
+    }
+
     pub fn cull(&self, aabb: &AABB) -> SDFPrimitivesList {
+        rl_core::profile_scope!("Cull SDF primitives");
         // TODO: parallelize this and make it possible to reuse already allocated arrays?
         let culled_primitives = self.primitives
             .iter()
@@ -149,7 +230,17 @@ impl SDFPrimitivesList {
         self.primitives.num() as u32
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &SDFPrimitive> {
+        self.primitives.iter()
+    }
+
     pub fn send_to_gpu(&self) -> SendSDFPrimitivesToGPUIter {
         SendSDFPrimitivesToGPUIter { list: &self.primitives, index: 0 }
     }
 }
+
+impl Serializable for SDFPrimitivesList {
+    fn serialize<A: Archive>(&mut self, ar: &mut A) -> ArchiveResult<()> {
+        ar.serialize(&mut self.primitives)
+    }
+}
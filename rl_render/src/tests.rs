@@ -0,0 +1,92 @@
+#[cfg(feature = "serde")]
+#[test]
+fn sdf_serde_test() {
+    use nalgebra_glm::Vec3;
+    use rl_math::Transform;
+    use crate::{SDFShape, SDFPrimitive};
+
+    let transform = Transform::from_location(Vec3::new(1.0, 2.0, 3.0));
+    let primitive = SDFPrimitive::new(SDFShape::RoundedBox { half_size: Vec3::new(0.5, 1.0, 1.5), radius: 0.25 }, &transform, 7);
+
+    let json = serde_json::to_string(&primitive).unwrap();
+    assert!(json.contains(r#""RoundedBox":{"half_size":[0.5,1.0,1.5],"radius":0.25}"#));
+    let from_json: SDFPrimitive = serde_json::from_str(&json).unwrap();
+    let from_ron: SDFPrimitive = ron::from_str(&ron::to_string(&primitive).unwrap()).unwrap();
+    for loaded in [from_json, from_ron] {
+        assert!(matches!(loaded.get_shape(), SDFShape::RoundedBox { radius, .. } if *radius == 0.25));
+        assert_eq!(loaded.get_inv_xform(), primitive.get_inv_xform());
+        assert_eq!(loaded.get_dist_scaling_factor(), primitive.get_dist_scaling_factor());
+        assert_eq!(loaded.get_group_id(), 7);
+    }
+}
+
+#[test]
+fn sdf_commands_test() {
+    use nalgebra_glm::Vec3;
+    use rl_core::UndoStack;
+    use rl_math::Transform;
+    use crate::{AddSDFPrimitive, RemoveSDFPrimitive, TransformSDFPrimitive, SDFPrimitive, SDFPrimitivesList, SDFShape};
+
+    let at = |x: f32| Transform::from_location(Vec3::new(x, 0.0, 0.0));
+    let sphere = |x: f32, group_id: u32| SDFPrimitive::new(SDFShape::Sphere { radius: 1.0 }, &at(x), group_id);
+    // Primitives are told apart by their group and placement
+    let is_list = |list: &SDFPrimitivesList, expected: &[SDFPrimitive]| {
+        list.count() as usize == expected.len() && list.iter().zip(expected).all(|(primitive, expected)| {
+            primitive.get_group_id() == expected.get_group_id() && primitive.get_inv_xform() == expected.get_inv_xform()
+        })
+    };
+
+    let mut list = SDFPrimitivesList::default();
+    let mut stack: UndoStack<SDFPrimitivesList> = UndoStack::new();
+    stack.execute(Box::new(AddSDFPrimitive::new(0, sphere(0.0, 1))), &mut list);
+    stack.execute(Box::new(AddSDFPrimitive::new(1, sphere(5.0, 2))), &mut list);
+    stack.execute(Box::new(AddSDFPrimitive::new(0, sphere(-5.0, 3))), &mut list);
+    assert_eq!(stack.undo_num(), 3);
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(0.0, 1), sphere(5.0, 2)]));
+
+    // Dragging a primitive is a single entry, transforming another one starts a new entry
+    for x in [1.0, 2.0, 3.0] {
+        let command = TransformSDFPrimitive::new(&list, 1, &at(x));
+        stack.execute(Box::new(command), &mut list);
+    }
+    assert_eq!(stack.undo_num(), 4);
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(3.0, 1), sphere(5.0, 2)]));
+    stack.execute(Box::new(TransformSDFPrimitive::new(&list, 2, &at(6.0))), &mut list);
+    assert_eq!(stack.undo_num(), 5);
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(3.0, 1), sphere(6.0, 2)]));
+
+    assert!(stack.undo(&mut list));
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(3.0, 1), sphere(5.0, 2)]));
+    assert!(stack.undo(&mut list));
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(0.0, 1), sphere(5.0, 2)]));
+    assert!(stack.redo(&mut list));
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(3.0, 1), sphere(5.0, 2)]));
+
+    // Removed primitives come back at their index
+    stack.execute(Box::new(RemoveSDFPrimitive::new(&list, 0)), &mut list);
+    assert!(is_list(&list, &[sphere(3.0, 1), sphere(5.0, 2)]));
+    assert!(stack.undo(&mut list));
+    assert!(is_list(&list, &[sphere(-5.0, 3), sphere(3.0, 1), sphere(5.0, 2)]));
+    assert!(stack.redo(&mut list));
+    assert!(is_list(&list, &[sphere(3.0, 1), sphere(5.0, 2)]));
+
+    while stack.undo(&mut list) { }
+    assert_eq!(list.count(), 0);
+    while stack.redo(&mut list) { }
+    assert!(is_list(&list, &[sphere(3.0, 1), sphere(5.0, 2)]));
+}
+
+#[test]
+fn sdf_primitive_matrix_test() {
+    use nalgebra_glm::{Vec3, translation};
+    use rl_math::{Transform, transform_point};
+    use crate::{SDFPrimitive, SDFShape};
+
+    // Mat4x3 columns hold the matrix rows, so a translation ends up in the fourth component of each column
+    let location = Vec3::new(1.0, 2.0, 3.0);
+    let from_matrix = SDFPrimitive::from_matrix(SDFShape::Sphere { radius: 1.0 }, &translation(&location), 0);
+    let from_transform = SDFPrimitive::new(SDFShape::Sphere { radius: 1.0 }, &Transform::from_location(location), 0);
+    assert!((from_matrix.get_inv_xform() - from_transform.get_inv_xform()).norm() < 1e-5);
+    assert!(transform_point(from_matrix.get_inv_xform(), &location).norm() < 1e-5);
+    assert!((from_matrix.get_aabb().center() - location).norm() < 1e-5);
+}
@@ -1,6 +1,6 @@
 use std::{convert::TryFrom, sync::Arc};
 
-use log::{info};
+use log::{info, warn};
 use bytemuck::{Pod, Zeroable};
 use egui::{epaint::Shadow, style::Margin, vec2, Align, Align2, Color32, Frame, Rounding, Window};
 use egui_winit_vulkano::{Gui, GuiConfig};
@@ -31,6 +31,13 @@ use vulkano_util::{
     renderer::SwapchainImageView,
     window::{VulkanoWindows, WindowDescriptor},
 };
+use rl_core::{AssetManager, CVars, CVarError, EventQueue, Profiler, UndoStack, Vfs};
+#[cfg(all(feature = "hot-reload", target_os = "linux"))]
+use rl_core::HotReloader;
+use rl_render::{CompiledShader, SDFPrimitive, SDFPrimitivesList, SDFShape};
+use rl_render::{AddSDFPrimitive, RemoveSDFPrimitive, TransformSDFPrimitive};
+use rl_math::Transform;
+use rl_ecs::{World, Schedule};
 use winit::{
     event::{Event, WindowEvent, ElementState},
     event_loop::{ControlFlow, EventLoop},
@@ -38,10 +45,64 @@ use winit::{
 
 // Render a triangle (scene) and a gui from a subpass on top of it (with some transparent fill)
 
+const ASSET_DIRECTORIES: [(&str, &str); 2] = [("shaders", "rl_render/src/shaders"), ("scenes", "scenes")];
+
+// Source directories first, then the paks/<mount>[.<patch>].pak files over them, later names overriding earlier ones
+fn mount_asset_sources(vfs: &Vfs) {
+    for (mount, directory) in ASSET_DIRECTORIES {
+        if let Err(error) = vfs.mount_directory(mount, directory, 0) {
+            warn!("{mount}:/ {error}");
+        }
+    }
+    let Ok(entries) = std::fs::read_dir("paks") else {
+        return;
+    };
+    let mut paks: Vec<_> = entries.flatten().map(|entry| entry.path()).filter(|path| path.extension().is_some_and(|extension| extension == "pak")).collect();
+    paks.sort();
+    for (index, pak) in paks.iter().enumerate() {
+        let Some(mount) = pak.file_name().and_then(|name| name.to_str()).and_then(|name| name.split('.').next()) else {
+            continue;
+        };
+        if let Err(error) = vfs.mount_pak(mount, pak, 1 + index as i32) {
+            warn!("{}: {error}", pak.display());
+        }
+    }
+}
+
 pub fn main() {
     // Init logger
     //egui_logger::init().unwrap();
 
+    // Console variables: registered defaults, then the config file, then the +name=value arguments
+    rl_render::register_globalsdf_cvars();
+    let cvars = CVars::global();
+    match cvars.load_config_file("config.cfg") {
+        Err(CVarError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(error) => warn!("config.cfg: {error}"),
+        Ok(_) => {}
+    }
+    if let Err(error) = cvars.apply_command_line(std::env::args().skip(1)) {
+        warn!("Command line: {error}");
+    }
+    mount_asset_sources(Vfs::global());
+    rl_render::register_asset_loaders(AssetManager::global());
+    // Edited sources are reloaded while running, the shaders compiled again
+    #[cfg(all(feature = "hot-reload", target_os = "linux"))]
+    let mut hot_reloader = HotReloader::new(std::time::Duration::from_millis(200)).ok();
+    #[cfg(all(feature = "hot-reload", target_os = "linux"))]
+    if let Some(hot_reloader) = &mut hot_reloader {
+        for (mount, directory) in ASSET_DIRECTORIES {
+            if let Err(error) = hot_reloader.watch(mount, directory) {
+                warn!("Hot reload of {mount}:/ {error}");
+            }
+        }
+    }
+    let globalsdf_shader = AssetManager::global().load::<CompiledShader>("shaders:/globalsdf_write_chunk.glsl");
+    let mut globalsdf_shader_version = 0;
+    let mut globalsdf_shader_error = None;
+    let mut console_input = String::new();
+    let mut console_output = String::new();
+
     // Winit event loop
     let event_loop = EventLoop::new();
     // Vulkano context
@@ -69,6 +130,15 @@ pub fn main() {
         },
     );
 
+    // Scene entities and the systems updating them
+    let mut world = World::new();
+    let mut schedule = Schedule::new();
+    // Events posted during a frame are dispatched once, after the systems ran
+    let events = EventQueue::new();
+    // Edited in the Scene window, every edit can be undone
+    let mut scene = SDFPrimitivesList::default();
+    let mut scene_history: UndoStack<SDFPrimitivesList> = UndoStack::new();
+
     // Create gui state (pass anything your state requires)
     event_loop.run(move |event, _, control_flow| {
         let renderer = windows.get_primary_renderer_mut().unwrap();
@@ -96,6 +166,12 @@ pub fn main() {
                         renderer.resize();
                     }
                     WindowEvent::CloseRequested => {
+                        // Last frames of the run, opened in Perfetto
+                        if cfg!(feature = "profiler") {
+                            if let Err(error) = Profiler::global().save_chrome_trace("profile.json") {
+                                warn!("profile.json: {error}");
+                            }
+                        }
                         *control_flow = ControlFlow::Exit;
                     }
                     WindowEvent::Focused(focused) => {
@@ -120,6 +196,11 @@ pub fn main() {
                 info!("*** Resumed");
             }
             Event::RedrawRequested(window_id) if !gui_pipeline.minimized && window_id == window_id => {
+                Profiler::global().end_frame();
+                rl_core::profile_scope!("Frame");
+                schedule.run(&mut world);
+                events.process();
+
                 // Set immediate UI in redraw here
                 gui.immediate_ui(|gui| {
                     let ctx = gui.context();
@@ -146,6 +227,92 @@ pub fn main() {
                             ui.label("Hello world!");
                             ui.label("See https://github.com/emilk/egui for how to make other UI elements");
                         });
+                    Window::new("Systems")
+                        .resizable(false)
+                        .show(&ctx, |ui| {
+                            egui::Grid::new("system_timings").striped(true).show(ui, |ui| {
+                                for (name, timing) in schedule.get_timings() {
+                                    ui.label(name.to_string());
+                                    ui.label(format!("{:.3} ms", timing.average.as_secs_f64() * 1000.0));
+                                    ui.end_row();
+                                }
+                            });
+                            // Conflicting systems run in insertion order, collected in debug builds
+                            let ambiguities = schedule.get_ambiguities();
+                            if !ambiguities.is_empty() {
+                                ui.separator();
+                                ui.label("Ambiguous orderings");
+                                for (first, second) in ambiguities.iter() {
+                                    ui.colored_label(egui::Color32::YELLOW, format!("{} before {}", first, second));
+                                }
+                            }
+                        });
+                    Window::new("Console")
+                        .default_width(400.0)
+                        .show(&ctx, |ui| {
+                            egui::ScrollArea::vertical().max_height(200.0).stick_to_bottom(true).show(ui, |ui| {
+                                ui.monospace(console_output.as_str());
+                            });
+                            let response = ui.text_edit_singleline(&mut console_input);
+                            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                                let result = match cvars.execute(&console_input) {
+                                    Ok(output) => output,
+                                    Err(error) => error.to_string(),
+                                };
+                                console_output.push_str(&format!("> {}\n{}\n", console_input, result));
+                                console_input.clear();
+                                response.request_focus();
+                            }
+                        });
+                    Window::new("Scene")
+                        .default_width(300.0)
+                        .show(&ctx, |ui| {
+                            let (undo_pressed, redo_pressed) = ui.input(|input| {
+                                let undo_pressed = input.modifiers.command && input.key_pressed(egui::Key::Z);
+                                (undo_pressed && !input.modifiers.shift, undo_pressed && input.modifiers.shift)
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.button("Add sphere").clicked() {
+                                    let index = scene.count() as usize;
+                                    let transform = Transform::from_location([index as f32, 0.0, 0.0].into());
+                                    let sphere = SDFPrimitive::new(SDFShape::Sphere { radius: 0.5 }, &transform, 0);
+                                    scene_history.execute(Box::new(AddSDFPrimitive::new(index, sphere)), &mut scene);
+                                }
+                                if ui.add_enabled(scene_history.can_undo(), egui::Button::new("Undo")).clicked() || undo_pressed {
+                                    scene_history.undo(&mut scene);
+                                }
+                                if ui.add_enabled(scene_history.can_redo(), egui::Button::new("Redo")).clicked() || redo_pressed {
+                                    scene_history.redo(&mut scene);
+                                }
+                            });
+                            let mut removed = None;
+                            for index in 0..scene.count() as usize {
+                                ui.horizontal(|ui| {
+                                    // Primitives added here only have a location, their bounds are centered on it
+                                    let mut location = scene.get(index).get_aabb().center();
+                                    let mut moved = false;
+                                    for axis in 0..3 {
+                                        let response = ui.add(egui::DragValue::new(&mut location[axis]).speed(0.05));
+                                        moved |= response.changed();
+                                        // The transforms of a drag merge in a single undo entry until it ends
+                                        if response.drag_released() || response.lost_focus() {
+                                            scene_history.seal();
+                                        }
+                                    }
+                                    if moved {
+                                        let command = TransformSDFPrimitive::new(&scene, index, &Transform::from_location(location));
+                                        scene_history.execute(Box::new(command), &mut scene);
+                                    }
+                                    if ui.button("Remove").clicked() {
+                                        removed = Some(index);
+                                    }
+                                });
+                            }
+                            if let Some(index) = removed {
+                                let command = RemoveSDFPrimitive::new(&scene, index);
+                                scene_history.execute(Box::new(command), &mut scene);
+                            }
+                        });
                     /*Window::new("Log")
                         .show(&ctx, |ui| {
                             // draws the logger ui.
@@ -169,6 +336,24 @@ pub fn main() {
                 renderer.present(after_future, true);
             }
             Event::MainEventsCleared => {
+                #[cfg(all(feature = "hot-reload", target_os = "linux"))]
+                if let Some(hot_reloader) = &mut hot_reloader {
+                    match hot_reloader.update(AssetManager::global()) {
+                        Ok(reloaded) => reloaded.iter().for_each(|path| info!("Reloading {path}")),
+                        Err(error) => warn!("Hot reload: {error}"),
+                    }
+                }
+                if globalsdf_shader.get_version() != globalsdf_shader_version {
+                    globalsdf_shader_version = globalsdf_shader.get_version();
+                    info!("{} compiled", globalsdf_shader.get_path());
+                }
+                let error = globalsdf_shader.get_reload_error().map(|error| error.to_string());
+                if error != globalsdf_shader_error {
+                    if let Some(error) = &error {
+                        warn!("{}: {error}, keeping the last compiled version", globalsdf_shader.get_path());
+                    }
+                    globalsdf_shader_error = error;
+                }
                 // Send request_redraw to render a new frame regardless
                 renderer.window().request_redraw();
             }